@@ -0,0 +1,19 @@
+#pragma plasm_functions
+
+// The fraction of the colormap of a value is normalization.x * value + normalization.y
+uniform vec4 normalization;
+uniform vec4 undefinedColor;
+
+in vec2 point;
+
+out vec4 fragmentColor;
+
+void main()
+{
+    float value = plasm_function(point.x, point.y);
+    if (isnan(value) || isinf(value)) {
+        fragmentColor = undefinedColor;
+    } else {
+        fragmentColor = vec4(plasm_colormap(normalization.x * value + normalization.y), 1.0);
+    }
+}
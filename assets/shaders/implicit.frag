@@ -0,0 +1,22 @@
+#pragma plasm_functions
+
+uniform vec4 color;
+// x is half the width of the line in pixels
+uniform vec4 width;
+
+in vec2 point;
+
+out vec4 fragmentColor;
+
+void main()
+{
+    // The curve is where the function changes sign, the value divided by its change across a pixel
+    // is the distance in pixels from the curve
+    float value = plasm_function(point.x, point.y);
+    float change = length(vec2(dFdx(value), dFdy(value)));
+    if (isnan(value) || isinf(value) || isinf(change) || change == 0.0) {
+        discard;
+    }
+    float coverage = clamp(width.x + 0.5 - abs(value) / change, 0.0, 1.0);
+    fragmentColor = vec4(color.rgb, color.a * coverage);
+}
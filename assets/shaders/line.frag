@@ -0,0 +1,11 @@
+in vec4 col;
+in vec2 distance;
+
+out vec4 fragmentColor;
+
+void main()
+{
+    // x is the distance in pixels from the middle of the line, y is half the width of the line
+    float coverage = clamp(distance.y + 0.5 - abs(distance.x), 0.0, 1.0);
+    fragmentColor = vec4(col.rgb, col.a * coverage);
+}
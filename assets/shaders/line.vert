@@ -0,0 +1,15 @@
+uniform mat4 worldViewProjectionMatrix;
+
+in vec3 position;
+in vec3 edge;
+uniform vec4 color;
+
+out vec4 col;
+out vec2 distance;
+
+void main()
+{
+    col = color;
+    distance = edge.xy;
+    gl_Position = worldViewProjectionMatrix * vec4(position, 1.0);
+}
@@ -0,0 +1,13 @@
+// x and y at the bottom left and at the top right corners of the view
+uniform vec4 view;
+
+in vec3 position;
+
+out vec2 point;
+
+void main()
+{
+    // the position covers the screen in normalized coordinates
+    point = mix(view.xy, view.zw, (position.xy + 1.0) / 2.0);
+    gl_Position = vec4(position, 1.0);
+}
@@ -0,0 +1,77 @@
+// The functions used by expressions translated to GLSL, which behave as their versions on the CPU
+
+float plasm_undefined()
+{
+    return uintBitsToFloat(0x7fc00000u);
+}
+
+float plasm_infinity()
+{
+    return uintBitsToFloat(0x7f800000u);
+}
+
+// Negative numbers only have integer powers, pow is not defined for them in GLSL
+float plasm_pow(float x, float y)
+{
+    if (x > 0.0) {
+        return pow(x, y);
+    }
+    if (x == 0.0) {
+        return y > 0.0 ? 0.0 : (y == 0.0 ? 1.0 : plasm_infinity());
+    }
+    if (y != floor(y)) {
+        return plasm_undefined();
+    }
+    float power = pow(-x, y);
+    return mod(y, 2.0) == 0.0 ? power : -power;
+}
+
+// The built-in functions are not defined outside their domain, where they may give any value instead of NaN
+float plasm_sqrt(float x)
+{
+    return x >= 0.0 ? sqrt(x) : plasm_undefined();
+}
+
+float plasm_log(float x)
+{
+    if (x > 0.0) {
+        return log(x);
+    }
+    return x == 0.0 ? -plasm_infinity() : plasm_undefined();
+}
+
+float plasm_log2(float x)
+{
+    if (x > 0.0) {
+        return log2(x);
+    }
+    return x == 0.0 ? -plasm_infinity() : plasm_undefined();
+}
+
+float plasm_asin(float x)
+{
+    return abs(x) <= 1.0 ? asin(x) : plasm_undefined();
+}
+
+float plasm_acos(float x)
+{
+    return abs(x) <= 1.0 ? acos(x) : plasm_undefined();
+}
+
+// The sign of 0 is 1
+float plasm_signum(float x)
+{
+    return x >= 0.0 ? 1.0 : (x < 0.0 ? -1.0 : x);
+}
+
+// Halfway values are rounded away from 0
+float plasm_round(float x)
+{
+    return sign(x) * floor(abs(x) + 0.5);
+}
+
+// An undefined condition makes the result undefined
+float plasm_select(float condition, float then, float otherwise)
+{
+    return isnan(condition) ? condition : (condition != 0.0 ? then : otherwise);
+}
@@ -0,0 +1,23 @@
+#pragma plasm_functions
+
+// The direction of the light, and the intensity of the ambient light in w
+uniform vec4 light;
+
+in vec3 nor;
+in float fraction;
+in float defined;
+
+out vec4 fragmentColor;
+
+void main()
+{
+    // the triangles around points where the function is not defined are left out
+    if (defined < 1.0) {
+        discard;
+    }
+    // both sides of the surface are lit
+    float diffuse = abs(dot(normalize(nor), -normalize(light.xyz)));
+    vec3 color = plasm_colormap(fraction);
+    // as lit by the directional light on the bands of the plots computed on the CPU
+    fragmentColor = vec4(color * (light.w + 0.4 * diffuse), 1.0);
+}
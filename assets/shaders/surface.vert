@@ -0,0 +1,37 @@
+#pragma plasm_functions
+
+uniform mat4 worldViewProjectionMatrix;
+// x and y of the corner of the grid, its side and the distance between its points
+uniform vec4 grid;
+// The center of the view in x, y and z, and its side
+uniform vec4 camera;
+// The fraction of the colormap of a height is normalization.x * height + normalization.y
+uniform vec4 normalization;
+
+// The point of the grid, from 0 to 1 along both sides
+in vec3 position;
+
+out vec3 nor;
+out float fraction;
+out float defined;
+
+void main()
+{
+    float x = grid.x + position.x * grid.z;
+    float y = grid.y + position.y * grid.z;
+    float height = plasm_function(x, y);
+
+    // the normal from the heights of the next points of the grid
+    float spacing = grid.w;
+    float dx = plasm_function(x + spacing, y) - height;
+    float dy = plasm_function(x, y + spacing) - height;
+    nor = normalize(vec3(-dx, spacing, dy));
+
+    defined = isnan(height) || isinf(height) ? 0.0 : 1.0;
+    height = defined > 0.0 ? height : camera.z;
+    fraction = normalization.x * height + normalization.y;
+
+    // the same normalized coordinates of the plots computed on the CPU, with the y and z axes swapped
+    vec3 normalized = 2.0 * (vec3(x, y, height) - camera.xyz) / camera.w;
+    gl_Position = worldViewProjectionMatrix * vec4(normalized.x, normalized.z, -normalized.y, 1.0);
+}
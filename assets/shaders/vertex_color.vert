@@ -0,0 +1,12 @@
+uniform mat4 worldViewProjectionMatrix;
+
+in vec3 position;
+in vec3 color;
+
+out vec4 col;
+
+void main()
+{
+    col = vec4(color, 1.0);
+    gl_Position = worldViewProjectionMatrix * vec4(position, 1.0);
+}
@@ -0,0 +1,48 @@
+// The loops which run in parallel with the parallel feature, compare them with
+// `cargo bench` and `cargo bench --features parallel`
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use plasm::operator_tables::{default_operator_table, interval_arithmetic_operator_table};
+use plasm::parser::parse;
+use plasm::plot_generator2d::{generate_2dplot, generate_2dplot_implicit, Rectangle, MAX_IMPLICIT_EVALUATIONS};
+use plasm::plotter3d::sample_grid;
+
+// The width of a full HD screen in pixels
+const RESOLUTION: u32 = 1920;
+
+fn view() -> Rectangle {
+    Rectangle { x_start: -5.0, y_start: -3.0, x_end: 5.0, y_end: 3.0 }
+}
+
+fn explicit(c: &mut Criterion) {
+    let expression = parse("sin(1/x) + sum(k, 1, 20, sin(k*x)/k)", &interval_arithmetic_operator_table()).unwrap();
+    c.bench_function("explicit columns", |b| b.iter(|| generate_2dplot(&expression, view(), RESOLUTION)));
+}
+
+fn implicit(c: &mut Criterion) {
+    let expression = parse("sin(x^2 + y^2) = cos(x y)", &interval_arithmetic_operator_table()).unwrap();
+    c.bench_function("implicit quadtree", |b| b.iter(|| generate_2dplot_implicit(&expression, view(), RESOLUTION, MAX_IMPLICIT_EVALUATIONS)));
+}
+
+fn grid(c: &mut Criterion) {
+    let expression = parse("sin(x) * cos(y) * exp(-(x^2 + y^2) / 10)", &default_operator_table()).unwrap();
+    c.bench_function("3d grid", |b| b.iter(|| sample_grid(&expression, 500, (-5.0, -5.0), 0.02)));
+}
+
+// The same points evaluated one at a time and in a batch
+fn batch(c: &mut Criterion) {
+    let expression = parse("x^2 * y - 3 * x * y^2 + sin(x) * cos(y) / (1 + x^2)", &default_operator_table()).unwrap();
+    let x: Vec<f64> = (0..100_000).map(|i| (i % 300) as f64 / 30.0).collect();
+    let y: Vec<f64> = (0..100_000).map(|i| (i / 300) as f64 / 30.0).collect();
+    let mut out = vec![0.0; x.len()];
+
+    c.bench_function("points one at a time", |b| b.iter(|| {
+        for i in 0..x.len() {
+            out[i] = expression.eval_3d(x[i], y[i]);
+        }
+    }));
+    c.bench_function("points in a batch", |b| b.iter(|| expression.eval_batch(&x, &y, &mut out)));
+}
+
+criterion_group!(benches, explicit, implicit, grid, batch);
+criterion_main!(benches);
@@ -1,3 +1,7 @@
 export function get_input_math_function() {
     return document.getElementById("input").value;
-}
\ No newline at end of file
+}
+
+export function set_info_text(text) {
+    document.getElementById("info").textContent = text;
+}
@@ -0,0 +1,342 @@
+// Rigorous analysis of 2d functions based on interval arithmetic:
+// the zeros of a function are isolated by bisection, discarding every subinterval
+// where the interval evaluation proves that there can't be any zero.
+use crate::expression::Expression;
+use crate::dual::Dual;
+use crate::interval;
+use honestintervals::IntervalSet;
+use std::fmt;
+
+// Upper bound on the number of subintervals evaluated by a single bisection
+const MAX_EVALUATIONS: usize = 100000;
+
+// Enclosures wider than this many times the tolerance are not isolated points,
+// e.g. the function is constantly zero over an interval
+const MAX_ENCLOSURE_WIDTH: f64 = 16.0;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PointKind {
+    Root,
+    Minimum,
+    Maximum,
+    Intersection,
+}
+
+#[derive(Clone)]
+pub struct PointOfInterest {
+    pub kind: PointKind,
+
+    // Enclosure of the x coordinate of the point
+    pub x_start: f64,
+    pub x_end: f64,
+
+    pub y: f64,
+
+    // True if the enclosure is proven to contain exactly one such point,
+    // otherwise it can only contain it
+    pub verified: bool,
+}
+
+impl PointOfInterest {
+    pub fn x(&self) -> f64 {
+        (self.x_start + self.x_end) / 2.0
+    }
+}
+
+impl fmt::Display for PointOfInterest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self.kind {
+            PointKind::Root => "root",
+            PointKind::Minimum => "local minimum",
+            PointKind::Maximum => "local maximum",
+            PointKind::Intersection => "intersection",
+        };
+        let certainty = if self.verified { "verified" } else { "not verified" };
+        write!(f, "{} at x = {:.6}, y = {:.6} (x in [{}, {}], {})", name, self.x(), self.y, self.x_start, self.x_end, certainty)
+    }
+}
+
+// Returns the zeros of f in the given range
+pub fn find_roots(expression: &Expression<IntervalSet<f64>>, derivative: Option<&Expression<Dual>>, x_range: (f64, f64), tolerance: f64) -> Vec<PointOfInterest> {
+    let f = |x| expression.eval_2d(x);
+    let slope = derivative.map(|derivative| move |x| derivative.eval_2d(Dual::variable(x)).derivative);
+
+    find_zeros(f, slope, PointKind::Root, x_range, tolerance)
+}
+
+// Returns the points where f and g intersect in the given range
+pub fn find_intersections(f: &Expression<IntervalSet<f64>>, f_derivative: Option<&Expression<Dual>>,
+                          g: &Expression<IntervalSet<f64>>, g_derivative: Option<&Expression<Dual>>,
+                          x_range: (f64, f64), tolerance: f64) -> Vec<PointOfInterest> {
+    let difference = |x: IntervalSet<f64>| f.eval_2d(x.clone()) - g.eval_2d(x);
+    let slope = match (f_derivative, g_derivative) {
+        (Some(f_derivative), Some(g_derivative)) => Some(move |x: IntervalSet<f64>| {
+            let x = Dual::variable(x);
+            (f_derivative.eval_2d(x.clone()) - g_derivative.eval_2d(x)).derivative
+        }),
+        _ => None,
+    };
+
+    let mut intersections = find_zeros(difference, slope, PointKind::Intersection, x_range, tolerance);
+    for intersection in intersections.iter_mut() {
+        intersection.y = midpoint(f.eval_2d(IntervalSet::singleton(intersection.x())));
+    }
+    intersections
+}
+
+// Returns the local minima and maxima of f in the given range, that is the zeros of its derivative
+// where the derivative changes sign
+pub fn find_extrema(expression: &Expression<IntervalSet<f64>>, derivative: &Expression<Dual>, x_range: (f64, f64), tolerance: f64) -> Vec<PointOfInterest> {
+    let slope = |x| derivative.eval_2d(Dual::variable(x)).derivative;
+
+    isolate_zeros(&slope, x_range, tolerance).into_iter()
+        .filter_map(|(x_start, x_end)| {
+            let kind = match (sign(slope(IntervalSet::singleton(x_start))), sign(slope(IntervalSet::singleton(x_end)))) {
+                (Some(s1), Some(s2)) if s1 < 0.0 && s2 > 0.0 => PointKind::Minimum,
+                (Some(s1), Some(s2)) if s1 > 0.0 && s2 < 0.0 => PointKind::Maximum,
+                _ => return None,
+            };
+
+            // The sign change proves there is an extremum if f is continuous in the enclosure,
+            // which is the case if its derivative is bounded there
+            let verified = is_bounded(&slope(IntervalSet::new(x_start, x_end)));
+            let y = midpoint(expression.eval_2d(IntervalSet::singleton((x_start + x_end) / 2.0)));
+
+            Some(PointOfInterest {
+                kind,
+                x_start,
+                x_end,
+                y,
+                verified,
+            })
+        })
+        .collect()
+}
+
+// Enclosure of the definite integral of f between a and b.
+// The range of f over each subinterval, times its width, encloses the integral over that subinterval,
+// so the sum, computed with interval arithmetic, encloses the whole integral.
+// Returns None if f is not defined over the whole interval, or is unbounded on it.
+pub fn integrate(expression: &Expression<IntervalSet<f64>>, a: f64, b: f64, subdivisions: u32) -> Option<(f64, f64)> {
+    let (start, end, orientation) = if a <= b { (a, b, 1.0) } else { (b, a, -1.0) };
+    let step = (end - start) / subdivisions as f64;
+
+    let mut integral = IntervalSet::singleton(0.0);
+    for i in 0..subdivisions {
+        let x_0 = start + step * i as f64;
+        let x_1 = if i + 1 == subdivisions { end } else { start + step * (i + 1) as f64 };
+
+        let width = IntervalSet::singleton(x_1) - IntervalSet::singleton(x_0);
+        integral = integral + expression.eval_2d(IntervalSet::new(x_0, x_1)) * width;
+    }
+
+    interval::hull(&(integral * IntervalSet::singleton(orientation)))
+        .filter(|(lower, upper)| lower.is_finite() && upper.is_finite())
+}
+
+fn find_zeros<F, D>(f: F, slope: Option<D>, kind: PointKind, x_range: (f64, f64), tolerance: f64) -> Vec<PointOfInterest>
+    where F: Fn(IntervalSet<f64>) -> IntervalSet<f64>,
+          D: Fn(IntervalSet<f64>) -> IntervalSet<f64> {
+
+    isolate_zeros(&f, x_range, tolerance).into_iter()
+        .map(|(x_start, x_end)| {
+            let changes_sign = match (sign(f(IntervalSet::singleton(x_start))), sign(f(IntervalSet::singleton(x_end)))) {
+                (Some(s1), Some(s2)) => s1 * s2 < 0.0,
+                _ => false,
+            };
+
+            // A bounded derivative makes f continuous in the enclosure, so the sign change
+            // proves there is a zero, and if the derivative doesn't vanish the zero is unique
+            let verified = changes_sign && slope.as_ref().map_or(false, |slope| {
+                let slope = slope(IntervalSet::new(x_start, x_end));
+                is_bounded(&slope) && !slope.has_zero()
+            });
+
+            PointOfInterest {
+                kind,
+                x_start,
+                x_end,
+                y: 0.0,
+                verified,
+            }
+        })
+        .collect()
+}
+
+// Bisect the range discarding the subintervals where f has no zero.
+// Returns the sorted list of enclosures not wider than tolerance that might contain a zero,
+// adjacent enclosures are merged together.
+fn isolate_zeros<F>(f: F, x_range: (f64, f64), tolerance: f64) -> Vec<(f64, f64)>
+    where F: Fn(IntervalSet<f64>) -> IntervalSet<f64> {
+
+    let mut enclosures: Vec<(f64, f64)> = Vec::new();
+    let mut stack = vec![x_range];
+    let mut evaluations = 0;
+
+    while let Some((x_start, x_end)) = stack.pop() {
+        evaluations += 1;
+        if evaluations > MAX_EVALUATIONS {
+            break;
+        }
+
+        if !f(IntervalSet::new(x_start, x_end)).has_zero() {
+            continue;
+        }
+
+        if x_end - x_start <= tolerance {
+            match enclosures.last_mut() {
+                Some(last) if last.1 == x_start => last.1 = x_end,
+                _ => enclosures.push((x_start, x_end)),
+            }
+        } else {
+            // Push the right half first, so that enclosures are found from left to right
+            let x_half = (x_start + x_end) / 2.0;
+            stack.push((x_half, x_end));
+            stack.push((x_start, x_half));
+        }
+    }
+
+    enclosures.retain(|enclosure| enclosure.1 - enclosure.0 <= MAX_ENCLOSURE_WIDTH * tolerance);
+    enclosures
+}
+
+// Returns the sign of every element of the set, if they all have the same sign
+fn sign(x: IntervalSet<f64>) -> Option<f64> {
+    let intervals = interval::intervals(&x);
+    if intervals.is_empty() {
+        None
+    } else if intervals.iter().all(|interval| interval.0 > 0.0) {
+        Some(1.0)
+    } else if intervals.iter().all(|interval| interval.1 < 0.0) {
+        Some(-1.0)
+    } else {
+        None
+    }
+}
+
+fn is_bounded(x: &IntervalSet<f64>) -> bool {
+    let intervals = interval::intervals(x);
+    !intervals.is_empty() && intervals.iter().all(|interval| interval.0.is_finite() && interval.1.is_finite())
+}
+
+fn midpoint(x: IntervalSet<f64>) -> f64 {
+    interval::hull(&x).map_or(std::f64::NAN, |(start, end)| (start + end) / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator_tables::{differentiation_operator_table, interval_arithmetic_operator_table};
+    use crate::parser::parse;
+    use std::f64::consts::PI;
+
+    const TOLERANCE: f64 = 1e-6;
+
+    fn function(input: &str) -> (Expression<IntervalSet<f64>>, Expression<Dual>) {
+        (parse(input, &interval_arithmetic_operator_table()).unwrap(), parse(input, &differentiation_operator_table()).unwrap())
+    }
+
+    fn roots(input: &str, x_range: (f64, f64)) -> Vec<PointOfInterest> {
+        let (expression, derivative) = function(input);
+        find_roots(&expression, Some(&derivative), x_range, TOLERANCE)
+    }
+
+    // The points enclose the given x coordinates, in order
+    fn assert_enclose(points: &[PointOfInterest], xs: &[f64]) {
+        assert_eq!(points.len(), xs.len());
+        for (point, &x) in points.iter().zip(xs) {
+            assert!(point.x_start <= x && x <= point.x_end, "[{}, {}] doesn't contain {}", point.x_start, point.x_end, x);
+            assert!(point.x_end - point.x_start <= MAX_ENCLOSURE_WIDTH * TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn simple_roots_are_verified() {
+        let points = roots("sin(x)", (-4.0, 4.0));
+        assert_enclose(&points, &[-PI, 0.0, PI]);
+        assert!(points.iter().all(|point| point.verified && point.kind == PointKind::Root));
+
+        let points = roots("x^2 - 2", (-4.0, 4.0));
+        assert_enclose(&points, &[-2f64.sqrt(), 2f64.sqrt()]);
+        assert!(points.iter().all(|point| point.verified));
+    }
+
+    #[test]
+    fn multiple_roots_are_not_verified() {
+        // the function touches zero without changing sign
+        let points = roots("(x - 1)^2", (-4.0, 4.0));
+        assert_enclose(&points, &[1.0]);
+        assert!(!points[0].verified);
+
+        // the sign changes but the derivative vanishes, the root could be one of several
+        let points = roots("x^3", (-4.0, 4.0));
+        assert_enclose(&points, &[0.0]);
+        assert!(!points[0].verified);
+    }
+
+    #[test]
+    fn functions_without_roots_have_none() {
+        assert!(roots("x^2 + 1", (-4.0, 4.0)).is_empty());
+        assert!(roots("1/x", (-4.0, 4.0)).is_empty());
+    }
+
+    #[test]
+    fn extrema_are_found_where_the_slope_changes_sign() {
+        let (expression, derivative) = function("sin(x)");
+        let points = find_extrema(&expression, &derivative, (0.0, 7.0), TOLERANCE);
+        assert_enclose(&points, &[PI / 2.0, 3.0 * PI / 2.0]);
+        assert!(points[0].kind == PointKind::Maximum && points[1].kind == PointKind::Minimum);
+        assert!(points.iter().all(|point| point.verified));
+        assert!((points[0].y - 1.0).abs() < 1e-9 && (points[1].y + 1.0).abs() < 1e-9);
+
+        let (expression, derivative) = function("x^2 - 2");
+        let points = find_extrema(&expression, &derivative, (-4.0, 4.0), TOLERANCE);
+        assert_enclose(&points, &[0.0]);
+        assert!(points[0].kind == PointKind::Minimum && points[0].verified);
+        assert!((points[0].y + 2.0).abs() < 1e-9);
+
+        // an inflection point is not an extremum
+        let (expression, derivative) = function("x^3");
+        assert!(find_extrema(&expression, &derivative, (-4.0, 4.0), TOLERANCE).is_empty());
+    }
+
+    #[test]
+    fn intersections_are_on_both_curves() {
+        let (sin, sin_derivative) = function("sin(x)");
+        let (cos, cos_derivative) = function("cos(x)");
+        let points = find_intersections(&sin, Some(&sin_derivative), &cos, Some(&cos_derivative), (-4.0, 4.0), TOLERANCE);
+        assert_enclose(&points, &[-3.0 * PI / 4.0, PI / 4.0, 5.0 * PI / 4.0]);
+        assert!(points.iter().all(|point| point.verified && point.kind == PointKind::Intersection));
+        assert!((points[1].y - 0.5f64.sqrt()).abs() < 1e-6);
+
+        // the tangent touches the curve without crossing it
+        let (one, one_derivative) = function("1");
+        let points = find_intersections(&cos, Some(&cos_derivative), &one, Some(&one_derivative), (-1.0, 1.0), TOLERANCE);
+        assert_enclose(&points, &[0.0]);
+        assert!(!points[0].verified);
+        assert!((points[0].y - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn integrals_are_enclosed() {
+        let (x_squared, _) = function("x^2");
+        let (lower, upper) = integrate(&x_squared, 0.0, 1.0, 1000).unwrap();
+        assert!(lower <= 1.0 / 3.0 && 1.0 / 3.0 <= upper && upper - lower < 0.002, "[{}, {}]", lower, upper);
+
+        let (sin, _) = function("sin(x)");
+        let (lower, upper) = integrate(&sin, 0.0, PI, 1000).unwrap();
+        assert!(lower <= 2.0 && 2.0 <= upper && upper - lower < 0.01, "[{}, {}]", lower, upper);
+
+        // integrating backwards changes the sign
+        let (lower, upper) = integrate(&x_squared, 1.0, 0.0, 1000).unwrap();
+        assert!(lower <= -1.0 / 3.0 && -1.0 / 3.0 <= upper && upper - lower < 0.002, "[{}, {}]", lower, upper);
+    }
+
+    #[test]
+    fn unbounded_functions_have_no_integral() {
+        let (reciprocal, _) = function("1/x");
+        assert!(integrate(&reciprocal, -1.0, 1.0, 1000).is_none());
+        assert!(integrate(&reciprocal, 0.0, 1.0, 1000).is_none());
+        assert!(integrate(&reciprocal, 1.0, 2.0, 1000).is_some());
+    }
+}
@@ -0,0 +1,112 @@
+use crate::parser;
+use crate::operator_tables;
+use crate::operator_descr::OperatorTable;
+use crate::expression::ExprType;
+use crate::interval;
+use crate::definitions::{Definitions, Environment};
+use honestintervals::IntervalSet;
+
+// Number of previous results to remember
+const MAX_HISTORY: usize = 10;
+
+// Evaluates expressions without variables, computing both the floating point value
+// and a rigorous enclosure of the exact value using interval arithmetic
+pub struct Calculator {
+    operator_table: OperatorTable<f64>,
+    interval_arithmetic_operator_table: OperatorTable<IntervalSet<f64>>,
+    definitions: Definitions,
+    history: Vec<String>,
+}
+
+impl Calculator {
+    pub fn new() -> Calculator {
+        Calculator {
+            operator_table: operator_tables::default_operator_table(),
+            interval_arithmetic_operator_table: operator_tables::interval_arithmetic_operator_table(),
+            definitions: Definitions::new(),
+            history: Vec::new(),
+        }
+    }
+
+    // The functions the expressions can use
+    pub fn set_definitions(&mut self, definitions: Definitions) {
+        self.definitions = definitions;
+    }
+
+    // Evaluate a constant expression and return the description of its result,
+    // which is also added to the history.
+    // The input can also be the definition of a function, which is remembered for the next expressions
+    pub fn evaluate(&mut self, input: &str) -> Result<String, &'static str> {
+        if let Some(definition) = parser::parse_definition(input, &self.operator_table) {
+            let mut definitions = self.definitions.clone();
+            definitions.insert(definition?);
+            definitions.check(&self.operator_table)?;
+            self.definitions = definitions;
+            return Ok(self.remember(String::from(input.trim())));
+        }
+
+        let environment = Environment::new(&self.operator_table, &self.definitions);
+        let expression = parser::parse(input, &environment)?;
+        match expression.expr_type() {
+            ExprType::Constant => (),
+            _ => return Err("Expression is not constant"),
+        }
+        let value = expression.eval_constant();
+
+        // The enclosure is empty if the value is not defined
+        let interval_environment = Environment::new(&self.interval_arithmetic_operator_table, &self.definitions);
+        let enclosure = parser::parse(input, &interval_environment)
+            .ok()
+            .and_then(|expression| interval::hull(&expression.eval_constant()));
+
+        let result = match enclosure {
+            Some((lower, upper)) => format!("{} = {} (enclosed in [{:e}, {:e}])", input.trim(), value, lower, upper),
+            None => format!("{} = {}", input.trim(), value),
+        };
+
+        Ok(self.remember(result))
+    }
+
+    fn remember(&mut self, result: String) -> String {
+        self.history.push(result.clone());
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+        result
+    }
+
+    // The previous results, the most recent one is the last
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_are_remembered() {
+        let mut calculator = Calculator::new();
+        assert_eq!(calculator.evaluate(" 2+3 "), Ok(String::from("2+3 = 5 (enclosed in [5e0, 5e0])")));
+        assert_eq!(calculator.evaluate("x+1"), Err("Expression is not constant"));
+        assert_eq!(calculator.evaluate("f(x) = 2*x + 1"), Ok(String::from("f(x) = 2*x + 1")));
+        assert_eq!(calculator.evaluate("f(3)"), Ok(String::from("f(3) = 7 (enclosed in [7e0, 7e0])")));
+        // the value of an undefined expression has no enclosure
+        assert_eq!(calculator.evaluate("{ 1 : 1 > 2 }"), Ok(String::from("{ 1 : 1 > 2 } = NaN")));
+        // the errors are not remembered
+        assert_eq!(calculator.history(), &["2+3 = 5 (enclosed in [5e0, 5e0])", "f(x) = 2*x + 1",
+                                           "f(3) = 7 (enclosed in [7e0, 7e0])", "{ 1 : 1 > 2 } = NaN"]);
+    }
+
+    #[test]
+    fn the_history_keeps_the_last_results() {
+        let mut calculator = Calculator::new();
+        for i in 0..MAX_HISTORY + 2 {
+            calculator.evaluate(&i.to_string()).unwrap();
+        }
+        assert_eq!(calculator.history().len(), MAX_HISTORY);
+        assert_eq!(calculator.history()[0], "2 = 2 (enclosed in [2e0, 2e0])");
+        assert!(calculator.history()[MAX_HISTORY - 1].starts_with(&format!("{0} = {0} ", MAX_HISTORY + 1)));
+    }
+}
@@ -0,0 +1,175 @@
+// Colormaps turn the values of a function into colors.
+// They are given as a list of evenly spaced colors, interpolated linearly in between
+
+// The viridis colormap, perceptually uniform and readable by color blind people, from dark blue to yellow
+const VIRIDIS: [u32; 9] = [0x440154, 0x472d7b, 0x3b528b, 0x2c728e, 0x21908c, 0x27ad81, 0x5dc863, 0xaadc32, 0xfde725];
+
+// A diverging colormap, from blue for negative values to red for positive ones, white in 0
+const DIVERGING: [u32; 5] = [0x0571b0, 0x92c5de, 0xf7f7f7, 0xf4a582, 0xca0020];
+
+// Number of contour lines drawn when the levels are not chosen by the user
+const AUTOMATIC_LEVELS: f64 = 10.0;
+
+pub enum Colormap {
+    Viridis,
+    Diverging,
+}
+
+impl Colormap {
+    pub fn from_name(name: &str) -> Option<Colormap> {
+        match name {
+            "viridis" => Some(Colormap::Viridis),
+            "diverging" => Some(Colormap::Diverging),
+            _ => None,
+        }
+    }
+
+    // Diverging colormaps are centered in 0, the others span the range of the function
+    pub fn is_diverging(&self) -> bool {
+        match self {
+            Colormap::Viridis => false,
+            Colormap::Diverging => true,
+        }
+    }
+
+    // The evenly spaced colors, written as 0xrrggbb
+    pub fn colors(&self) -> &'static [u32] {
+        match self {
+            Colormap::Viridis => &VIRIDIS,
+            Colormap::Diverging => &DIVERGING,
+        }
+    }
+
+    // The color of the fraction t of the range of the function, t is clamped between 0 and 1
+    pub fn color(&self, t: f64) -> (f32, f32, f32) {
+        let colors = self.colors();
+
+        let position = t.clamp(0.0, 1.0) * (colors.len() - 1) as f64;
+        let i = (position.floor() as usize).min(colors.len() - 2);
+        let fraction = (position - i as f64) as f32;
+
+        let (start, end) = (rgb(colors[i]), rgb(colors[i + 1]));
+        (start.0 + (end.0 - start.0) * fraction,
+         start.1 + (end.1 - start.1) * fraction,
+         start.2 + (end.2 - start.2) * fraction)
+    }
+
+    // The fraction of the range for value, where the range goes from min to max.
+    // For diverging colormaps the range is made symmetric around 0
+    pub fn normalize(&self, value: f64, min: f64, max: f64) -> f64 {
+        if self.is_diverging() {
+            let bound = min.abs().max(max.abs());
+            if bound > 0.0 { 0.5 + value / (2.0 * bound) } else { 0.5 }
+        } else if max > min {
+            (value - min) / (max - min)
+        } else {
+            0.5
+        }
+    }
+
+    // normalize is linear in the value, the shaders compute it as scale * value + offset.
+    // Returns the scale and the offset
+    pub fn normalization(&self, min: f64, max: f64) -> (f64, f64) {
+        let offset = self.normalize(0.0, min, max);
+        (self.normalize(1.0, min, max) - offset, offset)
+    }
+}
+
+// The levels of the contour lines, chosen by the user or following the range of the visible values
+pub struct Levels {
+    chosen: Vec<f64>,
+}
+
+impl Levels {
+    // No levels means automatic ones
+    pub fn new(chosen: Vec<f64>) -> Levels {
+        Levels { chosen }
+    }
+
+    // The levels along with their labels, given the range of the visible values.
+    // Automatic levels are multiples of 1, 2 or 5 times a power of 10
+    pub fn values(&self, min: f64, max: f64) -> Vec<(f64, String)> {
+        if !self.chosen.is_empty() {
+            return self.chosen.iter().map(|level| (*level, level.to_string())).collect();
+        }
+        if !(min.is_finite() && max.is_finite() && max > min) {
+            return Vec::new();
+        }
+
+        let raw_step = (max - min) / AUTOMATIC_LEVELS;
+        let magnitude = (10.0f64).powf(raw_step.log10().floor());
+        let step = [1.0, 2.0, 5.0, 10.0].iter()
+            .map(|factor| factor * magnitude)
+            .find(|step| *step >= raw_step)
+            .unwrap();
+        let decimals = (-step.log10().floor()).max(0.0) as usize;
+
+        let first = (min / step).ceil() as i64;
+        let last = (max / step).floor() as i64;
+        (first..=last)
+            .map(|k| k as f64 * step)
+            .map(|level| (level, format!("{:.*}", decimals, level)))
+            .collect()
+    }
+}
+
+// Split a color written as 0xrrggbb in its components between 0 and 1
+pub fn rgb(color: u32) -> (f32, f32, f32) {
+    let component = |shift: u32| ((color >> shift) & 0xff) as f32 / 255.0;
+    (component(16), component(8), component(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(levels: &[(f64, String)]) -> Vec<&str> {
+        levels.iter().map(|(_, label)| label.as_str()).collect()
+    }
+
+    #[test]
+    fn automatic_levels_are_round_numbers() {
+        let levels = Levels::new(Vec::new());
+        assert_eq!(labels(&levels.values(-3.0, 7.0)), ["-3", "-2", "-1", "0", "1", "2", "3", "4", "5", "6", "7"]);
+        assert_eq!(labels(&levels.values(0.0, 130.0)), ["0", "20", "40", "60", "80", "100", "120"]);
+        assert_eq!(labels(&levels.values(0.01, 0.37)), ["0.05", "0.10", "0.15", "0.20", "0.25", "0.30", "0.35"]);
+        assert!(levels.values(-3.0, 7.0).iter().all(|(level, label)| label.parse::<f64>().unwrap() == *level));
+    }
+
+    #[test]
+    fn automatic_levels_need_a_range() {
+        let levels = Levels::new(Vec::new());
+        assert!(levels.values(1.0, 1.0).is_empty());
+        assert!(levels.values(f64::NEG_INFINITY, 1.0).is_empty());
+        assert!(levels.values(f64::INFINITY, f64::NEG_INFINITY).is_empty());
+    }
+
+    #[test]
+    fn chosen_levels_ignore_the_range() {
+        let levels = Levels::new(vec![1.0, 4.0, 9.5]);
+        assert_eq!(labels(&levels.values(0.0, 2.0)), ["1", "4", "9.5"]);
+        assert_eq!(labels(&levels.values(1.0, 1.0)), ["1", "4", "9.5"]);
+    }
+
+    #[test]
+    fn values_are_normalized_in_the_range() {
+        assert_eq!(Colormap::Viridis.normalize(5.0, 0.0, 10.0), 0.5);
+        assert_eq!(Colormap::Viridis.normalize(-2.0, -2.0, 6.0), 0.0);
+        assert_eq!(Colormap::Viridis.normalize(3.0, 3.0, 3.0), 0.5);
+        // diverging colormaps keep 0 in the middle
+        assert_eq!(Colormap::Diverging.normalize(0.0, -2.0, 1.0), 0.5);
+        assert_eq!(Colormap::Diverging.normalize(-2.0, -2.0, 1.0), 0.0);
+        assert_eq!(Colormap::Diverging.normalize(1.0, -2.0, 1.0), 0.75);
+        assert_eq!(Colormap::Diverging.normalize(0.0, 0.0, 0.0), 0.5);
+        // as computed by the shaders
+        assert_eq!(Colormap::Viridis.normalization(-2.0, 6.0), (0.125, 0.25));
+        assert_eq!(Colormap::Diverging.normalization(-2.0, 1.0), (0.25, 0.5));
+    }
+
+    #[test]
+    fn colors_are_clamped_to_the_ends() {
+        assert_eq!(Colormap::Viridis.color(-1.0), rgb(VIRIDIS[0]));
+        assert_eq!(Colormap::Viridis.color(2.0), rgb(VIRIDIS[8]));
+        assert_eq!(Colormap::Diverging.color(0.5), rgb(DIVERGING[2]));
+    }
+}
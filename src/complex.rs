@@ -0,0 +1,361 @@
+use std::f64::consts::{PI, LN_2, LN_10};
+use num_complex::Complex;
+use crate::number_literal::NumberLiteral;
+use crate::plasm_number::PlasmNumber;
+use crate::special;
+
+// Complex numbers, used to plot functions of the complex variable z = x + iy.
+// Functions which only make sense for real numbers, like comparisons, floor and most special functions,
+// agree with the real ones on the real axis and are undefined everywhere else.
+
+// Integer exponents up to this one are computed by repeated multiplication
+const MAX_INTEGER_EXPONENT: f64 = 64.0;
+
+fn zero() -> Complex<f64> {
+    Complex::from(0.0)
+}
+
+fn one() -> Complex<f64> {
+    Complex::from(1.0)
+}
+
+// Apply a function of real numbers, if the argument is real
+fn on_real_axis(z: Complex<f64>, f: fn(f64) -> f64) -> Complex<f64> {
+    if z.im == 0.0 {
+        Complex::from(f(z.re))
+    } else {
+        Complex::undefined()
+    }
+}
+
+// Apply a function of two real numbers, if both the arguments are real
+fn on_real_axis2(z: Complex<f64>, w: Complex<f64>, f: fn(f64, f64) -> f64) -> Complex<f64> {
+    if z.im == 0.0 && w.im == 0.0 {
+        Complex::from(f(z.re, w.re))
+    } else {
+        Complex::undefined()
+    }
+}
+
+// z^n by repeated squaring, n can be negative
+fn integer_power(z: Complex<f64>, n: i32) -> Complex<f64> {
+    let mut result = one();
+    let mut base = z;
+    let mut exponent = n.abs();
+    while exponent > 0 {
+        if exponent % 2 == 1 {
+            result = result * base;
+        }
+        base = base * base;
+        exponent /= 2;
+    }
+
+    if n < 0 {
+        one() / result
+    } else {
+        result
+    }
+}
+
+// sin(pi z), with the real part reduced exactly so that it is accurate for big z
+fn sin_pi(z: Complex<f64>) -> Complex<f64> {
+    let reduced = Complex::new(z.re - 2.0 * (z.re / 2.0).round(), z.im);
+    Complex::sin(&(reduced * PI))
+}
+
+// Lanczos approximation, with the reflection formula for Re(z) < 0.5
+fn gamma(z: Complex<f64>) -> Complex<f64> {
+    if z.re < 0.5 {
+        Complex::from(PI) / (sin_pi(z) * gamma(one() - z))
+    } else {
+        let z = z - 1.0;
+        let mut sum = Complex::from(special::LANCZOS_COEFFICIENTS[0]);
+        for (i, coefficient) in special::LANCZOS_COEFFICIENTS.iter().enumerate().skip(1) {
+            sum = sum + Complex::from(*coefficient) / (z + i as f64);
+        }
+
+        // t^(z+1/2) e^(-t) is computed as a single exponential to avoid overflowing
+        let t = z + special::LANCZOS_G + 0.5;
+        Complex::exp(&((z + 0.5) * Complex::ln(&t) - t)) * sum * special::SQRT_2PI
+    }
+}
+
+// Riemann zeta function, by the same algorithm as the real one
+fn zeta(s: Complex<f64>) -> Complex<f64> {
+    if s.re < 0.0 {
+        // Functional equation
+        let reflected = one() - s;
+        Complex::exp(&(s * LN_2 + (s - 1.0) * PI.ln())) * sin_pi(s / 2.0) * gamma(reflected) * zeta(reflected)
+    } else {
+        // zeta(s) = eta(s) / (1 - 2^(1-s)), where eta is the alternating zeta function
+        let n = special::BORWEIN_TERMS;
+        let d = special::borwein_coefficients();
+        let mut eta = zero();
+        for k in 0..n {
+            let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+            eta = eta + Complex::exp(&(-s * ((k + 1) as f64).ln())) * (sign * (d[k] - d[n]));
+        }
+        eta = -eta / d[n];
+
+        eta / (one() - Complex::exp(&((one() - s) * LN_2)))
+    }
+}
+
+// Write a complex number as a + bi
+pub fn to_string(z: Complex<f64>) -> String {
+    if z.im < 0.0 {
+        format!("{:.6} - {:.6}i", z.re, -z.im)
+    } else {
+        format!("{:.6} + {:.6}i", z.re, z.im)
+    }
+}
+
+impl PlasmNumber for Complex<f64> {
+    fn from_literal(literal: NumberLiteral) -> Complex<f64> {
+        Complex::from(literal.value)
+    }
+
+    fn pi() -> Complex<f64> {
+        Complex::from(PI)
+    }
+
+    fn e() -> Complex<f64> {
+        Complex::from(std::f64::consts::E)
+    }
+
+    // Principal value of the power, integer powers are defined everywhere but in 0 for negative exponents
+    fn pow(self, exponent: Complex<f64>) -> Complex<f64> {
+        if exponent.im == 0.0 && exponent.re == exponent.re.round() && exponent.re.abs() <= MAX_INTEGER_EXPONENT {
+            integer_power(self, exponent.re as i32)
+        } else if self == zero() {
+            if exponent.re > 0.0 {
+                zero()
+            } else {
+                Complex::undefined()
+            }
+        } else {
+            Complex::powc(&self, exponent)
+        }
+    }
+
+    fn exp(self) -> Complex<f64> {
+        Complex::exp(&self)
+    }
+
+    fn ln(self) -> Complex<f64> {
+        Complex::ln(&self)
+    }
+
+    fn log10(self) -> Complex<f64> {
+        Complex::ln(&self) / LN_10
+    }
+
+    fn log2(self) -> Complex<f64> {
+        Complex::ln(&self) / LN_2
+    }
+
+    fn sqrt(self) -> Complex<f64> {
+        Complex::sqrt(&self)
+    }
+
+    fn sin(self) -> Complex<f64> {
+        Complex::sin(&self)
+    }
+
+    fn cos(self) -> Complex<f64> {
+        Complex::cos(&self)
+    }
+
+    fn tan(self) -> Complex<f64> {
+        Complex::tan(&self)
+    }
+
+    fn asin(self) -> Complex<f64> {
+        Complex::asin(&self)
+    }
+
+    fn acos(self) -> Complex<f64> {
+        Complex::acos(&self)
+    }
+
+    fn atan(self) -> Complex<f64> {
+        Complex::atan(&self)
+    }
+
+    // The modulus
+    fn abs(self) -> Complex<f64> {
+        Complex::from(self.norm())
+    }
+
+    // The point of the unit circle in the direction of self, 0 for 0
+    fn signum(self) -> Complex<f64> {
+        if self == zero() {
+            self
+        } else {
+            self / self.norm()
+        }
+    }
+
+    fn sinh(self) -> Complex<f64> {
+        Complex::sinh(&self)
+    }
+
+    fn cosh(self) -> Complex<f64> {
+        Complex::cosh(&self)
+    }
+
+    fn tanh(self) -> Complex<f64> {
+        Complex::tanh(&self)
+    }
+
+    // Rounding is done on the real and imaginary parts separately
+    fn floor(self) -> Complex<f64> {
+        Complex::new(self.re.floor(), self.im.floor())
+    }
+
+    fn ceil(self) -> Complex<f64> {
+        Complex::new(self.re.ceil(), self.im.ceil())
+    }
+
+    fn round(self) -> Complex<f64> {
+        Complex::new(self.re.round(), self.im.round())
+    }
+
+    fn min(self, other: Complex<f64>) -> Complex<f64> {
+        on_real_axis2(self, other, f64::min)
+    }
+
+    fn max(self, other: Complex<f64>) -> Complex<f64> {
+        on_real_axis2(self, other, f64::max)
+    }
+
+    fn gamma(self) -> Complex<f64> {
+        if self.im == 0.0 {
+            // The real version takes care of the poles
+            Complex::from(special::gamma(self.re))
+        } else {
+            gamma(self)
+        }
+    }
+
+    // Logarithm of gamma, on the real axis it is the logarithm of the absolute value as for real numbers
+    fn lgamma(self) -> Complex<f64> {
+        if self.im == 0.0 {
+            Complex::from(special::lgamma(self.re))
+        } else {
+            Complex::ln(&gamma(self))
+        }
+    }
+
+    fn erf(self) -> Complex<f64> {
+        on_real_axis(self, special::erf)
+    }
+
+    fn erfc(self) -> Complex<f64> {
+        on_real_axis(self, special::erfc)
+    }
+
+    fn besselj(n: Complex<f64>, x: Complex<f64>) -> Complex<f64> {
+        on_real_axis2(n, x, special::besselj)
+    }
+
+    fn airy(self) -> Complex<f64> {
+        on_real_axis(self, |x| special::airy(x).0)
+    }
+
+    fn zeta(self) -> Complex<f64> {
+        if self.im == 0.0 {
+            Complex::from(special::zeta(self.re))
+        } else {
+            zeta(self)
+        }
+    }
+
+    fn lambert_w(self) -> Complex<f64> {
+        on_real_axis(self, special::lambert_w)
+    }
+
+    fn less(self, other: Complex<f64>) -> Complex<f64> {
+        on_real_axis2(self, other, f64::less)
+    }
+
+    fn less_equal(self, other: Complex<f64>) -> Complex<f64> {
+        on_real_axis2(self, other, f64::less_equal)
+    }
+
+    // Equality makes sense for any complex number
+    fn equal(self, other: Complex<f64>) -> Complex<f64> {
+        if self.is_nan() || other.is_nan() {
+            Complex::undefined()
+        } else if self == other {
+            one()
+        } else {
+            zero()
+        }
+    }
+
+    fn logical_not(self) -> Complex<f64> {
+        on_real_axis(self, f64::logical_not)
+    }
+
+    fn logical_and(self, other: Complex<f64>) -> Complex<f64> {
+        on_real_axis2(self, other, f64::logical_and)
+    }
+
+    fn logical_or(self, other: Complex<f64>) -> Complex<f64> {
+        on_real_axis2(self, other, f64::logical_or)
+    }
+
+    fn select(condition: Complex<f64>, then: Complex<f64>, otherwise: Complex<f64>) -> Complex<f64> {
+        if condition.is_nan() {
+            condition
+        } else if condition != zero() {
+            then
+        } else {
+            otherwise
+        }
+    }
+
+    fn undefined() -> Complex<f64> {
+        Complex::new(std::f64::NAN, std::f64::NAN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(function: &str, value: Complex<f64>, expected: Complex<f64>) {
+        assert!((value - expected).norm() <= 1e-10 * expected.norm().max(1.0), "{} = {}, expected {}", function, value, expected);
+    }
+
+    #[test]
+    fn gamma_is_extended_by_reflection() {
+        let sqrt_pi = PI.sqrt();
+        assert_close("gamma(1/2)", gamma(Complex::from(0.5)), Complex::from(sqrt_pi));
+        assert_close("gamma(-1/2)", gamma(Complex::from(-0.5)), Complex::from(-2.0 * sqrt_pi));
+        assert_close("gamma(-5/2)", gamma(Complex::from(-2.5)), Complex::from(-8.0 / 15.0 * sqrt_pi));
+
+        // gamma(z + 1) = z gamma(z), with z on the reflected side and z + 1 on the other one
+        for z in [Complex::new(-0.3, 0.7), Complex::new(-2.6, -1.5), Complex::new(0.2, 4.0)].iter() {
+            assert_close("gamma(z + 1)", gamma(z + 1.0), z * gamma(*z));
+            assert_close("gamma(conj(z))", gamma(z.conj()), gamma(*z).conj());
+        }
+    }
+
+    #[test]
+    fn zeta_is_extended_by_the_functional_equation() {
+        assert_close("zeta(2)", zeta(Complex::from(2.0)), Complex::from(PI * PI / 6.0));
+        assert_close("zeta(4)", zeta(Complex::from(4.0)), Complex::from(PI.powi(4) / 90.0));
+        assert_close("zeta(-1)", zeta(Complex::from(-1.0)), Complex::from(-1.0 / 12.0));
+        assert_close("zeta(-3)", zeta(Complex::from(-3.0)), Complex::from(1.0 / 120.0));
+        assert!(zeta(Complex::from(-2.0)).norm() < 1e-12);
+
+        // the two branches agree with the real function next to the real axis
+        for s in [-3.5, -0.5, 0.5, 3.5].iter() {
+            assert_close("zeta(s)", zeta(Complex::new(*s, 1e-12)), Complex::from(special::zeta(*s)));
+        }
+        let s = Complex::new(-1.5, 2.0);
+        assert_close("zeta(conj(s))", zeta(s.conj()), zeta(s).conj());
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use crate::expression::Operation;
+use crate::semantics::*;
+use crate::number_literal::NumberLiteral;
+use crate::parser;
+
+// A function defined by the user, like f(x) = x^2 + 1.
+// Calls to it are inlined by the parser, replacing the parameters with the arguments in the body
+#[derive(Clone)]
+pub struct Definition {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub body: String,
+}
+
+#[derive(Clone)]
+pub struct Definitions {
+    functions: HashMap<String, Definition>,
+}
+
+impl Definitions {
+    pub fn new() -> Definitions {
+        Definitions {
+            functions: HashMap::new(),
+        }
+    }
+
+    // Separate the definitions of functions from the other inputs.
+    // The definitions which can't be parsed or expanded are left out along with their errors,
+    // so that the other ones can still be used
+    pub fn from_inputs<'i, S: Semantics>(inputs: &[&'i str], table: &S) -> (Definitions, Vec<&'i str>, Vec<(&'i str, &'static str)>) {
+        let mut definitions = Definitions::new();
+        let mut sources = HashMap::new();
+        let mut expressions = Vec::new();
+        let mut errors = Vec::new();
+        for input in inputs {
+            match parser::parse_definition(input, table) {
+                Some(Ok(definition)) => {
+                    sources.insert(definition.name.clone(), *input);
+                    definitions.insert(definition);
+                },
+                Some(Err(err)) => errors.push((*input, err)),
+                None => expressions.push(*input),
+            }
+        }
+
+        // Leaving out a definition breaks those calling it, which are left out in turn
+        while let Some((name, err)) = definitions.failing(table) {
+            definitions.functions.remove(&name);
+            errors.push((sources[&name], err));
+        }
+        (definitions, expressions, errors)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Definition> {
+        self.functions.get(name)
+    }
+
+    // A new definition replaces any previous one with the same name
+    pub fn insert(&mut self, definition: Definition) {
+        self.functions.insert(definition.name.clone(), definition);
+    }
+
+    // Check that every function can be expanded:
+    // its body must be well formed and it must not call itself, even through other functions
+    pub fn check<S: Semantics>(&self, table: &S) -> Result<(), &'static str> {
+        match self.failing(table) {
+            Some((_, err)) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    // The first function by name which can't be expanded, along with the error
+    fn failing<S: Semantics>(&self, table: &S) -> Option<(String, &'static str)> {
+        let environment = Environment::new(table, self);
+        let mut names: Vec<&String> = self.functions.keys().collect();
+        names.sort();
+        names.into_iter().find_map(|name| {
+            let definition = &self.functions[name];
+            let call = format!("{}({})", definition.name, vec!["0"; definition.parameters.len()].join(", "));
+            parser::parse(&call, &environment).err().map(|err| (name.clone(), err))
+        })
+    }
+}
+
+// The operators of a table along with the functions defined by the user
+pub struct Environment<'a, S: Semantics> {
+    table: &'a S,
+    definitions: &'a Definitions,
+}
+
+impl<'a, S: Semantics> Environment<'a, S> {
+    pub fn new(table: &'a S, definitions: &'a Definitions) -> Environment<'a, S> {
+        Environment {
+            table,
+            definitions,
+        }
+    }
+}
+
+impl<'a, S: Semantics> Semantics for Environment<'a, S> {
+    type Number = S::Number;
+
+    fn has_symbol(&self, name: &str) -> bool {
+        self.table.has_symbol(name)
+    }
+
+    fn lookup_binary(&self, name: &str) -> Option<&BinaryOp<S::Number>> {
+        self.table.lookup_binary(name)
+    }
+
+    fn lookup_unary(&self, name: &str) -> Option<&UnaryOp<S::Number>> {
+        self.table.lookup_unary(name)
+    }
+
+    fn lookup_function(&self, name: &str) -> Option<&FunctionOp<S::Number>> {
+        self.table.lookup_function(name)
+    }
+
+    fn lookup_const(&self, name: &str) -> Option<&ConstantOp<S::Number>> {
+        self.table.lookup_const(name)
+    }
+
+    fn lookup_definition(&self, name: &str) -> Option<&Definition> {
+        self.definitions.get(name)
+    }
+
+    fn number(&self, literal: NumberLiteral) -> Operation<S::Number> {
+        self.table.number(literal)
+    }
+
+    fn undefined(&self) -> Operation<S::Number> {
+        self.table.undefined()
+    }
+
+    fn xvar(&self) -> Operation<S::Number> {
+        self.table.xvar()
+    }
+
+    fn yvar(&self) -> Operation<S::Number> {
+        self.table.yvar()
+    }
+
+    fn zvar(&self) -> Option<Operation<S::Number>> {
+        self.table.zvar()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::Expression;
+    use crate::operator_tables::default_operator_table;
+    use crate::parser::parse;
+
+    // Parse the last input with the functions defined by the others
+    fn parse_with_definitions(inputs: &[&str]) -> Result<Expression<f64>, &'static str> {
+        let table = default_operator_table();
+        let (definitions, expressions, errors) = Definitions::from_inputs(inputs, &table);
+        if let Some((_, err)) = errors.first() {
+            return Err(*err);
+        }
+        parse(expressions.last().unwrap(), &Environment::new(&table, &definitions))
+    }
+
+    #[test]
+    fn calls_are_inlined() {
+        let expression = parse_with_definitions(&["f(x) = x^2 + 1", "g(a, b) = a f(b)", "g(2, x) + f(3)"]).unwrap();
+        assert_eq!(expression.eval_2d(3.0), 30.0);
+
+        // the parameters hide the variables with the same name, the other variables are those of the caller
+        let expression = parse_with_definitions(&["h(x) = x + y", "h(y)"]).unwrap();
+        assert_eq!(expression.eval_3d(1.0, 2.0), 4.0);
+
+        let expression = parse_with_definitions(&["f(x) = x", "f(x) = 2x", "f(1)"]).unwrap();
+        assert_eq!(expression.eval_constant(), 2.0);
+    }
+
+    #[test]
+    fn failing_definitions_are_left_out() {
+        let inputs = ["f(x) = x^2", "g(x) = h(x)", "k(x, x) = 1", "u(x) = g(x) + f(x)", "f(2) + 1"];
+        let (definitions, expressions, errors) = Definitions::from_inputs(&inputs, &default_operator_table());
+        assert!(definitions.get("f").is_some());
+        assert!(definitions.get("g").is_none() && definitions.get("u").is_none());
+        assert_eq!(expressions, vec!["f(2) + 1"]);
+        assert_eq!(errors, vec![("k(x, x) = 1", "Repeated parameter name"), ("g(x) = h(x)", "Unrecognized identifier"),
+                                ("u(x) = g(x) + f(x)", "Unrecognized identifier")]);
+    }
+
+    #[test]
+    fn arguments_are_computed_once() {
+        // the argument is used three times by the body, inlining it would take 3^30 copies of x
+        let call = (0..30).fold(String::from("x"), |argument, _| format!("f({})", argument));
+        let expression = parse_with_definitions(&["f(x) = x*x - x", call.as_str()]).unwrap();
+        let value = (0..30).fold(0.5, |x, _| x*x - x);
+        assert_eq!(expression.eval_2d(0.5), value);
+
+        let mut out = [0.0];
+        expression.eval_batch(&[0.5], &[0.0], &mut out);
+        assert_eq!(out[0], value);
+    }
+
+    #[test]
+    fn sums_in_definitions_count_the_enclosing_terms() {
+        assert!(parse_with_definitions(&["f(x) = sum(k, 1, 1000, k*x)", "sum(j, 1, 10, f(j))"]).is_ok());
+        assert!(parse_with_definitions(&["f(x) = sum(k, 1, 1000, k*x)", "sum(j, 1, 11, f(j))"]).is_err());
+    }
+
+    #[test]
+    fn definitions_are_recognized() {
+        let table = default_operator_table();
+        assert!(parser::parse_definition("x^2 + y^2 = 1", &table).is_none());
+        assert!(parser::parse_definition("x == 1", &table).is_none());
+        assert!(parser::parse_definition("f(x, 2) = x", &table).is_none());
+        assert!(parser::parse_definition("f(x, x) = x", &table).unwrap().is_err());
+        assert!(parser::parse_definition("f(x) = ", &table).unwrap().is_err());
+        let definition = parser::parse_definition("dist(a, b) = sqrt(a^2 + b^2)", &table).unwrap().unwrap();
+        assert_eq!(definition.name, "dist");
+        assert_eq!(definition.parameters, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn calls_need_as_many_arguments_as_parameters() {
+        for inputs in [&["f(a, b) = a + b", "f(x)"][..], &["f(a, b) = a + b", "f(1, 2, x)"], &["f(a) = a", "f + 1"], &["f(a) = a", "f(x"]] {
+            assert!(parse_with_definitions(inputs).is_err(), "{:?} should not parse", inputs);
+        }
+        // the calls between definitions are checked as well
+        assert!(parse_with_definitions(&["f(a) = a", "g(a) = f(a, a)", "x"]).is_err());
+    }
+
+    #[test]
+    fn recursive_definitions_are_rejected() {
+        assert!(parse_with_definitions(&["f(x) = f(x - 1)", "x"]).is_err());
+        assert!(parse_with_definitions(&["f(x) = g(x) + 1", "g(x) = 2 f(x)", "x"]).is_err());
+        // the same function can be called in its own arguments
+        let expression = parse_with_definitions(&["f(x) = 2x", "f(f(f(x)))"]).unwrap();
+        assert_eq!(expression.eval_2d(1.0), 8.0);
+    }
+}
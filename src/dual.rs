@@ -0,0 +1,373 @@
+use std::ops::{Add, Sub, Mul, Div, Neg};
+use honestintervals::IntervalSet;
+use crate::number_literal::NumberLiteral;
+use crate::plasm_number::PlasmNumber;
+use crate::interval;
+
+// A dual number holding an enclosure of the value of a function
+// and an enclosure of its derivative.
+// Evaluating an expression on dual numbers is forward mode automatic differentiation.
+#[derive(Clone)]
+pub struct Dual {
+    pub value: IntervalSet<f64>,
+    pub derivative: IntervalSet<f64>,
+}
+
+impl Dual {
+    pub fn new(value: IntervalSet<f64>, derivative: IntervalSet<f64>) -> Dual {
+        Dual {
+            value,
+            derivative,
+        }
+    }
+
+    // The independent variable, its derivative is 1
+    pub fn variable(value: IntervalSet<f64>) -> Dual {
+        Dual::new(value, IntervalSet::singleton(1.0))
+    }
+
+    // Constants have zero derivative
+    pub fn constant(value: IntervalSet<f64>) -> Dual {
+        Dual::new(value, IntervalSet::singleton(0.0))
+    }
+
+    // Apply the chain rule, given the value of f(self) and the value of f'(self)
+    fn chain(self, value: IntervalSet<f64>, derivative: IntervalSet<f64>) -> Dual {
+        Dual::new(value, derivative * self.derivative)
+    }
+}
+
+fn one() -> IntervalSet<f64> {
+    IntervalSet::singleton(1.0)
+}
+
+impl PlasmNumber for Dual {
+    fn from_literal(literal: NumberLiteral) -> Dual {
+        Dual::constant(<IntervalSet<f64> as PlasmNumber>::from_literal(literal))
+    }
+
+    fn pi() -> Dual {
+        Dual::constant(<IntervalSet<f64> as PlasmNumber>::pi())
+    }
+
+    fn e() -> Dual {
+        Dual::constant(<IntervalSet<f64> as PlasmNumber>::e())
+    }
+
+    fn pow(self, exponent: Dual) -> Dual {
+        let value = interval::pow(self.value.clone(), exponent.value.clone());
+
+        // d(u^v) = v * u^(v-1) * u' + u^v * ln(u) * v'
+        let exponent_minus_one = exponent.value.clone() - one();
+        let mut derivative = exponent.value.clone() * interval::pow(self.value.clone(), exponent_minus_one) * self.derivative;
+
+        // When the exponent is constant the second term vanishes, skipping it
+        // avoids taking the logarithm of a negative base
+        if !interval::is_zero(&exponent.derivative) {
+            derivative = derivative + value.clone() * interval::ln(self.value) * exponent.derivative;
+        }
+
+        Dual::new(value, derivative)
+    }
+
+    fn exp(self) -> Dual {
+        let exp = interval::exp(self.value.clone());
+        self.chain(exp.clone(), exp)
+    }
+
+    fn ln(self) -> Dual {
+        let x = self.value.clone();
+        self.chain(interval::ln(x.clone()), one() / x)
+    }
+
+    fn log10(self) -> Dual {
+        let x = self.value.clone();
+        let derivative = one() / (x.clone() * interval::ln(IntervalSet::singleton(10.0)));
+        self.chain(interval::log10(x), derivative)
+    }
+
+    fn log2(self) -> Dual {
+        let x = self.value.clone();
+        let derivative = one() / (x.clone() * interval::ln(IntervalSet::singleton(2.0)));
+        self.chain(interval::log2(x), derivative)
+    }
+
+    fn sqrt(self) -> Dual {
+        let sqrt = interval::sqrt(self.value.clone());
+        let derivative = IntervalSet::singleton(0.5) / sqrt.clone();
+        self.chain(sqrt, derivative)
+    }
+
+    fn sin(self) -> Dual {
+        let x = self.value.clone();
+        self.chain(interval::sin(x.clone()), interval::cos(x))
+    }
+
+    fn cos(self) -> Dual {
+        let x = self.value.clone();
+        self.chain(interval::cos(x.clone()), -interval::sin(x))
+    }
+
+    fn tan(self) -> Dual {
+        let tan = interval::tan(self.value.clone());
+        let derivative = one() + tan.clone() * tan.clone();
+        self.chain(tan, derivative)
+    }
+
+    fn asin(self) -> Dual {
+        let x = self.value.clone();
+        let derivative = one() / interval::sqrt(one() - x.clone() * x.clone());
+        self.chain(interval::asin(x), derivative)
+    }
+
+    fn acos(self) -> Dual {
+        let x = self.value.clone();
+        let derivative = -(one() / interval::sqrt(one() - x.clone() * x.clone()));
+        self.chain(interval::acos(x), derivative)
+    }
+
+    fn atan(self) -> Dual {
+        let x = self.value.clone();
+        let derivative = one() / (one() + x.clone() * x.clone());
+        self.chain(interval::atan(x), derivative)
+    }
+
+    fn abs(self) -> Dual {
+        let x = self.value.clone();
+        self.chain(interval::abs(x.clone()), interval::signum(x))
+    }
+
+    fn signum(self) -> Dual {
+        let x = self.value.clone();
+        // The jump in 0 has no derivative, we can't say anything about it
+        let derivative = if x.has_zero() { interval::whole() } else { IntervalSet::singleton(0.0) };
+        self.chain(interval::signum(x), derivative)
+    }
+
+    fn sinh(self) -> Dual {
+        let x = self.value.clone();
+        self.chain(interval::sinh(x.clone()), interval::cosh(x))
+    }
+
+    fn cosh(self) -> Dual {
+        let x = self.value.clone();
+        self.chain(interval::cosh(x.clone()), interval::sinh(x))
+    }
+
+    fn tanh(self) -> Dual {
+        let tanh = interval::tanh(self.value.clone());
+        let derivative = one() - tanh.clone() * tanh.clone();
+        self.chain(tanh, derivative)
+    }
+
+    fn floor(self) -> Dual {
+        let floor = interval::floor(self.value.clone());
+        let derivative = step_derivative(&floor);
+        self.chain(floor, derivative)
+    }
+
+    fn ceil(self) -> Dual {
+        let ceil = interval::ceil(self.value.clone());
+        let derivative = step_derivative(&ceil);
+        self.chain(ceil, derivative)
+    }
+
+    fn round(self) -> Dual {
+        let round = interval::round(self.value.clone());
+        let derivative = step_derivative(&round);
+        self.chain(round, derivative)
+    }
+
+    fn min(self, other: Dual) -> Dual {
+        let value = interval::min(self.value.clone(), other.value.clone());
+        match (interval::hull(&self.value), interval::hull(&other.value)) {
+            (Some(x), Some(y)) if x.1 < y.0 => Dual::new(value, self.derivative),
+            (Some(x), Some(y)) if y.1 < x.0 => Dual::new(value, other.derivative),
+            _ => Dual::new(value, derivative_hull(&self.derivative, &other.derivative)),
+        }
+    }
+
+    fn max(self, other: Dual) -> Dual {
+        let value = interval::max(self.value.clone(), other.value.clone());
+        match (interval::hull(&self.value), interval::hull(&other.value)) {
+            (Some(x), Some(y)) if x.0 > y.1 => Dual::new(value, self.derivative),
+            (Some(x), Some(y)) if y.0 > x.1 => Dual::new(value, other.derivative),
+            _ => Dual::new(value, derivative_hull(&self.derivative, &other.derivative)),
+        }
+    }
+
+    fn gamma(self) -> Dual {
+        let x = self.value.clone();
+        let gamma = interval::gamma(x.clone());
+        let derivative = gamma.clone() * interval::digamma(x);
+        self.chain(gamma, derivative)
+    }
+
+    fn lgamma(self) -> Dual {
+        let x = self.value.clone();
+        self.chain(interval::lgamma(x.clone()), interval::digamma(x))
+    }
+
+    fn erf(self) -> Dual {
+        let x = self.value.clone();
+        self.chain(interval::erf(x.clone()), erf_derivative(x))
+    }
+
+    fn erfc(self) -> Dual {
+        let x = self.value.clone();
+        self.chain(interval::erfc(x.clone()), -erf_derivative(x))
+    }
+
+    fn besselj(n: Dual, x: Dual) -> Dual {
+        let value = interval::besselj(n.value.clone(), x.value.clone());
+        // Only integer orders are supported, so the order can't vary continuously
+        if !interval::is_zero(&n.derivative) {
+            return Dual::new(value, interval::whole());
+        }
+
+        // J_n' = (J_(n-1) - J_(n+1)) / 2
+        let previous = interval::besselj(n.value.clone() - one(), x.value.clone());
+        let next = interval::besselj(n.value + one(), x.value.clone());
+        let derivative = (previous - next) / IntervalSet::singleton(2.0);
+        x.chain(value, derivative)
+    }
+
+    fn airy(self) -> Dual {
+        let x = self.value.clone();
+        self.chain(interval::airy(x.clone()), interval::airy_derivative(x))
+    }
+
+    fn zeta(self) -> Dual {
+        let x = self.value.clone();
+        // We have no enclosure of the derivative of zeta
+        self.chain(interval::zeta(x), interval::whole())
+    }
+
+    fn lambert_w(self) -> Dual {
+        let w = interval::lambert_w(self.value.clone());
+        // W' = 1 / (e^W (1 + W)), unbounded at the branch point
+        let derivative = one() / (interval::exp(w.clone()) * (one() + w.clone()));
+        self.chain(w, derivative)
+    }
+
+    fn less(self, other: Dual) -> Dual {
+        condition(interval::less(self.value, other.value))
+    }
+
+    fn less_equal(self, other: Dual) -> Dual {
+        condition(interval::less_equal(self.value, other.value))
+    }
+
+    fn equal(self, other: Dual) -> Dual {
+        condition(interval::equal(self.value, other.value))
+    }
+
+    fn logical_not(self) -> Dual {
+        condition(interval::logical_not(self.value))
+    }
+
+    fn logical_and(self, other: Dual) -> Dual {
+        condition(interval::logical_and(self.value, other.value))
+    }
+
+    fn logical_or(self, other: Dual) -> Dual {
+        condition(interval::logical_or(self.value, other.value))
+    }
+
+    fn select(condition: Dual, then: Dual, otherwise: Dual) -> Dual {
+        let value = interval::select(condition.value.clone(), then.value.clone(), otherwise.value.clone());
+        if interval::is_zero(&condition.value) {
+            otherwise
+        } else if condition.value.has_zero() {
+            // Undecided, the function may jump between the branches
+            Dual::new(value, interval::whole())
+        } else {
+            // Either decided for the first branch, or undefined
+            Dual::new(value, then.derivative)
+        }
+    }
+
+    fn undefined() -> Dual {
+        Dual::new(interval::empty(), interval::empty())
+    }
+}
+
+// Comparisons and logical operators are constant while they are decided,
+// otherwise they jump and there's nothing we can say about the derivative
+fn condition(value: IntervalSet<f64>) -> Dual {
+    let derivative = step_derivative(&value);
+    Dual::new(value, derivative)
+}
+
+// erf' = 2/sqrt(pi) e^(-x^2)
+fn erf_derivative(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    IntervalSet::singleton(2.0) / interval::sqrt(<IntervalSet<f64> as PlasmNumber>::pi()) * interval::exp(-(x.clone() * x))
+}
+
+// Step functions are flat, unless the enclosure crosses a jump
+fn step_derivative(value: &IntervalSet<f64>) -> IntervalSet<f64> {
+    match interval::hull(value) {
+        Some((start, end)) if start == end => IntervalSet::singleton(0.0),
+        _ => interval::whole(),
+    }
+}
+
+// Where the two operands of min or max may cross, the derivative can be the one of either operand,
+// or anything in between at a corner
+fn derivative_hull(x: &IntervalSet<f64>, y: &IntervalSet<f64>) -> IntervalSet<f64> {
+    match (interval::hull(x), interval::hull(y)) {
+        (Some(x), Some(y)) => IntervalSet::new(x.0.min(y.0), x.1.max(y.1)),
+        _ => interval::whole(),
+    }
+}
+
+impl From<f64> for Dual {
+    fn from(value: f64) -> Dual {
+        Dual::constant(IntervalSet::singleton(value))
+    }
+}
+
+impl Neg for Dual {
+    type Output = Dual;
+
+    fn neg(self) -> Dual {
+        Dual::new(-self.value, -self.derivative)
+    }
+}
+
+impl Add for Dual {
+    type Output = Dual;
+
+    fn add(self, other: Dual) -> Dual {
+        Dual::new(self.value + other.value, self.derivative + other.derivative)
+    }
+}
+
+impl Sub for Dual {
+    type Output = Dual;
+
+    fn sub(self, other: Dual) -> Dual {
+        Dual::new(self.value - other.value, self.derivative - other.derivative)
+    }
+}
+
+impl Mul for Dual {
+    type Output = Dual;
+
+    fn mul(self, other: Dual) -> Dual {
+        let value = self.value.clone() * other.value.clone();
+        let derivative = self.derivative * other.value + self.value * other.derivative;
+        Dual::new(value, derivative)
+    }
+}
+
+impl Div for Dual {
+    type Output = Dual;
+
+    fn div(self, other: Dual) -> Dual {
+        // (u/v)' = (u' - (u/v) * v') / v
+        let quotient = self.value / other.value.clone();
+        let derivative = (self.derivative - quotient.clone() * other.derivative) / other.value;
+        Dual::new(quotient, derivative)
+    }
+}
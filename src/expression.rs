@@ -6,17 +6,53 @@ pub struct InputSpace<Number: Clone> {
     pub y: Number,
 }
 
+#[derive(Clone)]
 pub enum Operation<Number: Clone + From<f64>> {
     BinaryOperation(fn (Number, Number) -> Number),
+    // A binary operation along with its version on whole arrays, used by batched evaluation
+    BatchedBinaryOperation(fn (Number, Number) -> Number, BatchedBinary<Number>),
     UnaryOperation(fn (Number) -> Number),
+    // A function and the number of its arguments
+    Function(fn (&[Number]) -> Number, usize),
     Constant(Number),
     Variable(fn (InputSpace<Number>) -> Number),
+    // A value bound by an enclosing sum or product, its index, or by a call, one of its arguments, by nesting depth
+    Index(usize),
+    Reduction(Reduction<Number>),
+    // A call to a function defined by the user: its body and the number of its arguments,
+    // which are computed once and read by the body as indices
+    Call(Vec<Operation<Number>>, usize),
+}
+
+// A sum or a product of the terms computed by a sub-program,
+// which is run once for each value of the index from the first to the last, both included
+#[derive(Clone)]
+pub struct Reduction<Number: Clone + From<f64>> {
+    pub body: Vec<Operation<Number>>,
+    pub first: i64,
+    pub last: i64,
+    // Either addition or multiplication, along with its identity
+    pub combine: fn (Number, Number) -> Number,
+    pub identity: Number,
+}
+
+// A binary operation applied to the elements of two arrays, storing the results in the first one
+pub type BatchedBinary<Number> = fn (&mut [Number], &[Number]);
+
+// Apply the operation to the elements of two arrays, storing the results in the first one.
+// Called with a closure the loop is compiled for each operation, so that on floats it can be vectorized
+#[inline(always)]
+pub fn elementwise<Number: Clone, F: Fn(Number, Number) -> Number>(a: &mut [Number], b: &[Number], f: F) {
+    for (a, b) in a.iter_mut().zip(b.iter()) {
+        *a = f(a.clone(), b.clone());
+    }
 }
 
 pub enum ExprType {
     Expr2d,
     Expr3d,
     ExprImplicit,
+    Constant,
 }
 
 // We represent an expression in its postfix form
@@ -62,24 +98,244 @@ impl<Number: Clone + From<f64>> Expression<Number> {
         })
     }
 
+    // Evaluate an expression which does not depend on any variable
+    pub fn eval_constant(&self) -> Number {
+        self.eval(InputSpace {
+            x: Number::from(0.0),
+            y: Number::from(0.0),
+        })
+    }
+
+    // The implicit curve where the expression is equal to level, represented as self - level = 0
+    pub fn level_set(&self, level: Number) -> Expression<Number> where
+        Number: std::ops::Sub<Output = Number> {
+        let mut ops = self.ops.clone();
+        ops.push(Operation::Constant(level));
+        ops.push(Operation::BinaryOperation(|a, b| a - b));
+        Expression::new(ops, ExprType::ExprImplicit)
+    }
+
+    // Evaluate the expression in many points at once, out[i] is the value in (x[i], y[i]).
+    // Every operation is run over all the points before the next one, instead of running
+    // the whole program for every point
+    pub fn eval_batch(&self, x: &[Number], y: &[Number], out: &mut [Number]) {
+        assert!(x.len() == y.len() && x.len() == out.len(), "the points and the values must be as many");
+        match run_batch(&self.ops, x, y, &mut Vec::new()) {
+            Column::Scalar(value) => {
+                for result in out.iter_mut() {
+                    *result = value.clone();
+                }
+            },
+            Column::Array(values) => out.clone_from_slice(&values),
+        }
+    }
+
     fn eval(&self, input: InputSpace<Number>) -> Number {
-        let mut stack = Vec::new();
-
-        for op in self.ops.iter() {
-            match op {
-                Operation::Constant(c) => stack.push(c.clone()),
-                Operation::Variable(f) => stack.push(f(input.clone())),
-                Operation::UnaryOperation(f) => {
-                    let arg = stack.pop().unwrap();
-                    stack.push(f(arg))
-                },
-                Operation::BinaryOperation(f) => {
-                    let arg2 = stack.pop().unwrap();
-                    let arg1 = stack.pop().unwrap();
-                    stack.push(f(arg1, arg2))
+        run(&self.ops, &input, &mut Vec::new())
+    }
+}
+
+// Run a program in the stack machine.
+// The values of the indices of the enclosing sums and products, and the arguments of the enclosing calls,
+// are kept in indices, the innermost is the last
+fn run<Number: Clone + From<f64>>(ops: &[Operation<Number>], input: &InputSpace<Number>, indices: &mut Vec<Number>) -> Number {
+    let mut stack = Vec::new();
+
+    for op in ops.iter() {
+        match op {
+            Operation::Constant(c) => stack.push(c.clone()),
+            Operation::Variable(f) => stack.push(f(input.clone())),
+            Operation::UnaryOperation(f) => {
+                let arg = stack.pop().unwrap();
+                stack.push(f(arg))
+            },
+            Operation::BinaryOperation(f) | Operation::BatchedBinaryOperation(f, _) => {
+                let arg2 = stack.pop().unwrap();
+                let arg1 = stack.pop().unwrap();
+                stack.push(f(arg1, arg2))
+            },
+            Operation::Function(f, arity) => {
+                let args = stack.split_off(stack.len() - arity);
+                stack.push(f(&args))
+            },
+            Operation::Index(depth) => stack.push(indices[*depth].clone()),
+            Operation::Reduction(reduction) => {
+                let mut result = reduction.identity.clone();
+                for index in reduction.first..=reduction.last {
+                    indices.push(Number::from(index as f64));
+                    let term = run(&reduction.body, input, indices);
+                    indices.pop();
+                    result = (reduction.combine)(result, term);
+                }
+                stack.push(result)
+            },
+            Operation::Call(body, arity) => {
+                let depth = indices.len();
+                indices.extend(stack.split_off(stack.len() - arity));
+                let result = run(body, input, indices);
+                indices.truncate(depth);
+                stack.push(result)
+            },
+        }
+    }
+    stack.pop().unwrap()
+}
+
+// A value of the batched stack machine, the values which are the same in every point are not repeated
+#[derive(Clone)]
+enum Column<Number> {
+    Scalar(Number),
+    Array(Vec<Number>),
+}
+
+impl<Number: Clone> Column<Number> {
+    // The values in every point
+    fn into_array(self, len: usize) -> Vec<Number> {
+        match self {
+            Column::Scalar(value) => vec![value; len],
+            Column::Array(values) => values,
+        }
+    }
+
+    fn is_scalar(&self) -> bool {
+        match self {
+            Column::Scalar(_) => true,
+            Column::Array(_) => false,
+        }
+    }
+
+    fn get(&self, i: usize) -> Number {
+        match self {
+            Column::Scalar(value) => value.clone(),
+            Column::Array(values) => values[i].clone(),
+        }
+    }
+}
+
+// Apply a binary operation to two columns, with its batched version if it has one
+fn combine<Number: Clone>(a: Column<Number>, b: Column<Number>, f: fn (Number, Number) -> Number, batched: Option<BatchedBinary<Number>>, len: usize) -> Column<Number> {
+    match (a, b, batched) {
+        (Column::Scalar(a), Column::Scalar(b), _) => Column::Scalar(f(a, b)),
+        (a, b, Some(batched)) => {
+            let mut a = a.into_array(len);
+            match b {
+                Column::Array(b) => batched(&mut a, &b),
+                Column::Scalar(b) => batched(&mut a, &vec![b; len]),
+            }
+            Column::Array(a)
+        },
+        (Column::Array(mut a), b, None) => {
+            for (i, value) in a.iter_mut().enumerate() {
+                *value = f(value.clone(), b.get(i));
+            }
+            Column::Array(a)
+        },
+        (Column::Scalar(a), Column::Array(mut b), None) => {
+            for value in b.iter_mut() {
+                *value = f(a.clone(), value.clone());
+            }
+            Column::Array(b)
+        },
+    }
+}
+
+// Run a program in the stack machine over all the points at once, an operation at a time
+fn run_batch<Number: Clone + From<f64>>(ops: &[Operation<Number>], x: &[Number], y: &[Number], indices: &mut Vec<Column<Number>>) -> Column<Number> {
+    let len = x.len();
+    let mut stack: Vec<Column<Number>> = Vec::new();
+
+    for op in ops.iter() {
+        match op {
+            Operation::Constant(c) => stack.push(Column::Scalar(c.clone())),
+            Operation::Variable(f) => {
+                let values = x.iter().zip(y.iter())
+                    .map(|(x, y)| f(InputSpace { x: x.clone(), y: y.clone() }))
+                    .collect();
+                stack.push(Column::Array(values))
+            },
+            Operation::UnaryOperation(f) => {
+                let arg = match stack.pop().unwrap() {
+                    Column::Scalar(value) => Column::Scalar(f(value)),
+                    Column::Array(mut values) => {
+                        for value in values.iter_mut() {
+                            *value = f(value.clone());
+                        }
+                        Column::Array(values)
+                    },
+                };
+                stack.push(arg)
+            },
+            Operation::BinaryOperation(f) => {
+                let arg2 = stack.pop().unwrap();
+                let arg1 = stack.pop().unwrap();
+                stack.push(combine(arg1, arg2, *f, None, len))
+            },
+            Operation::BatchedBinaryOperation(f, batched) => {
+                let arg2 = stack.pop().unwrap();
+                let arg1 = stack.pop().unwrap();
+                stack.push(combine(arg1, arg2, *f, Some(*batched), len))
+            },
+            Operation::Function(f, arity) => {
+                let args = stack.split_off(stack.len() - arity);
+                let result = if args.iter().all(Column::is_scalar) {
+                    let args: Vec<Number> = args.iter().map(|arg| arg.get(0)).collect();
+                    Column::Scalar(f(&args))
+                } else {
+                    let mut point_args = Vec::with_capacity(*arity);
+                    let values = (0..len)
+                        .map(|i| {
+                            point_args.clear();
+                            point_args.extend(args.iter().map(|arg| arg.get(i)));
+                            f(&point_args)
+                        })
+                        .collect();
+                    Column::Array(values)
+                };
+                stack.push(result)
+            },
+            Operation::Index(depth) => stack.push(indices[*depth].clone()),
+            Operation::Reduction(reduction) => {
+                let mut result = Column::Scalar(reduction.identity.clone());
+                for index in reduction.first..=reduction.last {
+                    indices.push(Column::Scalar(Number::from(index as f64)));
+                    let term = run_batch(&reduction.body, x, y, indices);
+                    indices.pop();
+                    result = combine(result, term, reduction.combine, None, len);
                 }
+                stack.push(result)
+            },
+            Operation::Call(body, arity) => {
+                let depth = indices.len();
+                indices.extend(stack.split_off(stack.len() - arity));
+                let result = run_batch(body, x, y, indices);
+                indices.truncate(depth);
+                stack.push(result)
+            },
+        }
+    }
+    stack.pop().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::operator_tables::default_operator_table;
+    use crate::parser::parse;
+
+    #[test]
+    fn batches_give_the_values_in_every_point() {
+        let table = default_operator_table();
+        let x: Vec<f64> = (0..100).map(|i| i as f64 / 10.0 - 5.0).collect();
+        let y: Vec<f64> = (0..100).map(|i| (i as f64 / 7.0).sin()).collect();
+
+        for input in ["x + y", "2 * x - y / 3", "sin(x) * cos(y)", "max(x, 1)", "if(x < 0, y, x^2)",
+                      "sum(k, 1, 5, sin(k*x)/k)", "pi", "x / 0"] {
+            let expression = parse(input, &table).unwrap();
+            let mut out = vec![0.0; x.len()];
+            expression.eval_batch(&x, &y, &mut out);
+            for i in 0..x.len() {
+                let value = expression.eval_3d(x[i], y[i]);
+                assert!(value == out[i] || (value.is_nan() && out[i].is_nan()), "{} at ({}, {})", input, x[i], y[i]);
             }
         }
-        stack.pop().unwrap()
     }
 }
@@ -0,0 +1,65 @@
+use crate::expression::Expression;
+use crate::semantics::Semantics;
+use crate::parser;
+
+// A field of directions in the plane, drawn as arrows on a grid
+pub enum Field {
+    // The vector (P(x, y), Q(x, y)) in every point
+    Vector(Expression<f64>, Expression<f64>),
+    // The slope of the solutions of the differential equation dy/dx = f(x, y)
+    Slope(Expression<f64>),
+}
+
+impl Field {
+    // The vector of the field in (x, y), for slope fields it is (1, f(x, y))
+    pub fn direction(&self, x: f64, y: f64) -> (f64, f64) {
+        match self {
+            Field::Vector(p, q) => (p.eval_3d(x, y), q.eval_3d(x, y)),
+            Field::Slope(f) => (1.0, f.eval_3d(x, y)),
+        }
+    }
+
+    // Only the direction of slope fields is meaningful, while vector fields also have a magnitude
+    pub fn has_magnitude(&self) -> bool {
+        match self {
+            Field::Vector(_, _) => true,
+            Field::Slope(_) => false,
+        }
+    }
+}
+
+// Recognize vector fields, like (-y, x), and slope fields, like dy/dx = x*y
+pub fn parse<S: Semantics<Number = f64>>(input: &str, table: &S) -> Option<Result<Field, &'static str>> {
+    if let Some(components) = parser::parse_vector_field(input, table) {
+        return Some(components.map(|(p, q)| Field::Vector(p, q)));
+    }
+    parser::parse_slope_field(input, table).map(|slope| slope.map(Field::Slope))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator_tables::default_operator_table;
+
+    fn parse_field(input: &str) -> Field {
+        parse(input, &default_operator_table()).unwrap().unwrap()
+    }
+
+    #[test]
+    fn directions_are_evaluated_in_the_point() {
+        let vector = parse_field("(-y, x)");
+        assert_eq!(vector.direction(1.0, 2.0), (-2.0, 1.0));
+        assert!(vector.has_magnitude());
+
+        let slope = parse_field("y' = x*y");
+        assert_eq!(slope.direction(2.0, 3.0), (1.0, 6.0));
+        assert!(!slope.has_magnitude());
+    }
+
+    #[test]
+    fn other_inputs_are_not_fields() {
+        for input in &["x^2 + y^2 = 1", "(x + 1) * y", "sin(x)"] {
+            assert!(parse(input, &default_operator_table()).is_none(), "{} is not a field", input);
+        }
+    }
+}
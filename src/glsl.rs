@@ -0,0 +1,380 @@
+use std::ops::{Add, Sub, Mul, Div, Neg};
+use crate::expression::Expression;
+use crate::colormap::{self, Colormap};
+use crate::number_literal::NumberLiteral;
+use crate::plasm_number::PlasmNumber;
+
+// Expressions are translated to GLSL by evaluating them on numbers which are the code computing them,
+// so that shaders can evaluate functions on every vertex or pixel.
+// The shader templates in assets/shaders get the generated functions in place of their #pragma plasm_functions
+
+// The functions the translated code calls
+const PRELUDE: &str = include_str!("../assets/shaders/plasm.glsl");
+
+const PLACEHOLDER: &str = "#pragma plasm_functions";
+
+// Longest code compiled, sums of many terms are unrolled and can get too long for a shader
+const MAX_CODE_LENGTH: usize = 20_000;
+
+// The GLSL code computing a number from the variables x and y
+#[derive(Clone, Debug)]
+pub struct Glsl {
+    // None when some function has no GLSL version, like the special functions
+    code: Option<String>,
+}
+
+impl Glsl {
+    pub fn variable(name: &str) -> Glsl {
+        Glsl::new(name.to_string())
+    }
+
+    pub fn code(&self) -> Option<&str> {
+        self.code.as_deref()
+    }
+
+    fn new(code: String) -> Glsl {
+        Glsl {
+            code: if code.len() <= MAX_CODE_LENGTH { Some(code) } else { None },
+        }
+    }
+
+    fn unsupported() -> Glsl {
+        Glsl { code: None }
+    }
+
+    // A call to a GLSL function, or to one of the prelude
+    fn call(name: &str, arguments: &[&Glsl]) -> Glsl {
+        let arguments: Option<Vec<&str>> = arguments.iter().map(|argument| argument.code()).collect();
+        match arguments {
+            Some(arguments) => Glsl::new(format!("{}({})", name, arguments.join(", "))),
+            None => Glsl::unsupported(),
+        }
+    }
+
+    fn infix(self, operator: &str, other: Glsl) -> Glsl {
+        match (self.code, other.code) {
+            (Some(a), Some(b)) => Glsl::new(format!("({} {} {})", a, operator, b)),
+            _ => Glsl::unsupported(),
+        }
+    }
+
+    // Comparisons and logical operators are booleans in GLSL, they are turned into 1 and 0
+    fn truth(self, operator: &str, other: Glsl) -> Glsl {
+        Glsl::call("float", &[&self.infix(operator, other)])
+    }
+}
+
+// A GLSL float literal, the values which have none are computed by the prelude
+fn literal(value: f64) -> String {
+    if value.is_nan() {
+        String::from("plasm_undefined()")
+    } else if value == f64::INFINITY {
+        String::from("plasm_infinity()")
+    } else if value == f64::NEG_INFINITY {
+        String::from("(-plasm_infinity())")
+    } else if value < 0.0 {
+        format!("({:?})", value)
+    } else {
+        format!("{:?}", value)
+    }
+}
+
+impl PlasmNumber for Glsl {
+    // the literal is rounded to a float by the shader compiler
+    fn from_literal(literal: NumberLiteral) -> Glsl {
+        Glsl::from(literal.value)
+    }
+
+    fn pi() -> Glsl {
+        Glsl::from(std::f64::consts::PI)
+    }
+
+    fn e() -> Glsl {
+        Glsl::from(std::f64::consts::E)
+    }
+
+    fn pow(self, exponent: Glsl) -> Glsl {
+        Glsl::call("plasm_pow", &[&self, &exponent])
+    }
+
+    fn exp(self) -> Glsl {
+        Glsl::call("exp", &[&self])
+    }
+
+    fn ln(self) -> Glsl {
+        Glsl::call("plasm_log", &[&self])
+    }
+
+    fn log10(self) -> Glsl {
+        Glsl::call("plasm_log", &[&self]) * Glsl::from(std::f64::consts::LOG10_E)
+    }
+
+    fn log2(self) -> Glsl {
+        Glsl::call("plasm_log2", &[&self])
+    }
+
+    fn sqrt(self) -> Glsl {
+        Glsl::call("plasm_sqrt", &[&self])
+    }
+
+    fn sin(self) -> Glsl {
+        Glsl::call("sin", &[&self])
+    }
+
+    fn cos(self) -> Glsl {
+        Glsl::call("cos", &[&self])
+    }
+
+    fn tan(self) -> Glsl {
+        Glsl::call("tan", &[&self])
+    }
+
+    fn asin(self) -> Glsl {
+        Glsl::call("plasm_asin", &[&self])
+    }
+
+    fn acos(self) -> Glsl {
+        Glsl::call("plasm_acos", &[&self])
+    }
+
+    fn atan(self) -> Glsl {
+        Glsl::call("atan", &[&self])
+    }
+
+    fn abs(self) -> Glsl {
+        Glsl::call("abs", &[&self])
+    }
+
+    fn signum(self) -> Glsl {
+        Glsl::call("plasm_signum", &[&self])
+    }
+
+    fn sinh(self) -> Glsl {
+        Glsl::call("sinh", &[&self])
+    }
+
+    fn cosh(self) -> Glsl {
+        Glsl::call("cosh", &[&self])
+    }
+
+    fn tanh(self) -> Glsl {
+        Glsl::call("tanh", &[&self])
+    }
+
+    fn floor(self) -> Glsl {
+        Glsl::call("floor", &[&self])
+    }
+
+    fn ceil(self) -> Glsl {
+        Glsl::call("ceil", &[&self])
+    }
+
+    fn round(self) -> Glsl {
+        Glsl::call("plasm_round", &[&self])
+    }
+
+    fn min(self, other: Glsl) -> Glsl {
+        Glsl::call("min", &[&self, &other])
+    }
+
+    fn max(self, other: Glsl) -> Glsl {
+        Glsl::call("max", &[&self, &other])
+    }
+
+    // the special functions are only computed on the CPU
+    fn gamma(self) -> Glsl {
+        Glsl::unsupported()
+    }
+
+    fn lgamma(self) -> Glsl {
+        Glsl::unsupported()
+    }
+
+    fn erf(self) -> Glsl {
+        Glsl::unsupported()
+    }
+
+    fn erfc(self) -> Glsl {
+        Glsl::unsupported()
+    }
+
+    fn besselj(_n: Glsl, _x: Glsl) -> Glsl {
+        Glsl::unsupported()
+    }
+
+    fn airy(self) -> Glsl {
+        Glsl::unsupported()
+    }
+
+    fn zeta(self) -> Glsl {
+        Glsl::unsupported()
+    }
+
+    fn lambert_w(self) -> Glsl {
+        Glsl::unsupported()
+    }
+
+    fn less(self, other: Glsl) -> Glsl {
+        self.truth("<", other)
+    }
+
+    fn less_equal(self, other: Glsl) -> Glsl {
+        self.truth("<=", other)
+    }
+
+    fn equal(self, other: Glsl) -> Glsl {
+        self.truth("==", other)
+    }
+
+    fn logical_not(self) -> Glsl {
+        self.truth("==", Glsl::from(0.0))
+    }
+
+    fn logical_and(self, other: Glsl) -> Glsl {
+        self.infix("!=", Glsl::from(0.0)).truth("&&", other.infix("!=", Glsl::from(0.0)))
+    }
+
+    fn logical_or(self, other: Glsl) -> Glsl {
+        self.infix("!=", Glsl::from(0.0)).truth("||", other.infix("!=", Glsl::from(0.0)))
+    }
+
+    fn select(condition: Glsl, then: Glsl, otherwise: Glsl) -> Glsl {
+        Glsl::call("plasm_select", &[&condition, &then, &otherwise])
+    }
+
+    fn undefined() -> Glsl {
+        Glsl::from(std::f64::NAN)
+    }
+}
+
+impl From<f64> for Glsl {
+    fn from(value: f64) -> Glsl {
+        Glsl::new(literal(value))
+    }
+}
+
+impl Neg for Glsl {
+    type Output = Glsl;
+
+    fn neg(self) -> Glsl {
+        match self.code {
+            Some(code) => Glsl::new(format!("(-{})", code)),
+            None => Glsl::unsupported(),
+        }
+    }
+}
+
+impl Add for Glsl {
+    type Output = Glsl;
+
+    fn add(self, other: Glsl) -> Glsl {
+        self.infix("+", other)
+    }
+}
+
+impl Sub for Glsl {
+    type Output = Glsl;
+
+    fn sub(self, other: Glsl) -> Glsl {
+        self.infix("-", other)
+    }
+}
+
+impl Mul for Glsl {
+    type Output = Glsl;
+
+    fn mul(self, other: Glsl) -> Glsl {
+        self.infix("*", other)
+    }
+}
+
+impl Div for Glsl {
+    type Output = Glsl;
+
+    fn div(self, other: Glsl) -> Glsl {
+        self.infix("/", other)
+    }
+}
+
+// The GLSL function plasm_function(x, y) computing the expression, along with the prelude.
+// None when the expression uses functions which have no GLSL version
+pub fn function_source(expression: &Expression<Glsl>) -> Option<String> {
+    let value = expression.eval_3d(Glsl::variable("x"), Glsl::variable("y"));
+    value.code().map(|code| format!("{}\nfloat plasm_function(float x, float y)\n{{\n    return {};\n}}\n", PRELUDE, code))
+}
+
+// The GLSL function plasm_colormap(t) giving the color of the fraction t of the range, interpolated as on the CPU
+pub fn colormap_source(colormap: &Colormap) -> String {
+    let colors = colormap.colors();
+    let constants: Vec<String> = colors.iter()
+        .map(|color| {
+            let (red, green, blue) = colormap::rgb(*color);
+            format!("vec3({:?}, {:?}, {:?})", red, green, blue)
+        })
+        .collect();
+    format!("const vec3 PLASM_COLORS[{count}] = vec3[{count}]({colors});\n\n\
+             vec3 plasm_colormap(float t)\n{{\n    \
+             float position = clamp(t, 0.0, 1.0) * {last:?};\n    \
+             int i = min(int(floor(position)), {before_last});\n    \
+             return mix(PLASM_COLORS[i], PLASM_COLORS[i + 1], position - float(i));\n}}\n",
+            count = colors.len(), colors = constants.join(", "), last = (colors.len() - 1) as f32, before_last = colors.len() - 2)
+}
+
+// The source of a shader, with the given functions in place of the placeholder of the template
+pub fn program_source(template: &str, functions: &[&str]) -> String {
+    template.replace(PLACEHOLDER, &functions.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator_tables::glsl_operator_table;
+    use crate::parser::parse;
+
+    fn translate(input: &str) -> Option<String> {
+        let expression = parse(input, &glsl_operator_table()).unwrap();
+        expression.eval_3d(Glsl::variable("x"), Glsl::variable("y")).code().map(String::from)
+    }
+
+    #[test]
+    fn expressions_translate_to_glsl() {
+        assert_eq!(translate("x^2 + sin(y)").as_deref(), Some("(plasm_pow(x, 2.0) + sin(y))"));
+        assert_eq!(translate("2 - x / 4").as_deref(), Some("(2.0 - (x / 4.0))"));
+        assert_eq!(translate("x < 1 && y < 2").as_deref(), Some("float(((float((x < 1.0)) != 0.0) && (float((y < 2.0)) != 0.0)))"));
+        assert_eq!(translate("x^2 + y^2 = 5").as_deref(), Some("((plasm_pow(x, 2.0) + plasm_pow(y, 2.0)) - 5.0)"));
+        assert_eq!(translate("if(x < 0, 1, y)").as_deref(), Some("plasm_select(float((x < 0.0)), 1.0, y)"));
+        assert_eq!(translate("sqrt(x) + asin(y)").as_deref(), Some("(plasm_sqrt(x) + plasm_asin(y))"));
+        assert_eq!(translate("sum(k, 1, 3, k x)").as_deref(), Some("(((0.0 + (1.0 * x)) + (2.0 * x)) + (3.0 * x))"));
+    }
+
+    #[test]
+    fn special_functions_are_left_to_the_cpu() {
+        assert_eq!(translate("gamma(x) + y"), None);
+        assert_eq!(translate("sin(x) * besselj(2, y)"), None);
+        assert_eq!(translate("sum(k, 1, 9000, sin(k x) / k)"), None);
+        assert!(function_source(&parse("zeta(x)", &glsl_operator_table()).unwrap()).is_none());
+    }
+
+    #[test]
+    fn values_without_literals_use_the_prelude() {
+        assert_eq!(literal(std::f64::NAN), "plasm_undefined()");
+        assert_eq!(literal(std::f64::NEG_INFINITY), "(-plasm_infinity())");
+        assert_eq!(literal(-0.5), "(-0.5)");
+        assert_eq!(literal(1e-20), "1e-20");
+    }
+
+    #[test]
+    fn shaders_get_the_functions() {
+        let function = function_source(&parse("x y", &glsl_operator_table()).unwrap()).unwrap();
+        assert!(function.contains("float plasm_pow(float x, float y)"));
+        assert!(function.ends_with("float plasm_function(float x, float y)\n{\n    return (x * y);\n}\n"));
+
+        let colormap = colormap_source(&Colormap::Viridis);
+        assert!(colormap.starts_with("const vec3 PLASM_COLORS[9] = vec3[9](vec3("));
+        assert!(colormap.contains("int i = min(int(floor(position)), 7);"));
+
+        let source = program_source("#pragma plasm_functions\nvoid main() {}", &[&function, &colormap]);
+        assert!(!source.contains(PLACEHOLDER));
+        assert!(source.ends_with("void main() {}"));
+    }
+}
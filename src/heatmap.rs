@@ -0,0 +1,71 @@
+use crate::expression::Expression;
+use crate::semantics::Semantics;
+use crate::colormap::{Colormap, Levels};
+use crate::parser;
+use crate::glsl::{self, Glsl};
+use honestintervals::IntervalSet;
+
+// A function of the plane drawn as a heatmap, with contour lines where it is constant
+pub struct Heatmap {
+    pub function: Expression<f64>,
+    // The same function on intervals, used to find the contour lines
+    pub interval_function: Expression<IntervalSet<f64>>,
+    // The same function in GLSL, when it can be evaluated by a shader
+    pub shader: Option<String>,
+    pub colormap: Colormap,
+    pub levels: Levels,
+}
+
+// A function of the plane drawn as a surface in 3d, colored by its height, with its contour lines
+pub struct Surface {
+    pub function: Expression<f64>,
+    pub colormap: Colormap,
+    pub levels: Levels,
+}
+
+// The colormap and the levels of the contour lines, written after the function
+fn options(colormap: Option<String>, levels: Vec<Expression<f64>>) -> Result<(Colormap, Levels), &'static str> {
+    let colormap = match colormap {
+        Some(name) => Colormap::from_name(&name).ok_or("Unknown colormap, use viridis or diverging")?,
+        None => Colormap::Viridis,
+    };
+    Ok((colormap, Levels::new(levels.iter().map(Expression::eval_constant).collect())))
+}
+
+// Recognize heatmaps, like heatmap(x^2 - y^2, diverging, -1, 0, 1)
+pub fn parse<S, I, G>(input: &str, table: &S, interval_table: &I, glsl_table: &G) -> Option<Result<Heatmap, &'static str>> where
+    S: Semantics<Number = f64>,
+    I: Semantics<Number = IntervalSet<f64>>,
+    G: Semantics<Number = Glsl> {
+    let arguments = parser::parse_contour_plot(input, "heatmap", table)?;
+
+    Some(arguments.and_then(|(function, colormap, levels)| {
+        let (colormap, levels) = options(colormap, levels)?;
+        // The input has already been recognized as a heatmap
+        let (interval_function, _, _) = parser::parse_contour_plot(input, "heatmap", interval_table).unwrap()?;
+        let shader = parser::parse_contour_plot(input, "heatmap", glsl_table).unwrap().ok()
+            .and_then(|(function, _, _)| glsl::function_source(&function));
+
+        Ok(Heatmap {
+            function,
+            interval_function,
+            shader,
+            colormap,
+            levels,
+        })
+    }))
+}
+
+// Recognize surfaces with their contour lines, like surface(x^2 - y^2, diverging), which are written as heatmaps
+pub fn parse_surface<S: Semantics<Number = f64>>(input: &str, table: &S) -> Option<Result<Surface, &'static str>> {
+    let arguments = parser::parse_contour_plot(input, "surface", table)?;
+
+    Some(arguments.and_then(|(function, colormap, levels)| {
+        let (colormap, levels) = options(colormap, levels)?;
+        Ok(Surface {
+            function,
+            colormap,
+            levels,
+        })
+    }))
+}
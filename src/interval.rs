@@ -0,0 +1,617 @@
+// Math functions on sets of intervals.
+// Where honestintervals already provides a function we just call it,
+// the others are built here making sure the results are rigorous enclosures.
+use honestintervals::IntervalSet;
+use honestintervals::transc::Transc;
+use crate::number_literal::{next_up, next_down};
+use crate::plasm_number;
+use crate::special;
+
+pub fn whole() -> IntervalSet<f64> {
+    IntervalSet::new(std::f64::NEG_INFINITY, std::f64::INFINITY)
+}
+
+// The disjoint intervals composing the set, sorted
+pub fn intervals(x: &IntervalSet<f64>) -> Vec<(f64, f64)> {
+    x.clone().into()
+}
+
+// Build a set from a list of intervals, which may overlap
+pub fn from_intervals(mut intervals: Vec<(f64, f64)>) -> IntervalSet<f64> {
+    intervals.retain(|interval| interval.0 <= interval.1);
+    intervals.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    let mut merged: Vec<(f64, f64)> = Vec::with_capacity(intervals.len());
+    for interval in intervals {
+        match merged.last_mut() {
+            Some(last) if interval.0 <= last.1 => last.1 = last.1.max(interval.1),
+            _ => merged.push(interval),
+        }
+    }
+
+    IntervalSet::from(merged)
+}
+
+// The smallest interval containing the whole set
+pub fn hull(x: &IntervalSet<f64>) -> Option<(f64, f64)> {
+    let intervals = intervals(x);
+    match (intervals.first(), intervals.last()) {
+        (Some(first), Some(last)) => Some((first.0, last.1)),
+        _ => None,
+    }
+}
+
+pub fn is_zero(x: &IntervalSet<f64>) -> bool {
+    intervals(x) == vec![(0.0, 0.0)]
+}
+
+pub fn empty() -> IntervalSet<f64> {
+    from_intervals(Vec::new())
+}
+
+pub fn union(x: IntervalSet<f64>, y: IntervalSet<f64>) -> IntervalSet<f64> {
+    let mut all = intervals(&x);
+    all.extend(intervals(&y));
+    from_intervals(all)
+}
+
+// Bounds of a value approximated within the given error
+fn lower(value: f64, error: f64) -> f64 {
+    if value.is_infinite() { next_down(value) } else { next_down(value - error) }
+}
+
+fn upper(value: f64, error: f64) -> f64 {
+    if value.is_infinite() { next_up(value) } else { next_up(value + error) }
+}
+
+// The parts of the intervals of the set inside the domain
+fn clip(x: &IntervalSet<f64>, domain: (f64, f64)) -> Vec<(f64, f64)> {
+    intervals(x).into_iter()
+        .map(|(start, end)| (start.max(domain.0), end.min(domain.1)))
+        .filter(|(start, end)| start <= end)
+        .collect()
+}
+
+// Apply a monotonically increasing function to every interval of the set, restricted to the domain of the function.
+// The floating point implementation of f is assumed to be accurate within one ulp,
+// so widening the result by one ulp on each side gives a rigorous enclosure.
+fn increasing(x: IntervalSet<f64>, domain: (f64, f64), f: fn (f64) -> f64) -> IntervalSet<f64> {
+    increasing_within(x, domain, f, |_| 0.0)
+}
+
+// Same as increasing, for monotonically decreasing functions
+fn decreasing(x: IntervalSet<f64>, domain: (f64, f64), f: fn (f64) -> f64) -> IntervalSet<f64> {
+    decreasing_within(x, domain, f, |_| 0.0)
+}
+
+// Same as increasing, for functions we can only approximate:
+// error gives a bound of the approximation error for each approximated value
+fn increasing_within(x: IntervalSet<f64>, domain: (f64, f64), f: fn (f64) -> f64, error: fn (f64) -> f64) -> IntervalSet<f64> {
+    from_intervals(clip(&x, domain).into_iter()
+        .map(|(start, end)| {
+            let (start, end) = (f(start), f(end));
+            (lower(start, error(start)), upper(end, error(end)))
+        })
+        .collect())
+}
+
+fn decreasing_within(x: IntervalSet<f64>, domain: (f64, f64), f: fn (f64) -> f64, error: fn (f64) -> f64) -> IntervalSet<f64> {
+    from_intervals(clip(&x, domain).into_iter()
+        .map(|(start, end)| {
+            let (start, end) = (f(start), f(end));
+            (lower(end, error(end)), upper(start, error(start)))
+        })
+        .collect())
+}
+
+// Enclose an oscillating function on every interval of the set restricted to the domain,
+// from its approximate value in the middle of the interval.
+// slope gives a bound of the absolute value of the derivative on an interval,
+// error a bound of the approximation error at a point, and the result is clipped to the range of the function.
+fn lipschitz<F, S, E>(x: &IntervalSet<f64>, domain: (f64, f64), f: F, slope: S, error: E, range: (f64, f64)) -> IntervalSet<f64>
+    where F: Fn(f64) -> f64, S: Fn(f64, f64) -> f64, E: Fn(f64) -> f64 {
+    from_intervals(clip(x, domain).into_iter()
+        .map(|(start, end)| {
+            if !(end - start).is_finite() {
+                return range;
+            }
+            let middle = start / 2.0 + end / 2.0;
+            let radius = next_up((middle - start).max(end - middle));
+            let value = f(middle);
+            let deviation = next_up(slope(start, end) * radius + error(middle));
+            // max and min also take care of NaN values, giving the whole range
+            (lower(value, deviation).max(range.0), upper(value, deviation).min(range.1))
+        })
+        .collect())
+}
+
+pub fn pow(x: IntervalSet<f64>, exponent: IntervalSet<f64>) -> IntervalSet<f64> {
+    x.pow(exponent)
+}
+
+pub fn exp(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    x.exp()
+}
+
+pub fn ln(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    x.log()
+}
+
+pub fn log10(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    x.log() / IntervalSet::singleton(10.0).log()
+}
+
+pub fn log2(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    x.log() / IntervalSet::singleton(2.0).log()
+}
+
+pub fn sqrt(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    x.sqrt()
+}
+
+pub fn sin(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    x.sin()
+}
+
+pub fn cos(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    x.cos()
+}
+
+pub fn tan(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    x.tan()
+}
+
+pub fn asin(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    increasing(x, (-1.0, 1.0), f64::asin)
+}
+
+pub fn acos(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    decreasing(x, (-1.0, 1.0), f64::acos)
+}
+
+pub fn atan(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    increasing(x, (std::f64::NEG_INFINITY, std::f64::INFINITY), f64::atan)
+}
+
+pub fn abs(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    x.abs()
+}
+
+pub fn signum(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    x.signum()
+}
+
+pub fn sinh(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    increasing(x, (std::f64::NEG_INFINITY, std::f64::INFINITY), f64::sinh)
+}
+
+// cosh is decreasing up to 0 and increasing afterwards
+pub fn cosh(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    from_intervals(intervals(&x).into_iter()
+        .map(|(start, end)| {
+            if start >= 0.0 {
+                (next_down(start.cosh()), next_up(end.cosh()))
+            } else if end <= 0.0 {
+                (next_down(end.cosh()), next_up(start.cosh()))
+            } else {
+                (1.0, next_up(start.cosh().max(end.cosh())))
+            }
+        })
+        .collect())
+}
+
+pub fn tanh(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    increasing(x, (std::f64::NEG_INFINITY, std::f64::INFINITY), f64::tanh)
+}
+
+// Beyond this many steps in a single interval we just take the hull of the steps
+const MAX_STEPS: f64 = 64.0;
+
+// 2^53, from here on every floating point number is an integer and adding 1 may not change it
+const EXACT_INTEGERS: f64 = 9007199254740992.0;
+
+// Apply a monotonically increasing step function, which is exact on floating point numbers.
+// The result of each interval is the set of the integers it is mapped to.
+fn steps(x: IntervalSet<f64>, f: fn (f64) -> f64) -> IntervalSet<f64> {
+    let mut result = Vec::new();
+    for (start, end) in intervals(&x) {
+        let (start, end) = (f(start), f(end));
+        if end - start > MAX_STEPS || !(end - start).is_finite()
+            || start.abs() >= EXACT_INTEGERS || end.abs() >= EXACT_INTEGERS {
+            result.push((start, end));
+        } else {
+            let mut step = start;
+            while step <= end {
+                result.push((step, step));
+                step += 1.0;
+            }
+        }
+    }
+    from_intervals(result)
+}
+
+pub fn floor(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    steps(x, f64::floor)
+}
+
+pub fn ceil(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    steps(x, f64::ceil)
+}
+
+pub fn round(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    steps(x, f64::round)
+}
+
+// Combine every pair of intervals with a function that is increasing in both arguments
+fn increasing2(x: IntervalSet<f64>, y: IntervalSet<f64>, f: fn (f64, f64) -> f64) -> IntervalSet<f64> {
+    let y = intervals(&y);
+    from_intervals(intervals(&x).into_iter()
+        .flat_map(|(x_start, x_end)| {
+            y.iter().map(move |(y_start, y_end)| (f(x_start, *y_start), f(x_end, *y_end)))
+        })
+        .collect())
+}
+
+pub fn min(x: IntervalSet<f64>, y: IntervalSet<f64>) -> IntervalSet<f64> {
+    increasing2(x, y, f64::min)
+}
+
+pub fn max(x: IntervalSet<f64>, y: IntervalSet<f64>) -> IntervalSet<f64> {
+    increasing2(x, y, f64::max)
+}
+
+// Gamma is computed directly from 1/2 on, on the two monotonic pieces around its minimum.
+// Below 1/2 we use the reflection formula in interval arithmetic,
+// so that near the poles the division by sin(pi x) gives unbounded enclosures.
+pub fn gamma(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    reflected(x, gamma_positive, |x| {
+        let pi: IntervalSet<f64> = plasm_number::PlasmNumber::pi();
+        pi.clone() / (sin(pi * x.clone()) * gamma_positive(IntervalSet::singleton(1.0) - x))
+    })
+}
+
+fn gamma_positive(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    let error = |value: f64| value.abs() * special::GAMMA_RELATIVE_ERROR;
+    union(decreasing_within(x.clone(), (0.0, special::GAMMA_MINIMUM), special::gamma, error),
+          increasing_within(x, (special::GAMMA_MINIMUM, std::f64::INFINITY), special::gamma, error))
+}
+
+pub fn lgamma(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    reflected(x, lgamma_positive, |x| {
+        let pi: IntervalSet<f64> = plasm_number::PlasmNumber::pi();
+        ln(pi.clone()) - ln(abs(sin(pi * x.clone()))) - lgamma_positive(IntervalSet::singleton(1.0) - x)
+    })
+}
+
+fn lgamma_positive(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    let error = |value: f64| (1.0 + value.abs()) * special::LGAMMA_ERROR;
+    union(decreasing_within(x.clone(), (0.0, special::GAMMA_MINIMUM), special::lgamma, error),
+          increasing_within(x, (special::GAMMA_MINIMUM, std::f64::INFINITY), special::lgamma, error))
+}
+
+pub fn digamma(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    reflected(x, digamma_positive, |x| {
+        let pi: IntervalSet<f64> = plasm_number::PlasmNumber::pi();
+        digamma_positive(IntervalSet::singleton(1.0) - x.clone()) - pi.clone() * cos(pi.clone() * x.clone()) / sin(pi * x)
+    })
+}
+
+fn digamma_positive(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    let error = |value: f64| (1.0 + value.abs()) * special::DIGAMMA_ERROR;
+    increasing_within(x, (0.0, std::f64::INFINITY), special::digamma, error)
+}
+
+// Apply direct to the part of the set from 1/2 on, and reflection to the part below
+fn reflected(x: IntervalSet<f64>, direct: fn (IntervalSet<f64>) -> IntervalSet<f64>,
+             reflection: fn (IntervalSet<f64>) -> IntervalSet<f64>) -> IntervalSet<f64> {
+    let above = clip(&x, (0.5, std::f64::INFINITY));
+    let below = clip(&x, (std::f64::NEG_INFINITY, 0.5));
+
+    let mut result = Vec::new();
+    if !above.is_empty() {
+        result.extend(intervals(&direct(from_intervals(above))));
+    }
+    if !below.is_empty() {
+        result.extend(intervals(&reflection(from_intervals(below))));
+    }
+    from_intervals(result)
+}
+
+pub fn erf(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    let error = |value: f64| value.abs() * special::ERF_RELATIVE_ERROR;
+    increasing_within(x, (std::f64::NEG_INFINITY, std::f64::INFINITY), special::erf, error)
+}
+
+pub fn erfc(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    let error = |value: f64| value.abs() * special::ERFC_RELATIVE_ERROR;
+    decreasing_within(x, (std::f64::NEG_INFINITY, std::f64::INFINITY), special::erfc, error)
+}
+
+// The integers in the set, None if there are too many of them
+fn integers(x: &IntervalSet<f64>) -> Option<Vec<f64>> {
+    let mut result = Vec::new();
+    for (start, end) in intervals(x) {
+        let (first, last) = (start.ceil(), end.floor());
+        if first > last {
+            continue;
+        }
+        if !(last - first < MAX_STEPS) || first.abs() >= EXACT_INTEGERS || last.abs() >= EXACT_INTEGERS {
+            return None;
+        }
+        let mut integer = first;
+        while integer <= last {
+            result.push(integer);
+            integer += 1.0;
+        }
+    }
+    Some(result)
+}
+
+// Only integer orders are supported, the derivative of J_n is (J_(n-1) - J_(n+1)) / 2 so it is bounded by 1
+pub fn besselj(n: IntervalSet<f64>, x: IntervalSet<f64>) -> IntervalSet<f64> {
+    let orders = match integers(&n) {
+        Some(orders) => orders,
+        None => return IntervalSet::new(-1.0, 1.0),
+    };
+
+    let whole_line = (std::f64::NEG_INFINITY, std::f64::INFINITY);
+    from_intervals(orders.into_iter()
+        .flat_map(|order| {
+            intervals(&lipschitz(&x, whole_line, |x| special::besselj(order, x), |_, _| 1.0,
+                                 special::besselj_error, (-1.0, 1.0)))
+        })
+        .collect())
+}
+
+// Ai is decreasing from the first zero of its derivative on,
+// before that it oscillates with an amplitude bounded by 0.6
+pub fn airy(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    let decreasing_part = decreasing_within(x.clone(), (special::AIRY_DERIVATIVE_ZERO, std::f64::INFINITY),
+                                            |x| special::airy(x).0, |_| special::AIRY_ERROR);
+    let oscillating_part = lipschitz(&x, (std::f64::NEG_INFINITY, special::AIRY_DERIVATIVE_ZERO),
+                                     |x| special::airy(x).0, |start, _| special::airy_slope_bound(start),
+                                     special::airy_error, (-0.6, 0.6));
+    union(decreasing_part, oscillating_part)
+}
+
+// Derivative of Ai. Since Ai'' = x Ai, it is increasing for positive x
+pub fn airy_derivative(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    let increasing_part = increasing_within(x.clone(), (0.0, std::f64::INFINITY),
+                                            |x| special::airy(x).1, |_| special::AIRY_ERROR);
+    let oscillating_part = lipschitz(&x, (std::f64::NEG_INFINITY, 0.0),
+                                     |x| special::airy(x).1, |start, _| 0.6 * start.abs(),
+                                     special::airy_error, (std::f64::NEG_INFINITY, std::f64::INFINITY));
+    union(increasing_part, oscillating_part)
+}
+
+// Zeta is decreasing on both sides of the pole in 1, down to its local maximum on the negative numbers.
+// Further left we use the functional equation in interval arithmetic:
+// zeta(s) = 2^s pi^(s-1) sin(pi s / 2) gamma(1-s) zeta(1-s)
+pub fn zeta(s: IntervalSet<f64>) -> IntervalSet<f64> {
+    let error = |value: f64| value.abs() * special::ZETA_RELATIVE_ERROR;
+    let bounds = |s: f64| {
+        let value = special::zeta(s);
+        (lower(value, error(value)), upper(value, error(value)))
+    };
+
+    let mut result = Vec::new();
+    for (start, end) in clip(&s, (1.0, std::f64::INFINITY)) {
+        let upper_bound = if start == 1.0 { std::f64::INFINITY } else { bounds(start).1 };
+        if end > 1.0 {
+            result.push((bounds(end).0, upper_bound));
+        }
+    }
+    for (start, end) in clip(&s, (special::ZETA_MAXIMUM, 1.0)) {
+        let lower_bound = if end == 1.0 { std::f64::NEG_INFINITY } else { bounds(end).0 };
+        if start < 1.0 {
+            result.push((lower_bound, bounds(start).1));
+        }
+    }
+
+    let far_left = clip(&s, (std::f64::NEG_INFINITY, special::ZETA_MAXIMUM));
+    if !far_left.is_empty() {
+        let s = from_intervals(far_left);
+        let pi: IntervalSet<f64> = plasm_number::PlasmNumber::pi();
+        let reflected = IntervalSet::singleton(1.0) - s.clone();
+        let power_of_two = exp(s.clone() * ln(IntervalSet::singleton(2.0)));
+        let power_of_pi = exp((s.clone() - IntervalSet::singleton(1.0)) * ln(pi.clone()));
+        let sine = sin(pi * s / IntervalSet::singleton(2.0));
+        let value = power_of_two * power_of_pi * sine * gamma_positive(reflected.clone()) * zeta(reflected);
+        result.extend(intervals(&value));
+    }
+
+    from_intervals(result)
+}
+
+// Principal branch of the Lambert W function, increasing from the branch point -1/e on
+pub fn lambert_w(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    let error = |value: f64| {
+        let branch_error = if value + 1.0 < 1e-3 { special::LAMBERT_W_BRANCH_ERROR } else { 0.0 };
+        value.abs() * special::LAMBERT_W_RELATIVE_ERROR + branch_error
+    };
+    let branch_point = -(-1.0f64).exp();
+    increasing_within(x, (branch_point, std::f64::INFINITY), special::lambert_w, error)
+}
+
+// Truth values are represented as numbers: 0 is false and everything else is true.
+// Over an interval a condition may be undecided, then its value is the set {0, 1}
+fn boolean(may_be_false: bool, may_be_true: bool) -> IntervalSet<f64> {
+    let mut values = Vec::new();
+    if may_be_false {
+        values.push((0.0, 0.0));
+    }
+    if may_be_true {
+        values.push((1.0, 1.0));
+    }
+    from_intervals(values)
+}
+
+fn may_be_false(x: &IntervalSet<f64>) -> bool {
+    x.has_zero()
+}
+
+fn may_be_true(x: &IntervalSet<f64>) -> bool {
+    hull(x).map_or(false, |(start, end)| start != 0.0 || end != 0.0)
+}
+
+// Compare the hulls of the sets, with compare giving (may be false, may be true) for the two hulls
+fn compare(x: IntervalSet<f64>, y: IntervalSet<f64>, compare: fn ((f64, f64), (f64, f64)) -> (bool, bool)) -> IntervalSet<f64> {
+    match (hull(&x), hull(&y)) {
+        (Some(x), Some(y)) => {
+            let (may_be_false, may_be_true) = compare(x, y);
+            boolean(may_be_false, may_be_true)
+        },
+        _ => empty(),
+    }
+}
+
+pub fn less(x: IntervalSet<f64>, y: IntervalSet<f64>) -> IntervalSet<f64> {
+    compare(x, y, |x, y| (x.1 >= y.0, x.0 < y.1))
+}
+
+pub fn less_equal(x: IntervalSet<f64>, y: IntervalSet<f64>) -> IntervalSet<f64> {
+    compare(x, y, |x, y| (x.1 > y.0, x.0 <= y.1))
+}
+
+pub fn equal(x: IntervalSet<f64>, y: IntervalSet<f64>) -> IntervalSet<f64> {
+    compare(x, y, |x, y| (x != y || x.0 != x.1, x.0 <= y.1 && y.0 <= x.1))
+}
+
+pub fn logical_not(x: IntervalSet<f64>) -> IntervalSet<f64> {
+    boolean(may_be_true(&x), may_be_false(&x))
+}
+
+pub fn logical_and(x: IntervalSet<f64>, y: IntervalSet<f64>) -> IntervalSet<f64> {
+    boolean(may_be_false(&x) || may_be_false(&y), may_be_true(&x) && may_be_true(&y))
+}
+
+pub fn logical_or(x: IntervalSet<f64>, y: IntervalSet<f64>) -> IntervalSet<f64> {
+    boolean(may_be_false(&x) && may_be_false(&y), may_be_true(&x) || may_be_true(&y))
+}
+
+// When the condition is undecided both branches are possible, so we take the union
+pub fn select(condition: IntervalSet<f64>, then: IntervalSet<f64>, otherwise: IntervalSet<f64>) -> IntervalSet<f64> {
+    match (may_be_true(&condition), may_be_false(&condition)) {
+        (true, false) => then,
+        (false, true) => otherwise,
+        (true, true) => union(then, otherwise),
+        (false, false) => empty(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(start: f64, end: f64) -> IntervalSet<f64> {
+        IntervalSet::new(start, end)
+    }
+
+    fn assert_encloses(function: &str, enclosure: IntervalSet<f64>, expected: f64) {
+        let intervals = intervals(&enclosure);
+        assert!(intervals.iter().any(|(start, end)| *start <= expected && expected <= *end),
+                "{} = {:?} doesn't contain {}", function, intervals, expected);
+    }
+
+    #[test]
+    fn hyperbolic_functions_are_enclosed() {
+        for &x in [-3.0, -0.5, 0.0, 0.25, 2.0].iter() {
+            assert_encloses("sinh", sinh(IntervalSet::singleton(x)), x.sinh());
+            assert_encloses("cosh", cosh(IntervalSet::singleton(x)), x.cosh());
+            assert_encloses("tanh", tanh(IntervalSet::singleton(x)), x.tanh());
+        }
+
+        // cosh has its minimum inside the interval
+        let (start, end) = hull(&cosh(interval(-1.0, 2.0))).unwrap();
+        assert_eq!(start, 1.0);
+        assert!(end >= 2.0f64.cosh() && end < 3.8);
+    }
+
+    #[test]
+    fn steps_are_the_integers_reached() {
+        assert_eq!(intervals(&floor(interval(1.5, 3.2))), vec![(1.0, 1.0), (2.0, 2.0), (3.0, 3.0)]);
+        assert_eq!(intervals(&ceil(interval(1.5, 3.2))), vec![(2.0, 2.0), (3.0, 3.0), (4.0, 4.0)]);
+        assert_eq!(intervals(&round(interval(-0.5, 0.4))), vec![(-1.0, -1.0), (0.0, 0.0)]);
+
+        // too many steps give their hull
+        assert_eq!(intervals(&floor(interval(0.5, 1000.5))), vec![(0.0, 1000.0)]);
+    }
+
+    #[test]
+    fn steps_of_huge_numbers_terminate() {
+        assert_eq!(intervals(&floor(IntervalSet::singleton(1e17))), vec![(1e17, 1e17)]);
+        assert_eq!(intervals(&floor(interval(1e17, 1e17 + 32.0))), vec![(1e17, 1e17 + 32.0)]);
+        assert_eq!(intervals(&round(interval(-EXACT_INTEGERS - 2.0, -EXACT_INTEGERS + 10.0))),
+                   vec![(-EXACT_INTEGERS - 2.0, -EXACT_INTEGERS + 10.0)]);
+        // the interval ends where adding 1 doesn't change the step any more
+        assert_eq!(intervals(&floor(interval(EXACT_INTEGERS - 10.0, EXACT_INTEGERS + 10.0))),
+                   vec![(EXACT_INTEGERS - 10.0, EXACT_INTEGERS + 10.0)]);
+    }
+
+    #[test]
+    fn besselj_of_huge_orders_terminates() {
+        let x = interval(0.0, 1.0);
+        assert_eq!(intervals(&besselj(IntervalSet::singleton(1e17), x.clone())), vec![(-1.0, 1.0)]);
+        assert_eq!(intervals(&besselj(interval(-1e17 - 16.0, -1e17), x)), vec![(-1.0, 1.0)]);
+    }
+
+    #[test]
+    fn min_and_max_combine_the_bounds() {
+        assert_eq!(intervals(&min(interval(0.0, 2.0), interval(1.0, 3.0))), vec![(0.0, 2.0)]);
+        assert_eq!(intervals(&max(interval(0.0, 2.0), interval(1.0, 3.0))), vec![(1.0, 3.0)]);
+        assert_eq!(intervals(&max(interval(-5.0, -4.0), IntervalSet::singleton(0.0))), vec![(0.0, 0.0)]);
+    }
+
+    #[test]
+    fn log2_is_enclosed() {
+        assert_encloses("log2", log2(IntervalSet::singleton(8.0)), 3.0);
+        assert_encloses("log2", log2(IntervalSet::singleton(0.1)), 0.1f64.log2());
+        let (start, end) = hull(&log2(interval(1.0, 4.0))).unwrap();
+        assert!(start <= 0.0 && end >= 2.0 && end < 2.001);
+    }
+
+    #[test]
+    fn comparisons_may_be_undecided() {
+        let (yes, no, undecided) = (vec![(1.0, 1.0)], vec![(0.0, 0.0)], vec![(0.0, 0.0), (1.0, 1.0)]);
+        assert_eq!(intervals(&less(interval(0.0, 1.0), interval(2.0, 3.0))), yes);
+        assert_eq!(intervals(&less(interval(2.0, 3.0), interval(0.0, 1.0))), no);
+        assert_eq!(intervals(&less(interval(0.0, 2.0), interval(1.0, 3.0))), undecided);
+        // the intervals touch, so x may equal y
+        assert_eq!(intervals(&less(interval(0.0, 1.0), interval(1.0, 2.0))), undecided);
+        assert_eq!(intervals(&less_equal(interval(0.0, 1.0), interval(1.0, 2.0))), yes);
+
+        assert_eq!(intervals(&equal(IntervalSet::singleton(2.0), IntervalSet::singleton(2.0))), yes);
+        assert_eq!(intervals(&equal(interval(0.0, 1.0), interval(2.0, 3.0))), no);
+        assert_eq!(intervals(&equal(interval(0.0, 2.0), interval(1.0, 3.0))), undecided);
+        assert_eq!(intervals(&equal(empty(), interval(1.0, 3.0))), vec![]);
+    }
+
+    #[test]
+    fn undecided_conditions_select_both_branches() {
+        let (then, otherwise) = (IntervalSet::singleton(-1.0), interval(1.0, 2.0));
+        let selected = |condition| intervals(&select(condition, then.clone(), otherwise.clone()));
+        assert_eq!(selected(IntervalSet::singleton(1.0)), vec![(-1.0, -1.0)]);
+        // every number but 0 is true
+        assert_eq!(selected(interval(0.5, 3.0)), vec![(-1.0, -1.0)]);
+        assert_eq!(selected(IntervalSet::singleton(0.0)), vec![(1.0, 2.0)]);
+        assert_eq!(selected(interval(0.0, 1.0)), vec![(-1.0, -1.0), (1.0, 2.0)]);
+        assert_eq!(selected(empty()), vec![]);
+    }
+
+    #[test]
+    fn piecewise_functions_are_enclosed() {
+        use crate::operator_tables::parse_interval;
+
+        let function = parse_interval("if(x < 0, -1, x + 1)");
+        assert_eq!(intervals(&function.eval_2d(interval(-2.0, -1.0))), vec![(-1.0, -1.0)]);
+        assert_eq!(intervals(&function.eval_2d(interval(1.0, 2.0))), vec![(2.0, 3.0)]);
+        // the condition is undecided across 0, so both pieces are taken
+        assert_eq!(intervals(&function.eval_2d(interval(-1.0, 2.0))), vec![(-1.0, -1.0), (0.0, 3.0)]);
+
+        // without the otherwise piece the function is undefined, the empty set, where the condition doesn't hold
+        let function = parse_interval("{ 2*x : x > 0 }");
+        assert_eq!(intervals(&function.eval_2d(interval(-2.0, -1.0))), vec![]);
+        assert_eq!(intervals(&function.eval_2d(interval(1.0, 2.0))), vec![(2.0, 4.0)]);
+        assert_eq!(intervals(&<IntervalSet<f64> as plasm_number::PlasmNumber>::undefined()), vec![]);
+    }
+}
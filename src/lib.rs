@@ -6,6 +6,7 @@ use wasm_bindgen::prelude::*;
 #[wasm_bindgen(module = "/main.js")]
 extern "C" {
     pub fn get_input_math_function() -> String;
+    pub fn set_info_text(text: &str);
 }
 
 #[wasm_bindgen(start)]
@@ -17,6 +18,6 @@ pub fn start() -> Result<(), JsValue>
     info!("Logging works!");
 
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
-    start_main(get_input_math_function);
+    start_main(get_input_math_function, set_info_text);
     Ok(())
 }
@@ -1,15 +1,39 @@
-mod plotter3d;
+// The modules used by the benchmarks are public
+pub mod plotter3d;
 mod plotter2d;
+mod plotter_complex;
+mod plotter_heatmap;
+mod plane;
 mod plotter;
 mod semantics;
 mod operator_descr;
-mod parser;
-mod expression;
-mod plot_generator2d;
-mod operator_tables;
+pub mod parser;
+mod number_literal;
+pub mod expression;
+pub mod plot_generator2d;
+pub mod operator_tables;
+mod plasm_number;
+mod interval;
+mod special;
+mod dual;
+mod analysis;
+mod calculator;
+mod definitions;
+mod complex;
+mod field;
+mod ode;
+mod colormap;
+mod heatmap;
+mod text;
+mod stroke;
+mod tile_cache;
+mod plot_worker;
+mod parallel;
+mod glsl;
 use three_d::*;
 use plotter::Plotter;
 use log::info;
+use honestintervals::IntervalSet;
 
 const DEFAULT_EXPR: &str = "sin(x)";
 const DEFAULT_MODE: DrawingMode = DrawingMode::Mode3d;
@@ -17,15 +41,93 @@ const DEFAULT_MODE: DrawingMode = DrawingMode::Mode3d;
 enum DrawingMode {
     Mode2d,
     Mode3d,
+    Calculator,
+    Complex,
+    Heatmap,
 }
 
 fn main() {
+    // Expressions given as arguments are evaluated as in a calculator
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        let mut calculator = calculator::Calculator::new();
+        for arg in args.iter() {
+            match calculator.evaluate(arg) {
+                Ok(result) => println!("{}", result),
+                Err(err) => eprintln!("{}: {}", arg, err),
+            }
+        }
+        return;
+    }
+
     let get_input = || {String::from(DEFAULT_EXPR)};
-    start_main(get_input);
+    let set_info = |text: &str| {println!("{}", text)};
+    start_main(get_input, set_info);
+}
+
+// Split the input at the ';' separating expressions, leaving alone those separating the pieces of piecewise functions
+fn split_expressions(input: &str) -> Vec<&str> {
+    let mut expressions = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ';' if depth == 0 => {
+                expressions.push(&input[start..i]);
+                start = i + 1;
+            },
+            _ => (),
+        }
+    }
+    expressions.push(&input[start..]);
+    expressions
+}
+
+// The function in GLSL, when it can be evaluated by shaders
+fn parse_shader<G: semantics::Semantics<Number = glsl::Glsl>>(input: &str, glsl_operator_table: &G) -> Option<String> {
+    parser::parse(input, glsl_operator_table).ok().and_then(|expression| glsl::function_source(&expression))
 }
 
-fn start_main<F: 'static>(get_input: F) where
-    F: Fn() -> String {
+// The function inside the options written around a 2d curve, with its line style, whether its asymptotes are drawn
+// and whether it is drawn by a shader. dashed(asymptotes(tan(x))) draws the asymptotes of the dashed curve
+fn curve_options(input: &str) -> (stroke::LineStyle, bool, bool, &str) {
+    fn option<'a>(input: &'a str, name: &str) -> (bool, &'a str) {
+        match parser::call_argument(input.trim(), name) {
+            Some(inner) => (true, inner),
+            None => (false, input),
+        }
+    }
+
+    let (style, input) = stroke::LineStyle::parse(input);
+    let (asymptotes, input) = option(input, "asymptotes");
+    let (gpu, input) = option(input, "gpu");
+    (style, asymptotes, gpu, input)
+}
+
+// Parse a 2d function along with its derivative, which is used to analyse the function
+fn parse_curve<I, D, G>(input: &str, interval_arithmetic_operator_table: &I, differentiation_operator_table: &D, glsl_operator_table: &G) -> Result<plotter2d::Curve, &'static str> where
+    I: semantics::Semantics<Number = IntervalSet<f64>>,
+    D: semantics::Semantics<Number = dual::Dual>,
+    G: semantics::Semantics<Number = glsl::Glsl> {
+    let (style, asymptotes, gpu, input) = curve_options(input);
+    let expression = parser::parse(input, interval_arithmetic_operator_table)?;
+    let derivative = parser::parse(input, differentiation_operator_table).ok();
+    let shader = if gpu { parse_shader(input, glsl_operator_table) } else { None };
+
+    Ok(plotter2d::Curve {
+        expression: std::sync::Arc::new(expression),
+        derivative,
+        style,
+        asymptotes,
+        shader,
+    })
+}
+
+fn start_main<F: 'static, G: 'static>(get_input: F, set_info: G) where
+    F: Fn() -> String,
+    G: Fn(&str) {
 
     let mut window = Window::new_default("Plasm").unwrap();
     let (screen_width, screen_height) = window.framebuffer_size();
@@ -35,53 +137,180 @@ fn start_main<F: 'static>(get_input: F) where
 
     let operator_table = operator_tables::default_operator_table();
     let interval_arithmetic_operator_table = operator_tables::interval_arithmetic_operator_table();
+    let differentiation_operator_table = operator_tables::differentiation_operator_table();
+    let complex_operator_table = operator_tables::complex_operator_table();
+    let glsl_operator_table = operator_tables::glsl_operator_table();
 
-    let expression = parser::parse(DEFAULT_EXPR, &interval_arithmetic_operator_table).unwrap();
-    let mut plotter2d = plotter2d::Plotter2d::new(&gl, expression, (screen_width, screen_height));
+    let curve = parse_curve(DEFAULT_EXPR, &interval_arithmetic_operator_table, &differentiation_operator_table, &glsl_operator_table).unwrap();
+    let mut plotter2d = plotter2d::Plotter2d::new(&gl, vec![curve], (screen_width, screen_height));
     let expression = parser::parse(DEFAULT_EXPR, &operator_table).unwrap();
-    let mut plotter3d = plotter3d::Plotter3d::new(&gl, expression, (screen_width, screen_height));
-    
+    let shader = parse_shader(DEFAULT_EXPR, &glsl_operator_table);
+    let mut plotter3d = plotter3d::Plotter3d::new(&gl, expression, shader, (screen_width, screen_height));
+    let mut calculator = calculator::Calculator::new();
+    let expression = parser::parse("z", &complex_operator_table).unwrap();
+    let mut plotter_complex = plotter_complex::PlotterComplex::new(&gl, expression, (screen_width, screen_height));
+    let heatmap = heatmap::parse("heatmap(x*y)", &operator_table, &interval_arithmetic_operator_table, &glsl_operator_table).unwrap().unwrap();
+    let mut plotter_heatmap = plotter_heatmap::PlotterHeatmap::new(&gl, heatmap, (screen_width, screen_height));
 
     // main loop
     let mut dragging = false;
+    // The trajectory whose initial point is being dragged, and whether the mouse moved since it was pressed
+    let mut dragged_trajectory: Option<usize> = None;
+    let mut moved = false;
+    let mut cursor_position = (screen_width as f64 / 2.0, screen_height as f64 / 2.0);
     let mut old_input = String::from(DEFAULT_EXPR);
     let mut drawing_mode = DEFAULT_MODE;
+    // whether the implicit curves of the view were too complex to be drawn in full detail
+    let mut budget_exceeded = false;
     window.render_loop(move |frame_input|
     {
         // read input
         let input = get_input();
         if input != old_input {
 
-            // determine if 2d function or 3d function
-            let expression = parser::parse(&input, &operator_table);
-            match expression {
-                Ok(expr) => {
-                    match expr.expr_type() {
-                        expression::ExprType::Expr2d | expression::ExprType::ExprImplicit => {
-                            // draw as 2d function parse again using interval arithmetic
-                            let expression = parser::parse(&input, &interval_arithmetic_operator_table);
-                            match expression {
-                                Ok(expr) => {
-                                    plotter2d.set_expression(expr);
-                                    info!("Draw implicit function");
-                                }
-                                Err(_) => {
-                                    info!("Could not parse input function");
-                                }
+            // Multiple 2d functions can be plotted together, separated by ';'
+            let inputs: Vec<&str> = split_expressions(&input).into_iter().map(str::trim).filter(|input| !input.is_empty()).collect();
+
+            // Functions defined by the user, like f(x) = x^2, can be used by the other expressions.
+            // Those using complex numbers, like f(z) = z^2 + i, can only be used by complex functions
+            let split = definitions::Definitions::from_inputs(&inputs, &operator_table);
+            let split = if split.2.is_empty() {
+                split
+            } else {
+                let complex_split = definitions::Definitions::from_inputs(&inputs, &complex_operator_table);
+                if complex_split.2.len() < split.2.len() { complex_split } else { split }
+            };
+            // The definitions which fail are reported, the other inputs are still drawn
+            let (definitions, inputs, errors) = split;
+            if !errors.is_empty() {
+                let messages: Vec<String> = errors.iter().map(|(input, err)| format!("Could not parse the definition {}: {}", input, err)).collect();
+                info!("{}", messages.join("\n"));
+                set_info(&messages.join("\n"));
+            }
+            let environment = definitions::Environment::new(&operator_table, &definitions);
+            let interval_arithmetic_environment = definitions::Environment::new(&interval_arithmetic_operator_table, &definitions);
+            let differentiation_environment = definitions::Environment::new(&differentiation_operator_table, &definitions);
+            let complex_environment = definitions::Environment::new(&complex_operator_table, &definitions);
+            let glsl_environment = definitions::Environment::new(&glsl_operator_table, &definitions);
+
+            // Vector fields (P, Q) and slope fields dy/dx = f(x, y) are drawn along with the 2d functions
+            let mut fields = Vec::new();
+            let mut expression_inputs = Vec::new();
+            for input in inputs {
+                match field::parse(input, &environment) {
+                    Some(field) => fields.push(field),
+                    None => expression_inputs.push(input),
+                }
+            }
+            let inputs = expression_inputs;
+
+            // determine if 2d function or 3d function, from the functions inside the options of the curves
+            let functions: Vec<&str> = inputs.iter().map(|input| curve_options(input).3).collect();
+            let expressions: Result<Vec<_>, _> = functions.iter().map(|function| parser::parse(function, &environment)).collect();
+            match expressions {
+                Ok(ref exprs) if exprs.is_empty() && fields.is_empty() => {
+                    info!("Could not parse input function");
+                }
+                Ok(mut exprs) => {
+                    let is_3d = exprs.iter().any(|expr| match expr.expr_type() {
+                        expression::ExprType::Expr3d => true,
+                        _ => false,
+                    });
+                    let is_constant = fields.is_empty() && exprs.len() == 1 && match exprs[0].expr_type() {
+                        expression::ExprType::Constant => true,
+                        _ => false,
+                    };
+
+                    if is_constant {
+                        // no variables, just show the value
+                        calculator.set_definitions(definitions.clone());
+                        match calculator.evaluate(functions[0]) {
+                            Ok(_) => set_info(&calculator.history().join("\n")),
+                            Err(err) => set_info(err),
+                        }
+                        drawing_mode = DrawingMode::Calculator;
+                    } else if !is_3d {
+                        // draw as 2d functions parse again using interval arithmetic
+                        let curves: Result<Vec<_>, _> = inputs.iter()
+                            .map(|input| parse_curve(input, &interval_arithmetic_environment, &differentiation_environment, &glsl_environment))
+                            .collect();
+                        match curves {
+                            Ok(curves) => {
+                                plotter2d.set_curves(curves);
+                                info!("Draw 2d functions");
                             }
-                            drawing_mode = DrawingMode::Mode2d;
-                            renderer.geometry_pass(screen_width, screen_height, &|| {
-                            }).unwrap();
-                        },
-                        expression::ExprType::Expr3d => {
-                            plotter3d.set_expression(expr);
-                            drawing_mode = DrawingMode::Mode3d;
-                            info!("Draw 3d function");
+                            Err(_) => {
+                                info!("Could not parse input function");
+                            }
+                        }
+
+                        if fields.len() > 1 {
+                            info!("Only one field can be plotted at a time");
                         }
+                        match fields.into_iter().next().transpose() {
+                            Ok(field) => plotter2d.set_field(field),
+                            Err(err) => {
+                                info!("Could not parse field: {}", err);
+                                plotter2d.set_field(None);
+                            }
+                        }
+                        drawing_mode = DrawingMode::Mode2d;
+                        renderer.geometry_pass(screen_width, screen_height, &|| {
+                        }).unwrap();
+                    } else if exprs.len() == 1 {
+                        plotter3d.set_expression(exprs.pop().unwrap(), parse_shader(functions[0], &glsl_environment));
+                        drawing_mode = DrawingMode::Mode3d;
+                        info!("Draw 3d function");
+                    } else {
+                        info!("Only one 3d function can be plotted at a time");
                     }
                 }
                 Err(_) => {
-                    info!("Could not parse input function");
+                    // Functions of two variables with their contour lines, drawn as heatmaps like heatmap(x^2 - y^2)
+                    // or as surfaces like surface(x^2 - y^2), are drawn on their own
+                    let single_input = match inputs.as_slice() {
+                        [input] => Some(*input),
+                        _ => None,
+                    };
+                    let heatmap = single_input.and_then(|input| heatmap::parse(input, &environment, &interval_arithmetic_environment, &glsl_environment));
+                    let surface = single_input.and_then(|input| heatmap::parse_surface(input, &environment));
+                    match (heatmap, surface) {
+                        (Some(Ok(heatmap)), _) => {
+                            plotter_heatmap.set_heatmap(heatmap);
+                            drawing_mode = DrawingMode::Heatmap;
+                            info!("Draw heatmap");
+                        },
+                        (_, Some(Ok(surface))) => {
+                            plotter3d.set_surface(surface);
+                            drawing_mode = DrawingMode::Mode3d;
+                            info!("Draw surface");
+                        },
+                        (Some(Err(err)), _) | (_, Some(Err(err))) => {
+                            info!("Could not parse contour plot: {}", err);
+                        },
+                        (None, None) => {
+                            // Functions using complex numbers, like z^2 + i, are drawn with domain coloring
+                            let complex_expression = match inputs.as_slice() {
+                                [input] => parser::parse(input, &complex_environment).ok(),
+                                _ => None,
+                            };
+                            match complex_expression {
+                                Some(expression) => {
+                                    if let expression::ExprType::Constant = expression.expr_type() {
+                                        set_info(&format!("{} = {}", inputs[0], complex::to_string(expression.eval_constant())));
+                                        drawing_mode = DrawingMode::Calculator;
+                                    } else {
+                                        plotter_complex.set_expression(expression);
+                                        drawing_mode = DrawingMode::Complex;
+                                        info!("Draw complex function");
+                                    }
+                                },
+                                None => {
+                                    info!("Could not parse input function");
+                                }
+                            }
+                        }
+                    }
                 }
             }
 
@@ -91,23 +320,97 @@ fn start_main<F: 'static>(get_input: F) where
         // mouse events handling
         for event in frame_input.events.iter() {
             match event {
-                Event::MouseClick {state, button, ..} => {
+                Event::MouseClick {state, button, position} => {
                     dragging = *button == MouseButton::Left && *state == State::Pressed;
+                    cursor_position = *position;
+
+                    if let DrawingMode::Mode2d = drawing_mode {
+                        // Clicking on a root, extremum or intersection shows its coordinates
+                        if dragging {
+                            if let Some(point) = plotter2d.point_of_interest_at(cursor_position) {
+                                set_info(&point.to_string());
+                            }
+
+                            // Pressing on the initial point of a trajectory drags it instead of the view
+                            dragged_trajectory = plotter2d.trajectory_at(cursor_position);
+                            moved = false;
+                        }
+
+                        // Clicking on the plot of a field starts a trajectory from the clicked point,
+                        // clicking on the initial point of a trajectory removes it
+                        if *button == MouseButton::Left && *state == State::Released {
+                            match dragged_trajectory {
+                                Some(index) if !moved => plotter2d.remove_trajectory(index),
+                                None if !moved => {
+                                    if let Some((x, y)) = plotter2d.add_trajectory(cursor_position) {
+                                        set_info(&format!("trajectory from x = {:.6}, y = {:.6}", x, y));
+                                    }
+                                },
+                                _ => (),
+                            }
+                            dragged_trajectory = None;
+                        }
+
+                        // Right clicking twice selects the interval to integrate the curve over
+                        if *button == MouseButton::Right && *state == State::Pressed {
+                            if let Some(area) = plotter2d.select_integration_bound(cursor_position) {
+                                set_info(&area.to_string());
+                            }
+                        }
+                    }
                 },
                 Event::MouseMotion {delta} => {
+                    // Only relative motion is reported, keep track of the absolute cursor position
+                    cursor_position.0 += delta.0;
+                    cursor_position.1 += delta.1;
+
                     if dragging {
                         let delta_x = -delta.0 as f32;
                         let delta_y = delta.1 as f32;
+                        moved = true;
 
                         match &drawing_mode {
                             DrawingMode::Mode2d => {
-                                plotter2d.translate(delta_x, delta_y);
+                                match dragged_trajectory {
+                                    Some(index) => plotter2d.move_trajectory(index, cursor_position),
+                                    None => plotter2d.translate(delta_x, delta_y),
+                                }
                             },
                             DrawingMode::Mode3d => {
                                 plotter3d.translate(delta_x, delta_y);
-                            }
+                            },
+                            DrawingMode::Complex => {
+                                plotter_complex.translate(delta_x, delta_y);
+                            },
+                            DrawingMode::Heatmap => {
+                                plotter_heatmap.translate(delta_x, delta_y);
+                            },
+                            DrawingMode::Calculator => ()
                         }
                     }
+
+                    // trace mode: show the coordinates of the plot under the cursor
+                    match &drawing_mode {
+                        DrawingMode::Mode2d => {
+                            if let Some((x, y)) = plotter2d.trace(cursor_position) {
+                                set_info(&format!("x = {:.6}, y = {:.6}", x, y));
+                            }
+                        },
+                        DrawingMode::Mode3d => {
+                            if let Some((x, y, z)) = plotter3d.trace(cursor_position) {
+                                set_info(&format!("x = {:.6}, y = {:.6}, f(x, y) = {:.6}", x, y, z));
+                            }
+                        },
+                        DrawingMode::Complex => {
+                            let (z, value) = plotter_complex.trace(cursor_position);
+                            set_info(&format!("z = {}, f(z) = {}", complex::to_string(z), complex::to_string(value)));
+                        },
+                        DrawingMode::Heatmap => {
+                            let (x, y, value) = plotter_heatmap.trace(cursor_position);
+                            set_info(&format!("x = {:.6}, y = {:.6}, f(x, y) = {:.6}", x, y, value));
+                        },
+                        DrawingMode::Calculator => ()
+                    }
                 },
                 Event::MouseWheel {delta} => {
                     match &drawing_mode {
@@ -116,7 +419,14 @@ fn start_main<F: 'static>(get_input: F) where
                         },
                         DrawingMode::Mode3d => {
                             plotter3d.zoom(*delta as f32);
-                        }
+                        },
+                        DrawingMode::Complex => {
+                            plotter_complex.zoom(*delta as f32);
+                        },
+                        DrawingMode::Heatmap => {
+                            plotter_heatmap.zoom(*delta as f32);
+                        },
+                        DrawingMode::Calculator => ()
                     }
                 },
                 _ => ()
@@ -126,6 +436,15 @@ fn start_main<F: 'static>(get_input: F) where
         // draw
         match &drawing_mode {
             DrawingMode::Mode2d => {
+                // the tiles are generated in the background and drawn as they are ready
+                if plotter2d.is_refining() {
+                    plotter2d.refine();
+                }
+                // the message is shown whenever the view gets too complex, after zooming, panning or changing the curves
+                if plotter2d.budget_exceeded() && !budget_exceeded {
+                    set_info("The implicit curves are too complex to be drawn in full detail, zoom in to refine them");
+                }
+                budget_exceeded = plotter2d.budget_exceeded();
                 plotter2d.render(&gl, &mut renderer);
             },
             DrawingMode::Mode3d => {
@@ -134,9 +453,54 @@ fn start_main<F: 'static>(get_input: F) where
 
                 let delta_rotation = frame_input.elapsed_time as f32 / 200.0;
                 plotter3d.rotate(delta_rotation);
+            },
+            DrawingMode::Complex => {
+                plotter_complex.render(&gl, &mut renderer);
+            },
+            DrawingMode::Heatmap => {
+                plotter_heatmap.render(&gl, &mut renderer);
+            },
+            DrawingMode::Calculator => {
+                Screen::write(&gl, 0, 0, screen_width, screen_height, Some(&vec4(0.9, 0.9, 0.9, 1.0)), None, &|| {
+                }).unwrap();
             }
         }
 
 
     }).unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curve_options_are_stripped_before_parsing() {
+        let table = operator_tables::default_operator_table();
+        for input in ["dashed(sin(x))", "dotted(x^2 + y^2 = 1)", "asymptotes(tan(x))", " dashed( asymptotes(1/x) ) ", "gpu(x^2 = y)"].iter() {
+            let (_, _, _, function) = curve_options(input);
+            assert!(parser::parse(function, &table).is_ok(), "{} gives {}", input, function);
+        }
+
+        let (style, asymptotes, gpu, function) = curve_options("dotted(asymptotes(tan(x)))");
+        assert!(matches!(style, stroke::LineStyle::Dotted));
+        assert!(asymptotes && !gpu);
+        assert_eq!(function, "tan(x)");
+
+        let (style, asymptotes, gpu, function) = curve_options("sin(x)");
+        assert!(matches!(style, stroke::LineStyle::Solid));
+        assert!(!asymptotes && !gpu);
+        assert_eq!(function, "sin(x)");
+    }
+
+    #[test]
+    fn only_curves_asking_for_it_are_drawn_by_shaders() {
+        let interval_table = operator_tables::interval_arithmetic_operator_table();
+        let differentiation_table = operator_tables::differentiation_operator_table();
+        let glsl_table = operator_tables::glsl_operator_table();
+        let curve = parse_curve("x^2 + y^2 = 1", &interval_table, &differentiation_table, &glsl_table).unwrap();
+        assert!(curve.shader.is_none());
+        let curve = parse_curve("gpu(x^2 + y^2 = 1)", &interval_table, &differentiation_table, &glsl_table).unwrap();
+        assert!(curve.shader.is_some());
+    }
+}
@@ -0,0 +1,148 @@
+// A number literal as written in the input.
+// Most decimal literals, like 0.1, are not exactly representable as floating point numbers,
+// so along with the correctly rounded value we remember whether rounding took place.
+#[derive(Clone, Copy)]
+pub struct NumberLiteral {
+    pub value: f64,
+    pub exact: bool,
+}
+
+// The largest integer such that all the integers below it are representable as f64
+const MAX_EXACT_INTEGER: u64 = 1 << 53;
+
+impl NumberLiteral {
+    // Build the literal digits * 10^exponent, where digits is a string of decimal digits
+    pub fn from_decimal(digits: &str, exponent: i64) -> NumberLiteral {
+        // Rust parsing of floating point numbers is correctly rounded
+        let value = format!("{}e{}", digits, exponent).parse::<f64>().unwrap();
+        let exact = value.is_finite() && NumberLiteral::is_representable(digits, exponent);
+
+        NumberLiteral {
+            value,
+            exact,
+        }
+    }
+
+    // Smallest interval of floating point numbers containing the exact value of the literal
+    // that we can cheaply compute: the rounded value widened by one ulp on each side
+    pub fn enclosure(&self) -> (f64, f64) {
+        if self.exact {
+            (self.value, self.value)
+        } else {
+            (next_down(self.value), next_up(self.value))
+        }
+    }
+
+    // Check whether digits * 10^exponent is exactly representable as f64.
+    // It may return false for some representable numbers with lots of significant digits,
+    // that is fine since they will only get a slightly wider enclosure.
+    fn is_representable(digits: &str, exponent: i64) -> bool {
+        let digits = digits.trim_start_matches('0');
+        let significant = digits.trim_end_matches('0');
+        if significant.is_empty() {
+            // It is zero
+            return true;
+        }
+        let exponent = exponent + (digits.len() - significant.len()) as i64;
+
+        let mantissa = match significant.parse::<u64>() {
+            Ok(mantissa) => mantissa,
+            Err(_) => return false,
+        };
+
+        // mantissa * 10^exponent = mantissa * 5^exponent * 2^exponent,
+        // the power of 2 is always representable, so it all depends on mantissa * 5^exponent
+        let odd_part = if exponent >= 0 {
+            pow5(exponent).and_then(|pow| mantissa.checked_mul(pow))
+        } else {
+            pow5(-exponent).and_then(|pow| if mantissa % pow == 0 { Some(mantissa / pow) } else { None })
+        };
+
+        odd_part.map_or(false, |odd_part| odd_part <= MAX_EXACT_INTEGER)
+    }
+}
+
+fn pow5(exponent: i64) -> Option<u64> {
+    if exponent > u32::max_value() as i64 {
+        None
+    } else {
+        5u64.checked_pow(exponent as u32)
+    }
+}
+
+// The smallest floating point number greater than x
+pub fn next_up(x: f64) -> f64 {
+    if x.is_nan() || x == std::f64::INFINITY {
+        x
+    } else if x == 0.0 {
+        f64::from_bits(1)
+    } else if x > 0.0 {
+        f64::from_bits(x.to_bits() + 1)
+    } else {
+        f64::from_bits(x.to_bits() - 1)
+    }
+}
+
+// The largest floating point number smaller than x
+pub fn next_down(x: f64) -> f64 {
+    -next_up(-x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    // Compare x with a positive decimal number written as digits, a point and more digits,
+    // using the exact decimal expansion of x
+    fn compare(x: f64, decimal: &str) -> Ordering {
+        let expansion = format!("{:.1100}", x);
+        let (integer, fraction) = expansion.split_once('.').unwrap();
+        let (decimal_integer, decimal_fraction) = decimal.split_once('.').unwrap();
+        let padded = format!("{:0<1100}", decimal_fraction);
+        integer.len().cmp(&decimal_integer.len())
+            .then(integer.cmp(decimal_integer))
+            .then(fraction.cmp(&padded))
+    }
+
+    #[test]
+    fn enclosures_contain_the_decimal_values() {
+        let cases = [("1", -1, "0.1"), ("3", -1, "0.3"), ("1", -3, "0.001"), ("25", -11, "0.00000000025"),
+                     ("314159265358979323846264338327950288", -35, "3.14159265358979323846264338327950288"),
+                     ("123456789012345678901234567890", 0, "123456789012345678901234567890.0")];
+        for (digits, exponent, decimal) in cases.iter() {
+            let literal = NumberLiteral::from_decimal(digits, *exponent);
+            assert!(!literal.exact, "{} is not representable", decimal);
+            let (lower, upper) = literal.enclosure();
+            assert_eq!(compare(lower, decimal), Ordering::Less, "{} is below the enclosure", decimal);
+            assert_eq!(compare(upper, decimal), Ordering::Greater, "{} is above the enclosure", decimal);
+            assert!(lower <= literal.value && literal.value <= upper);
+        }
+    }
+
+    #[test]
+    fn exact_literals_are_singletons() {
+        for (digits, exponent, value) in [("5", -1, 0.5), ("125", -3, 0.125), ("1", 22, 1e22), ("9007199254740992", 0, 9007199254740992.0),
+                                          ("0", 400, 0.0), ("1000", -3, 1.0)] {
+            let literal = NumberLiteral::from_decimal(digits, exponent);
+            assert!(literal.exact, "{}e{} is representable", digits, exponent);
+            assert_eq!(literal.enclosure(), (value, value));
+        }
+        // too big to be representable, or beyond the range of f64
+        for (digits, exponent) in [("1", 23), ("9007199254740993", 0), ("1", 400)] {
+            assert!(!NumberLiteral::from_decimal(digits, exponent).exact, "{}e{} is not representable", digits, exponent);
+        }
+    }
+
+    #[test]
+    fn next_up_and_down_are_adjacent() {
+        assert_eq!(next_up(1.0), 1.0 + f64::EPSILON);
+        assert_eq!(next_down(1.0), 1.0 - f64::EPSILON / 2.0);
+        assert_eq!(next_up(0.0), f64::from_bits(1));
+        assert_eq!(next_down(0.0), -f64::from_bits(1));
+        assert_eq!(next_up(-f64::from_bits(1)), 0.0);
+        assert_eq!(next_up(f64::MAX), f64::INFINITY);
+        assert_eq!(next_up(f64::INFINITY), f64::INFINITY);
+        assert_eq!(next_down(f64::INFINITY), f64::MAX);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::field::Field;
+
+// Integration of the trajectories of fields:
+// the solutions of x' = P(x, y), y' = Q(x, y) for vector fields and the solutions of dy/dx = f(x, y) for slope fields,
+// which are the trajectories of the field (1, f(x, y)).
+// We use the Dormand-Prince method, an adaptive Runge-Kutta method of order 5 with an embedded method of order 4
+// estimating the error of each step.
+
+// Maximum number of steps in each direction
+const MAX_STEPS: usize = 5000;
+
+// Coefficients of the Dormand-Prince method
+const A: [[f64; 6]; 6] = [
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [19372.0 / 6561.0, -25360.0 / 2187.0, 64448.0 / 6561.0, -212.0 / 729.0, 0.0, 0.0],
+    [9017.0 / 3168.0, -355.0 / 33.0, 46732.0 / 5247.0, 49.0 / 176.0, -5103.0 / 18656.0, 0.0],
+    [35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0],
+];
+// Difference between the weights of the solutions of order 5 and order 4
+const ERROR_WEIGHTS: [f64; 7] = [
+    71.0 / 57600.0, 0.0, -71.0 / 16695.0, 71.0 / 1920.0, -17253.0 / 339200.0, 22.0 / 525.0, -1.0 / 40.0,
+];
+
+// The region of the plane where trajectories are computed, with the desired accuracy
+pub struct Bounds {
+    pub x_start: f64,
+    pub x_end: f64,
+    pub y_start: f64,
+    pub y_end: f64,
+    // Maximum error of each step
+    pub tolerance: f64,
+    // Maximum length of each step, so that the trajectory looks smooth
+    pub max_step: f64,
+}
+
+impl Bounds {
+    fn contains(&self, point: (f64, f64)) -> bool {
+        point.0 >= self.x_start && point.0 <= self.x_end && point.1 >= self.y_start && point.1 <= self.y_end
+    }
+}
+
+// The trajectory through the start point, until it leaves the bounds in both directions.
+// The points are ordered along the trajectory, periodic orbits go around once and end in the start point
+pub fn trajectory(field: &Field, start: (f64, f64), bounds: &Bounds) -> Vec<(f64, f64)> {
+    let forward = integrate(field, start, 1.0, bounds);
+    if forward.len() > 1 && forward.last() == Some(&start) {
+        return forward;
+    }
+
+    let mut points = integrate(field, start, -1.0, bounds);
+    points.reverse();
+    points.pop();
+    points.extend(forward);
+    points
+}
+
+// Integrate forward in time if direction is 1, backward if it is -1
+fn integrate(field: &Field, start: (f64, f64), direction: f64, bounds: &Bounds) -> Vec<(f64, f64)> {
+    let derivative = |point: (f64, f64)| {
+        let (dx, dy) = field.direction(point.0, point.1);
+        (direction * dx, direction * dy)
+    };
+
+    let mut points = vec![start];
+    let mut point = start;
+    let mut k1 = derivative(point);
+    let start_direction = k1;
+    // The initial step moves by about max_step
+    let mut step = bounds.max_step / k1.0.hypot(k1.1).max(1.0);
+    let mut left_start = false;
+
+    while points.len() < MAX_STEPS && bounds.contains(point) {
+        let (next, k7, error) = dormand_prince_step(&derivative, point, k1, step);
+        if !(next.0.is_finite() && next.1.is_finite() && error.is_finite()) {
+            break;
+        }
+
+        let length = (next.0 - point.0).hypot(next.1 - point.1);
+        if error <= bounds.tolerance && length <= bounds.max_step {
+            let previous = point;
+            point = next;
+            k1 = k7;
+            points.push(point);
+            if k1.0.hypot(k1.1) < bounds.tolerance {
+                // Close to an equilibrium point, the trajectory would stop here anyway
+                break;
+            }
+
+            let distance = (point.0 - start.0).hypot(point.1 - start.1);
+            if distance > 2.0 * bounds.max_step {
+                left_start = true;
+            } else if left_start && returns_to_start(previous, point, start, start_direction, closing_distance(points.len(), bounds)) {
+                // A periodic orbit, close it
+                points.pop();
+                points.push(start);
+                break;
+            }
+        }
+
+        // The error of a step of size h is O(h^5)
+        let mut factor = if error > 0.0 {
+            (0.9 * (bounds.tolerance / error).powf(0.2)).clamp(0.2, 5.0)
+        } else {
+            5.0
+        };
+        if length > bounds.max_step {
+            factor = factor.min(bounds.max_step / length);
+        }
+        step *= factor;
+        if step < f64::EPSILON * bounds.max_step {
+            // The step can't get any smaller, the trajectory probably goes to infinity
+            break;
+        }
+    }
+
+    points
+}
+
+// The distance from the start point within which a trajectory has come back to it, the error accumulated by the steps
+fn closing_distance(steps: usize, bounds: &Bounds) -> f64 {
+    (steps as f64 * bounds.tolerance).min(bounds.max_step)
+}
+
+// True when the step from previous to point goes through start, up to the given error, in the direction of the field there.
+// Trajectories spiraling around an equilibrium point pass near the start again, but not within the error of the steps
+fn returns_to_start(previous: (f64, f64), point: (f64, f64), start: (f64, f64), start_direction: (f64, f64), error: f64) -> bool {
+    let step = (point.0 - previous.0, point.1 - previous.1);
+    let length = step.0.hypot(step.1);
+    if length == 0.0 || step.0 * start_direction.0 + step.1 * start_direction.1 <= 0.0 {
+        return false;
+    }
+
+    // The closest point of the step to start
+    let t = (((start.0 - previous.0) * step.0 + (start.1 - previous.1) * step.1) / (length * length)).clamp(0.0, 1.0);
+    let closest = (previous.0 + t * step.0, previous.1 + t * step.1);
+    (closest.0 - start.0).hypot(closest.1 - start.1) <= error
+}
+
+// A single step of size h from point, with k1 the derivative in point.
+// Returns the new point, the derivative in the new point and the estimated error
+fn dormand_prince_step<F>(derivative: &F, point: (f64, f64), k1: (f64, f64), h: f64) -> ((f64, f64), (f64, f64), f64) where
+    F: Fn((f64, f64)) -> (f64, f64) {
+    let mut k = [k1; 7];
+    for stage in 0..6 {
+        let mut next = point;
+        for (j, a) in A[stage].iter().enumerate().take(stage + 1) {
+            next.0 += h * a * k[j].0;
+            next.1 += h * a * k[j].1;
+        }
+        k[stage + 1] = derivative(next);
+    }
+
+    // The last stage is evaluated in the solution of order 5
+    let mut next = point;
+    for (j, a) in A[5].iter().enumerate() {
+        next.0 += h * a * k[j].0;
+        next.1 += h * a * k[j].1;
+    }
+
+    let mut error = (0.0, 0.0);
+    for (j, weight) in ERROR_WEIGHTS.iter().enumerate() {
+        error.0 += h * weight * k[j].0;
+        error.1 += h * weight * k[j].1;
+    }
+
+    (next, k[6], error.0.hypot(error.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field;
+    use crate::operator_tables::default_operator_table;
+
+    fn parse_field(input: &str) -> Field {
+        field::parse(input, &default_operator_table()).unwrap().unwrap()
+    }
+
+    fn bounds(size: f64) -> Bounds {
+        Bounds { x_start: -size, x_end: size, y_start: -size, y_end: size, tolerance: 1e-7, max_step: 0.01 }
+    }
+
+    #[test]
+    fn slope_fields_follow_the_solutions() {
+        let points = trajectory(&parse_field("dy/dx = y"), (0.0, 1.0), &bounds(5.0));
+        assert!(points.iter().all(|point| (point.1 - point.0.exp()).abs() <= 1e-5 * point.0.exp()));
+        // it leaves the bounds at the top and goes on to the left until the end of the bounds
+        let (first, last) = (points[0], points[points.len() - 1]);
+        assert!(first.0 <= -5.0 && last.1 >= 5.0);
+        assert!(points.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn periodic_orbits_are_closed() {
+        let points = trajectory(&parse_field("(-y, x)"), (1.0, 0.0), &bounds(2.0));
+        assert!(points.iter().all(|point| (point.0.hypot(point.1) - 1.0).abs() < 1e-5));
+        assert!(points[0] == (1.0, 0.0) && points[points.len() - 1] == (1.0, 0.0));
+        // once around, counterclockwise
+        let length: f64 = points.windows(2).map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1)).sum();
+        assert!((length - 2.0 * std::f64::consts::PI).abs() < 1e-3);
+        assert!(points[1].1 > 0.0);
+    }
+
+    #[test]
+    fn spirals_are_not_closed() {
+        // the trajectory comes back near the start after a turn, but further than the error of the steps
+        let points = trajectory(&parse_field("(-0.001x - y, x - 0.001y)"), (1.0, 0.0), &bounds(2.0));
+        assert!(points.len() > 1000);
+        assert!(points[0] != points[points.len() - 1]);
+    }
+
+    #[test]
+    fn trajectories_stop_at_equilibrium_points() {
+        let points = trajectory(&parse_field("(-x, -2y)"), (1.0, 1.0), &bounds(2.0));
+        let last = points[points.len() - 1];
+        assert!(last.0.hypot(last.1) < 1e-6);
+        assert!(points.len() < 2 * MAX_STEPS);
+        // backwards it goes away from the equilibrium, out of the bounds
+        assert!(points[0].0 >= 2.0 || points[0].1 >= 2.0);
+    }
+}
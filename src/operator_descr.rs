@@ -1,25 +1,36 @@
 use std::collections::HashMap;
 
-use crate::expression::Operation;
+use crate::expression::{Operation, InputSpace};
 use crate::semantics::*;
+use crate::number_literal::NumberLiteral;
 
 // Description of an operator supported
-// For now we support three types of operators:
+// For now we support four types of operators:
 // - Unary operators, are written in prefix form
 // - Binary operators, are written in infix form
+// - Functions of several arguments, are written as f(x, y)
 // - Constants,
 pub struct OperatorTable<Number: Clone + From<f64>> {
     unary_ops: HashMap<&'static str, UnaryOp<Number>>,
     binary_ops: HashMap<&'static str, BinaryOp<Number>>,
+    function_ops: HashMap<&'static str, FunctionOp<Number>>,
     const_ops: HashMap<&'static str, ConstantOp<Number>>,
+    // How number literals are represented
+    number_semantics: fn (NumberLiteral) -> Number,
+    undefined: Number,
+    // The complex variable, if the numbers have one
+    zvar: Option<fn (InputSpace<Number>) -> Number>,
 }
 
 impl<Number: Clone + From<f64>> OperatorTable<Number> {
     // panics if there is any duplicate symbol
     // TODO: Check that constants and unary symbols don't overlap
-    pub fn new(unary: Vec<UnaryOp<Number>>, binary: Vec<BinaryOp<Number>>, consts: Vec<ConstantOp<Number>>) -> OperatorTable<Number> {
+    pub fn new(unary: Vec<UnaryOp<Number>>, binary: Vec<BinaryOp<Number>>, functions: Vec<FunctionOp<Number>>,
+               consts: Vec<ConstantOp<Number>>, number_semantics: fn (NumberLiteral) -> Number,
+               undefined: Number) -> OperatorTable<Number> {
         let mut unary_table = HashMap::new();
         let mut binary_table = HashMap::new();
+        let mut function_table = HashMap::new();
         let mut const_table = HashMap::new();
 
         for op in unary.into_iter() {
@@ -36,6 +47,13 @@ impl<Number: Clone + From<f64>> OperatorTable<Number> {
             }
         }
 
+        for op in functions.into_iter() {
+            if let Some(_) = function_table.insert(op.symbol, op) {
+                // Duplicate symbols are not allowed
+                panic!("Duplicate function symbol")
+            }
+        }
+
         for op in consts.into_iter() {
             if let Some(_) = const_table.insert(op.symbol, op) {
                 // Duplicate symbols are not allowed
@@ -46,9 +64,17 @@ impl<Number: Clone + From<f64>> OperatorTable<Number> {
         OperatorTable {
             unary_ops: unary_table,
             binary_ops: binary_table,
+            function_ops: function_table,
             const_ops: const_table,
+            number_semantics,
+            undefined,
+            zvar: None,
         }
     }
+
+    pub fn set_zvar(&mut self, zvar: fn (InputSpace<Number>) -> Number) {
+        self.zvar = Some(zvar);
+    }
 }
 
 impl<Number: Clone + From<f64>> Semantics for OperatorTable<Number> {
@@ -61,6 +87,11 @@ impl<Number: Clone + From<f64>> Semantics for OperatorTable<Number> {
     fn lookup_binary(&self, symbol: &str) -> Option<&BinaryOp<Number>> {
         self.binary_ops.get(symbol)
     }
+
+    fn lookup_function(&self, symbol: &str) -> Option<&FunctionOp<Number>> {
+        self.function_ops.get(symbol)
+    }
+
     fn lookup_const(&self, symbol: &str) -> Option<&ConstantOp<Number>> {
         self.const_ops.get(symbol)
     }
@@ -68,11 +99,16 @@ impl<Number: Clone + From<f64>> Semantics for OperatorTable<Number> {
     fn has_symbol(&self, symbol: &str) -> bool {
         self.unary_ops.contains_key(symbol) ||
         self.binary_ops.contains_key(symbol) ||
+        self.function_ops.contains_key(symbol) ||
         self.const_ops.contains_key(symbol)
     }
 
-    fn number(&self, num: f64) -> Operation<Number> {
-        Operation::Constant(Number::from(num))
+    fn number(&self, literal: NumberLiteral) -> Operation<Number> {
+        Operation::Constant((self.number_semantics)(literal))
+    }
+
+    fn undefined(&self) -> Operation<Number> {
+        Operation::Constant(self.undefined.clone())
     }
 
     fn xvar(&self) -> Operation<Number> {
@@ -82,6 +118,10 @@ impl<Number: Clone + From<f64>> Semantics for OperatorTable<Number> {
     fn yvar(&self) -> Operation<Number> {
         Operation::Variable(|input| input.y)
     }
+
+    fn zvar(&self) -> Option<Operation<Number>> {
+        self.zvar.map(Operation::Variable)
+    }
 }
 
 
@@ -1,17 +1,33 @@
 use crate::semantics::*;
+use crate::expression::elementwise;
 use crate::operator_descr::OperatorTable;
+use crate::plasm_number::PlasmNumber;
 use honestintervals::IntervalSet;
-use honestintervals::transc::Transc;
+use crate::dual::Dual;
+use crate::glsl::Glsl;
+use num_complex::Complex;
 
-pub fn default_operator_table() -> OperatorTable<f64> {
-    let unary_ops: Vec<UnaryOp<f64>> = vec![
+fn unary_ops<Number: PlasmNumber>() -> Vec<UnaryOp<Number>> {
+    vec![
+        UnaryOp {
+            symbol: "-",
+            semantics: |x| (-x),
+        },
         UnaryOp {
             symbol: "ln",
             semantics: |x| x.ln(),
         },
         UnaryOp {
             symbol: "log",
-            semantics: |x| x.log(10.0),
+            semantics: |x| x.log10(),
+        },
+        UnaryOp {
+            symbol: "log2",
+            semantics: |x| x.log2(),
+        },
+        UnaryOp {
+            symbol: "exp",
+            semantics: |x| x.exp(),
         },
         UnaryOp {
             symbol: "sin",
@@ -29,10 +45,6 @@ pub fn default_operator_table() -> OperatorTable<f64> {
             symbol: "sqrt",
             semantics: |x| x.sqrt(),
         },
-        UnaryOp {
-            symbol: "-",
-            semantics: |x| (-x),
-        },
         UnaryOp {
             symbol: "sgn",
             semantics: |x| x.signum(),
@@ -57,142 +69,381 @@ pub fn default_operator_table() -> OperatorTable<f64> {
             symbol: "atan",
             semantics: |x| x.atan(),
         },
-    ];
+        UnaryOp {
+            symbol: "sinh",
+            semantics: |x| x.sinh(),
+        },
+        UnaryOp {
+            symbol: "cosh",
+            semantics: |x| x.cosh(),
+        },
+        UnaryOp {
+            symbol: "tanh",
+            semantics: |x| x.tanh(),
+        },
+        UnaryOp {
+            symbol: "floor",
+            semantics: |x| x.floor(),
+        },
+        UnaryOp {
+            symbol: "ceil",
+            semantics: |x| x.ceil(),
+        },
+        UnaryOp {
+            symbol: "round",
+            semantics: |x| x.round(),
+        },
+        UnaryOp {
+            symbol: "gamma",
+            semantics: |x| x.gamma(),
+        },
+        UnaryOp {
+            symbol: "lgamma",
+            semantics: |x| x.lgamma(),
+        },
+        UnaryOp {
+            symbol: "erf",
+            semantics: |x| x.erf(),
+        },
+        UnaryOp {
+            symbol: "erfc",
+            semantics: |x| x.erfc(),
+        },
+        UnaryOp {
+            symbol: "airy",
+            semantics: |x| x.airy(),
+        },
+        UnaryOp {
+            symbol: "zeta",
+            semantics: |x| x.zeta(),
+        },
+        UnaryOp {
+            symbol: "W",
+            semantics: |x| x.lambert_w(),
+        },
+        UnaryOp {
+            symbol: "!",
+            semantics: |x| x.logical_not(),
+        },
+        UnaryOp {
+            symbol: "not",
+            semantics: |x| x.logical_not(),
+        },
+    ]
+}
 
-    let binary_ops: Vec<BinaryOp<f64>> = vec![
+fn binary_ops<Number: PlasmNumber>() -> Vec<BinaryOp<Number>> {
+    vec![
         BinaryOp {
-            symbol: "+",
-            semantics: |x, y| x+y,
+            symbol: "||",
+            semantics: |x, y| x.logical_or(y),
+            batched: None,
             assoc: Assoc::Left,
             prec: 1,
         },
         BinaryOp {
-            symbol: "-",
-            semantics: |x, y| x-y,
+            symbol: "or",
+            semantics: |x, y| x.logical_or(y),
+            batched: None,
             assoc: Assoc::Left,
             prec: 1,
-
         },
         BinaryOp {
-            symbol: "*",
-            semantics: |x, y| x*y,
+            symbol: "&&",
+            semantics: |x, y| x.logical_and(y),
+            batched: None,
             assoc: Assoc::Left,
             prec: 2,
         },
         BinaryOp {
-            symbol: "/",
-            semantics: |x, y| x/y,
+            symbol: "and",
+            semantics: |x, y| x.logical_and(y),
+            batched: None,
             assoc: Assoc::Left,
             prec: 2,
         },
         BinaryOp {
-            symbol: "^",
-            semantics: |x, y| x.powf(y),
+            symbol: "<",
+            semantics: |x, y| x.less(y),
+            batched: None,
             assoc: Assoc::Left,
             prec: 3,
         },
-    ];
-
-    let consts: Vec<ConstantOp<f64>> = vec![
-        ConstantOp {
-            symbol: "pi",
-            semantics: std::f64::consts::PI,
-        },
-        ConstantOp {
-            symbol: "e",
-            semantics: std::f64::consts::E,
-        },
-    ];
-
-    OperatorTable::new(unary_ops, binary_ops, consts)
-}
-
-
-
-pub fn interval_arithmetic_operator_table() -> OperatorTable<IntervalSet<f64>> {
-    let unary_ops: Vec<UnaryOp<IntervalSet<f64>>> = vec![
-        UnaryOp {
-            symbol: "-",
-            semantics: |x| (-x),
-        },
-        UnaryOp {
-            symbol: "sin",
-            semantics: |x| x.sin(),
-        },
-        UnaryOp {
-            symbol: "cos",
-            semantics: |x| x.cos(),
-        },
-        UnaryOp {
-            symbol: "tan",
-            semantics: |x| x.tan(),
-        },
-        UnaryOp {
-            symbol: "exp",
-            semantics: |x| x.exp(),
+        BinaryOp {
+            symbol: "<=",
+            semantics: |x, y| x.less_equal(y),
+            batched: None,
+            assoc: Assoc::Left,
+            prec: 3,
         },
-        UnaryOp {
-            symbol: "sqrt",
-            semantics: |x| x.sqrt(),
+        BinaryOp {
+            symbol: ">",
+            semantics: |x, y| y.less(x),
+            batched: None,
+            assoc: Assoc::Left,
+            prec: 3,
         },
-        UnaryOp {
-            symbol: "abs",
-            semantics: |x| x.abs(),
+        BinaryOp {
+            symbol: ">=",
+            semantics: |x, y| y.less_equal(x),
+            batched: None,
+            assoc: Assoc::Left,
+            prec: 3,
         },
-        UnaryOp {
-            symbol: "sign",
-            semantics: |x| x.signum(),
+        BinaryOp {
+            symbol: "==",
+            semantics: |x, y| x.equal(y),
+            batched: None,
+            assoc: Assoc::Left,
+            prec: 3,
         },
-        UnaryOp {
-            symbol: "sgn",
-            semantics: |x| x.signum(),
+        BinaryOp {
+            symbol: "!=",
+            semantics: |x, y| x.equal(y).logical_not(),
+            batched: None,
+            assoc: Assoc::Left,
+            prec: 3,
         },
-    ];
-
-    let binary_ops: Vec<BinaryOp<IntervalSet<f64>>> = vec![
         BinaryOp {
             symbol: "+",
             semantics: |x, y| x+y,
+            batched: Some(|x, y| elementwise(x, y, |x, y| x+y)),
             assoc: Assoc::Left,
-            prec: 1,
+            prec: 4,
         },
         BinaryOp {
             symbol: "-",
             semantics: |x, y| x-y,
+            batched: Some(|x, y| elementwise(x, y, |x, y| x-y)),
             assoc: Assoc::Left,
-            prec: 1,
+            prec: 4,
 
         },
         BinaryOp {
             symbol: "*",
             semantics: |x, y| x*y,
+            batched: Some(|x, y| elementwise(x, y, |x, y| x*y)),
             assoc: Assoc::Left,
-            prec: 2,
+            prec: 5,
         },
         BinaryOp {
             symbol: "/",
             semantics: |x, y| x/y,
+            batched: Some(|x, y| elementwise(x, y, |x, y| x/y)),
             assoc: Assoc::Left,
-            prec: 2,
+            prec: 5,
         },
         BinaryOp {
             symbol: "^",
             semantics: |x, y| x.pow(y),
+            batched: None,
             assoc: Assoc::Left,
-            prec: 3,
+            prec: 6,
         },
-    ];
+    ]
+}
+
+fn functions<Number: PlasmNumber>() -> Vec<FunctionOp<Number>> {
+    vec![
+        FunctionOp {
+            symbol: "min",
+            arity: 2,
+            semantics: |args| args[0].clone().min(args[1].clone()),
+        },
+        FunctionOp {
+            symbol: "max",
+            arity: 2,
+            semantics: |args| args[0].clone().max(args[1].clone()),
+        },
+        FunctionOp {
+            symbol: "besselj",
+            arity: 2,
+            semantics: |args| Number::besselj(args[0].clone(), args[1].clone()),
+        },
+        FunctionOp {
+            symbol: "if",
+            arity: 3,
+            semantics: |args| Number::select(args[0].clone(), args[1].clone(), args[2].clone()),
+        },
+    ]
+}
 
-    let consts: Vec<ConstantOp<IntervalSet<f64>>> = vec![
+fn consts<Number: PlasmNumber>() -> Vec<ConstantOp<Number>> {
+    vec![
         ConstantOp {
             symbol: "pi",
-            semantics: IntervalSet::singleton(std::f64::consts::PI),
+            semantics: Number::pi(),
         },
         ConstantOp {
             symbol: "e",
-            semantics: IntervalSet::singleton(std::f64::consts::E),
+            semantics: Number::e(),
         },
-    ];
+    ]
+}
+
+// Every kind of number supports the same operators, so we build all the tables the same way
+pub fn operator_table<Number: PlasmNumber>() -> OperatorTable<Number> {
+    OperatorTable::new(unary_ops(), binary_ops(), functions(), consts(), Number::from_literal, Number::undefined())
+}
 
-    OperatorTable::new(unary_ops, binary_ops, consts)
-}
\ No newline at end of file
+pub fn default_operator_table() -> OperatorTable<f64> {
+    operator_table()
+}
+
+pub fn interval_arithmetic_operator_table() -> OperatorTable<IntervalSet<f64>> {
+    operator_table()
+}
+
+// The expression of the input evaluated on intervals, as the plots are, for the tests
+#[cfg(test)]
+pub fn parse_interval(input: &str) -> crate::expression::Expression<IntervalSet<f64>> {
+    crate::parser::parse(input, &interval_arithmetic_operator_table()).unwrap()
+}
+
+// Evaluating expressions on dual numbers gives an enclosure of the derivative along with the value
+pub fn differentiation_operator_table() -> OperatorTable<Dual> {
+    operator_table()
+}
+
+// Evaluating expressions on GLSL code translates them to shaders
+pub fn glsl_operator_table() -> OperatorTable<Glsl> {
+    operator_table()
+}
+
+// The parts of a complex number, only complex numbers have them
+fn complex_unary_ops() -> Vec<UnaryOp<Complex<f64>>> {
+    vec![
+        UnaryOp {
+            symbol: "re",
+            semantics: |z| Complex::from(z.re),
+        },
+        UnaryOp {
+            symbol: "im",
+            semantics: |z| Complex::from(z.im),
+        },
+        UnaryOp {
+            symbol: "arg",
+            semantics: |z| Complex::from(z.arg()),
+        },
+        UnaryOp {
+            symbol: "conj",
+            semantics: |z| z.conj(),
+        },
+    ]
+}
+
+// Complex numbers also have the imaginary unit i and the variable z = x + iy
+pub fn complex_operator_table() -> OperatorTable<Complex<f64>> {
+    let mut unary = unary_ops();
+    unary.extend(complex_unary_ops());
+    let mut constants = consts();
+    constants.push(ConstantOp {
+        symbol: "i",
+        semantics: Complex::i(),
+    });
+
+    let mut table = OperatorTable::new(unary, binary_ops(), functions(), constants, Complex::from_literal, Complex::undefined());
+    table.set_zvar(|input| Complex::new(input.x.re, input.y.re));
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, parse_contour_plot};
+
+    // Check that every symbol can be used with every kind of number
+    fn assert_parses<Number: PlasmNumber>(table: &OperatorTable<Number>) {
+        for op in unary_ops::<f64>() {
+            let input = format!("{}(x)", op.symbol);
+            assert!(parse(&input, table).is_ok(), "could not parse {}", input);
+        }
+
+        for op in binary_ops::<f64>() {
+            let input = format!("x {} x", op.symbol);
+            assert!(parse(&input, table).is_ok(), "could not parse {}", input);
+        }
+
+        for op in functions::<f64>() {
+            let input = format!("{}({})", op.symbol, vec!["x"; op.arity].join(", "));
+            assert!(parse(&input, table).is_ok(), "could not parse {}", input);
+        }
+
+        for op in consts::<f64>() {
+            assert!(parse(op.symbol, table).is_ok(), "could not parse {}", op.symbol);
+        }
+    }
+
+    #[test]
+    fn piecewise_functions_parse() {
+        let table = default_operator_table();
+        for input in &["if(x < 0, -x, x^2)", "{ x^2 : x > 0 ; -x : otherwise }", "{ 1 : x >= 0 && x <= 1 }", "x == 1"] {
+            assert!(parse(input, &table).is_ok(), "could not parse {}", input);
+        }
+        for input in &["{ x : otherwise ; 1 : x > 0 }", "{ x : x > 0", "{ x }"] {
+            assert!(parse(input, &table).is_err(), "{} should not parse", input);
+        }
+    }
+
+    #[test]
+    fn sums_and_products_evaluate() {
+        let table = default_operator_table();
+        let cases = [("sum(k, 1, 4, k)", 10.0), ("prod(k, 1, 5, k)", 120.0), ("sum(k, 3, 1, k)", 0.0),
+                     ("sum(j, 1, 3, sum(k, 1, 2, j*k))", 18.0), ("sum(k, -2, 2, k^2)", 10.0), ("2 sum(k, 0, 2, 2^k)", 14.0)];
+        for (input, value) in cases.iter() {
+            assert_eq!(parse(input, &table).unwrap().eval_constant(), *value, "wrong value of {}", input);
+        }
+        for input in &["sum(k, 1, x, k)", "sum(j, 1, 3, sum(k, 1, j, k))", "sum(1, 1, 2, 3)", "sum(k, 1.5, 2, k)", "sum(k, 1, 100000, k)", "k"] {
+            assert!(parse(input, &table).is_err(), "{} should not parse", input);
+        }
+
+        // the terms of nested sums are counted together
+        assert!(parse("sum(j, 1, 100, sum(k, 1, 100, j*k))", &table).is_ok());
+        assert!(parse("sum(j, 1, 100, sum(k, 1, 101, j*k))", &table).is_err());
+        assert!(parse("sum(i, 1, 100, sum(j, 1, 100, sum(k, 1, 100, i*j*k)))", &table).is_err());
+        assert!(parse("sum(j, 1, 100, 1) + sum(k, 1, 10000, k)", &table).is_ok());
+    }
+
+    #[test]
+    fn sums_over_intervals_are_enclosed() {
+        let table = interval_arithmetic_operator_table();
+        let sum = parse("sum(k, 1, 3, k*x)", &table).unwrap().eval_2d(IntervalSet::new(1.0, 2.0));
+        let (start, end) = crate::interval::hull(&sum).unwrap();
+        assert!(start <= 6.0 && end >= 12.0 && end - start < 6.001);
+        let product = parse("prod(k, 1, 2, x - k)", &table).unwrap().eval_2d(IntervalSet::singleton(0.5));
+        assert_eq!(crate::interval::hull(&product), Some((0.75, 0.75)));
+    }
+
+    #[test]
+    fn every_symbol_parses_under_every_table() {
+        assert_parses(&default_operator_table());
+        assert_parses(&interval_arithmetic_operator_table());
+        assert_parses(&differentiation_operator_table());
+        assert_parses(&glsl_operator_table());
+        assert_parses(&complex_operator_table());
+    }
+
+    #[test]
+    fn complex_functions_evaluate() {
+        let table = complex_operator_table();
+        let value = parse("z^2 + i", &table).unwrap().eval_3d(Complex::from(1.0), Complex::from(2.0));
+        assert_eq!(value, Complex::new(-3.0, 5.0));
+        let value = parse("exp(i pi) + 1", &table).unwrap().eval_constant();
+        assert!(value.norm() < 1e-15);
+        assert!(parse("z", &default_operator_table()).is_err());
+    }
+
+    #[test]
+    fn contour_plots_parse() {
+        let table = default_operator_table();
+        let (function, colormap, levels) = parse_contour_plot("heatmap(x*y, diverging, -1, 0, 2/4)", "heatmap", &table).unwrap().unwrap();
+        assert_eq!(function.eval_3d(2.0, 3.0), 6.0);
+        assert_eq!(colormap.as_deref(), Some("diverging"));
+        assert_eq!(levels.iter().map(|level| level.eval_constant()).collect::<Vec<_>>(), vec![-1.0, 0.0, 0.5]);
+        assert!(parse_contour_plot("x*y", "heatmap", &table).is_none());
+        assert!(parse_contour_plot("surface(x*y)", "heatmap", &table).is_none());
+        for input in &["heatmap(x*y, 1, y)", "heatmap(x = y)", "heatmap(x*y, 1, viridis)", "heatmap(x*y"] {
+            assert!(parse_contour_plot(input, "heatmap", &table).unwrap().is_err(), "{} should not parse", input);
+        }
+    }
+}
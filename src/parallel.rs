@@ -0,0 +1,54 @@
+// Loops over independent items, run on all the cores with the parallel feature, as on desktop,
+// and serially without it, as on the web. The results are always in the order of the items,
+// so the plots are the same either way
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+// Apply the function to every item
+pub fn map<T, R, F>(items: &[T], f: F) -> Vec<R> where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync + Send {
+    #[cfg(feature = "parallel")]
+    let results = items.par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    let results = items.iter().map(f).collect();
+    results
+}
+
+// Apply the function to the numbers from 0 to count, excluded
+pub fn map_range<R, F>(count: usize, f: F) -> Vec<R> where
+    R: Send,
+    F: Fn(usize) -> R + Sync + Send {
+    #[cfg(feature = "parallel")]
+    let results = (0..count).into_par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    let results = (0..count).map(f).collect();
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_are_in_the_order_of_the_items() {
+        let items: Vec<u64> = (0..10_000).collect();
+        assert_eq!(map(&items, |item| item * 2), (0..10_000).map(|item| item * 2).collect::<Vec<u64>>());
+        assert_eq!(map_range(10_000, |i| i + 1), (1..=10_000).collect::<Vec<usize>>());
+    }
+
+    // The order is kept however the items are spread over the threads
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_results_are_the_serial_ones() {
+        let items: Vec<u64> = (0..10_000).collect();
+        let serial: Vec<u64> = items.iter().map(|item| item * item % 977).collect();
+        for threads in [1, 2, 3, 8].iter() {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(*threads).build().unwrap();
+            assert_eq!(pool.install(|| map(&items, |item| item * item % 977)), serial);
+            assert_eq!(pool.install(|| map_range(items.len(), |i| items[i] * items[i] % 977)), serial);
+        }
+    }
+}
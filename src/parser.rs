@@ -2,16 +2,29 @@ use std::str::Chars;
 use std::iter::Peekable;
 use std::result::Result;
 
-use crate::expression::{Operation, ExprType, Expression};
+use crate::expression::{Operation, ExprType, Expression, Reduction};
 use crate::semantics::*;
+use crate::number_literal::NumberLiteral;
+use crate::definitions::Definition;
 
 enum Token {
     Operator(String),
+    // A name which is not a builtin symbol, like a function defined by the user
+    Name(String),
     XVar,
     YVar,
-    Number(f64),
+    ZVar,
+    Number(NumberLiteral),
     LeftParen,
     RightParen,
+    Comma,
+    LeftBrace,
+    RightBrace,
+    Colon,
+    Semicolon,
+    Otherwise,
+    Sum,
+    Product,
     Equal,
     Eof,
     Error(&'static str), // Error with explaination of the error
@@ -36,42 +49,92 @@ impl<'s, S: Semantics> Tokenizer<'s, S> {
         }
     }
 
-    // Returns the lexed number and the number of digits it is composed of
-    fn read_integer(&mut self) -> (f64, u32) {
-        let mut digits = 0;
-        let mut number = 0.0;
+    // Append the digits to the given string and return how many they are.
+    // Digits can be grouped with the '_' separator, e.g. 1_000_000
+    fn read_digits(&mut self, digits: &mut String) -> Result<usize, &'static str> {
+        let mut count = 0;
 
-        while self.input.peek().map_or(false, |c| c.is_digit(10)) {
-            digits += 1;
-            number *= 10.0;
-            number += self.input.next().unwrap().to_digit(10).unwrap() as f64;
+        loop {
+            match self.input.peek() {
+                Some(c) if c.is_digit(10) => {
+                    digits.push(*c);
+                    count += 1;
+                    self.input.next();
+                },
+                Some('_') if count > 0 => {
+                    self.input.next();
+                    if !self.input.peek().map_or(false, |c| c.is_digit(10)) {
+                        return Err("Digit separator must be followed by a digit");
+                    }
+                },
+                _ => return Ok(count),
+            }
+        }
+    }
+
+    // Check if an exponent follows, i.e. 'e' or 'E' followed by digits, optionally signed.
+    // Otherwise 'e' is the constant, as in 2e
+    fn exponent_follows(&self) -> bool {
+        let mut lookahead = self.input.clone();
+        match lookahead.next() {
+            Some('e') | Some('E') => (),
+            _ => return false,
         }
 
-        return (number, digits)
+        match lookahead.next() {
+            Some(c) if c.is_digit(10) => true,
+            Some('+') | Some('-') => lookahead.next().map_or(false, |c| c.is_digit(10)),
+            _ => false,
+        }
     }
 
+    // Lex a number literal: digits, an optional decimal part and an optional exponent,
+    // e.g. 12, 1_000, 0.5, .5, 1e-3, 2.5E10
     fn read_number(&mut self) -> Token {
-        let (integer_part, _) = self.read_integer();
+        let mut digits = String::new();
+
+        if let Err(err) = self.read_digits(&mut digits) {
+            return Token::Error(err);
+        }
 
         // Parse decimal part
+        let mut decimal_digits = 0;
         if self.input.peek().map_or(false, |c| *c == '.') {
             self.input.next();
             // Now we need digits for the decimal part, if none is found, it is an error
             if !self.input.peek().map_or(false, |c| c.is_digit(10)) {
                 return Token::Error("Missing decimal part in floating point number");
-            } else {
-                let (decimal_digits, digits) = self.read_integer();
+            }
+            decimal_digits = match self.read_digits(&mut digits) {
+                Ok(count) => count,
+                Err(err) => return Token::Error(err),
+            };
+        }
 
-                // Interpret the decimal part correctly, now it is an integer,
-                // but we want to divide it by 10^{digits}
-                let decimal_part_magnitude = (10 as i32).pow(digits) as f64;
-                let decimal_part = decimal_digits / decimal_part_magnitude;
+        // Parse exponent
+        let mut exponent: i64 = 0;
+        if self.exponent_follows() {
+            self.input.next();
+            let negative = match self.input.peek() {
+                Some('-') => true,
+                _ => false,
+            };
+            if self.input.peek().map_or(false, |c| *c == '+' || *c == '-') {
+                self.input.next();
+            }
 
-                return Token::Number(integer_part + decimal_part);
+            let mut exponent_digits = String::new();
+            if let Err(err) = self.read_digits(&mut exponent_digits) {
+                return Token::Error(err);
+            }
+            // Absurdly big exponents just saturate, they give either 0 or infinity anyway
+            exponent = exponent_digits.parse::<i64>().unwrap_or(i32::max_value() as i64);
+            if negative {
+                exponent = -exponent;
             }
-        } else {
-            return Token::Number(integer_part);
         }
+
+        Token::Number(NumberLiteral::from_decimal(&digits, exponent - decimal_digits as i64))
     }
 
     fn read_identifier(&mut self) -> Token {
@@ -84,23 +147,56 @@ impl<'s, S: Semantics> Tokenizer<'s, S> {
         // The second and third type are words composed only of alphabetic characters,
         // we use this fact to distinguish and correctly recognize identifiers
 
-        if self.input.peek().map_or(false, char::is_ascii_alphabetic) {
+        let is_word = self.input.peek().map_or(false, char::is_ascii_alphabetic);
+        if is_word {
             // Ok, this is either a variable or a function
             while self.input.peek().map_or(false, char::is_ascii_alphabetic) {
                 identifier.push(self.input.next().unwrap());
             }
+
+            // A few functions end with digits, like log2.
+            // Only take the digits if they complete a known symbol, so that x2 is still x*2
+            if self.input.peek().map_or(false, |c| c.is_digit(10)) {
+                let mut lookahead = self.input.clone();
+                let mut candidate = identifier.clone();
+                while lookahead.peek().map_or(false, |c| c.is_digit(10)) {
+                    candidate.push(lookahead.next().unwrap());
+                }
+                if self.table.has_symbol(&candidate.as_str()) {
+                    self.input = lookahead;
+                    identifier = candidate;
+                }
+            }
         } else {
             // We are sure we are not at EOF
             identifier.push(self.input.next().unwrap());
+
+            // Some operators are made of two characters, like <= and &&
+            if let Some(c) = self.input.peek() {
+                let candidate = format!("{}{}", identifier, c);
+                if self.table.has_symbol(&candidate.as_str()) {
+                    self.input.next();
+                    identifier = candidate;
+                }
+            }
         }
 
-        // First check for variables
-        if identifier == "x" {
+        // First check for variables and keywords
+        if identifier == "otherwise" {
+            return Token::Otherwise
+        } else if identifier == "sum" {
+            return Token::Sum
+        } else if identifier == "prod" {
+            return Token::Product
+        } else if identifier == "x" {
             return Token::XVar
         } else if identifier == "y" {
             return Token::YVar
+        } else if identifier == "z" && self.table.zvar().is_some() {
+            return Token::ZVar
         } else {
             match self.table.has_symbol(&identifier.as_str()) {
+                false if is_word => Token::Name(identifier),
                 false => Token::Error("Unrecognized identifier"),
                 true => return Token::Operator(identifier),
             }
@@ -121,11 +217,36 @@ impl<'s, S: Semantics> Tokenizer<'s, S> {
                         self.input.next();
                         return Token::RightParen
                     },
+                    ',' => {
+                        self.input.next();
+                        return Token::Comma
+                    },
+                    '{' => {
+                        self.input.next();
+                        return Token::LeftBrace
+                    },
+                    '}' => {
+                        self.input.next();
+                        return Token::RightBrace
+                    },
+                    ':' => {
+                        self.input.next();
+                        return Token::Colon
+                    },
+                    ';' => {
+                        self.input.next();
+                        return Token::Semicolon
+                    },
                     '=' => {
                         self.input.next();
+                        // == is the equality comparison, while a single = makes an implicit function
+                        if self.input.peek() == Some(&'=') && self.table.has_symbol("==") {
+                            self.input.next();
+                            return Token::Operator(String::from("=="))
+                        }
                         return Token::Equal
                     },
-                    c if c.is_digit(10) => self.read_number(),
+                    c if c.is_digit(10) || *c == '.' => self.read_number(),
                     _ => self.read_identifier(),
 
 
@@ -147,19 +268,24 @@ struct Parser<'s, S: Semantics> {
     // Internal state representing the current expression
     // that is being parsed
     operations: Vec<Operation<S::Number>>,
+    has_x: bool,
     is_3d: bool,
+
+    // The user defined functions being inlined, to detect recursion
+    call_stack: Vec<String>,
+    // The index names of the enclosing sums and products, and the parameter names
+    // of the user defined function being parsed, the innermost is the last
+    indices: Vec<String>,
+    // The number of times the expression being parsed is evaluated by the enclosing sums and products
+    terms: i64,
 }
 
-pub fn parse<S: Semantics>(input: & str, table: &S) -> Result<Expression<S::Number>, &'static str>  {
-    let mut parser = Parser {
-        tokenizer: Tokenizer::new(input, table),
-        table: table,
-        look_ahead: Token::Eof,
-        operations: Vec::new(),
-        is_3d: false,
-    };
+// Maximum number of terms of a sum or a product, counting those of the enclosing ones:
+// sum(j, 1, 100, sum(k, 1, 100, j*k)) has 10000 terms
+const MAX_TERMS: i64 = 10_000;
 
-    parser.look_ahead = parser.tokenizer.next_token();
+pub fn parse<S: Semantics>(input: & str, table: &S) -> Result<Expression<S::Number>, &'static str>  {
+    let mut parser = Parser::new(input, table);
 
     parser.parse_expr(0)?;
     match parser.look_ahead {
@@ -179,14 +305,222 @@ pub fn parse<S: Semantics>(input: & str, table: &S) -> Result<Expression<S::Numb
             Ok(Expression::new(parser.operations, ExprType::ExprImplicit))
         }
         Token::Eof => {
-            let expr_type = if parser.is_3d { ExprType::Expr3d } else { ExprType::Expr2d };
+            let expr_type = parser.expr_type();
             Ok(Expression::new(parser.operations, expr_type))
         },
         _ => Err("Unexpected token at end of expression")
     }
 }
 
+// Recognize a vector field, written as the pair of its components (P, Q)
+pub fn parse_vector_field<S: Semantics>(input: &str, table: &S) -> Option<Result<(Expression<S::Number>, Expression<S::Number>), &'static str>> {
+    let mut parser = Parser::new(input, table);
+    match parser.look_ahead {
+        Token::LeftParen => parser.next_token(),
+        _ => return None,
+    }
+
+    // Without the comma it is just an expression in parentheses
+    parser.parse_expr(0).ok()?;
+    match parser.look_ahead {
+        Token::Comma => parser.next_token(),
+        _ => return None,
+    }
+    let first = std::mem::replace(&mut parser.operations, Vec::new());
+
+    Some(parser.parse_expr(0).and_then(|_| {
+        match parser.look_ahead {
+            Token::RightParen => parser.next_token(),
+            _ => return Err("Missing )"),
+        }
+        match parser.look_ahead {
+            Token::Eof => (),
+            _ => return Err("Unexpected token at end of expression"),
+        }
+
+        // The components are functions of the whole plane
+        Ok((Expression::new(first, ExprType::Expr3d), Expression::new(parser.operations, ExprType::Expr3d)))
+    }))
+}
+
+// Recognize a plot of a function of the plane with its contour lines, written keyword(f(x, y)), optionally followed
+// by the name of a colormap and by the levels of the contour lines, like heatmap(x^2 - y^2, diverging, -1, 0, 1).
+// Returns the function, the name of the colormap and the levels, which must be constants
+pub fn parse_contour_plot<S: Semantics>(input: &str, keyword: &str, table: &S) -> Option<Result<(Expression<S::Number>, Option<String>, Vec<Expression<S::Number>>), &'static str>> {
+    let mut parser = Parser::new(input, table);
+    match parser.look_ahead {
+        Token::Name(ref name) if name == keyword => parser.next_token(),
+        _ => return None,
+    }
+    match parser.look_ahead {
+        Token::LeftParen => parser.next_token(),
+        _ => return None,
+    }
+
+    Some(parser.parse_expr(0).and_then(|_| {
+        let function = std::mem::replace(&mut parser.operations, Vec::new());
+        let mut colormap = None;
+        let mut levels = Vec::new();
+
+        loop {
+            match parser.look_ahead {
+                Token::Comma => parser.next_token(),
+                Token::RightParen => {
+                    parser.next_token();
+                    break;
+                },
+                Token::Equal => return Err("Unexpected = in the function"),
+                _ => return Err("Missing )"),
+            }
+
+            // The colormap can only be the first argument after the function
+            if let Token::Name(ref name) = parser.look_ahead {
+                if colormap.is_none() && levels.is_empty() {
+                    colormap = Some(name.clone());
+                    parser.next_token();
+                    continue;
+                }
+            }
+
+            parser.has_x = false;
+            parser.is_3d = false;
+            parser.parse_expr(0)?;
+            match parser.expr_type() {
+                ExprType::Constant => (),
+                _ => return Err("The levels of the contour lines must be constants"),
+            }
+            levels.push(Expression::new(std::mem::replace(&mut parser.operations, Vec::new()), ExprType::Constant));
+        }
+
+        match parser.look_ahead {
+            Token::Eof => (),
+            _ => return Err("Unexpected token at end of expression"),
+        }
+        Ok((Expression::new(function, ExprType::Expr3d), colormap, levels))
+    }))
+}
+
+// The argument of name(argument), if the parenthesis after the name is closed at the end of the input,
+// used for the options written around a function, like dashed(sin(x))
+pub fn call_argument<'a>(input: &'a str, name: &str) -> Option<&'a str> {
+    if !input.starts_with(name) || !input[name.len()..].trim_start().starts_with('(') {
+        return None;
+    }
+    let open = name.len() + input[name.len()..].find('(').unwrap();
+
+    let mut depth = 0;
+    for (i, c) in input.char_indices().skip(open) {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => (),
+        }
+        if depth == 0 {
+            return if i == input.len() - 1 { Some(&input[open + 1..i]) } else { None };
+        }
+    }
+    None
+}
+
+// Recognize a slope field, written as the differential equation dy/dx = f(x, y) or y' = f(x, y)
+pub fn parse_slope_field<S: Semantics>(input: &str, table: &S) -> Option<Result<Expression<S::Number>, &'static str>> {
+    let input = input.trim_start();
+    let rest = ["dy/dx", "y'"].iter()
+        .find(|derivative| input.starts_with(*derivative))
+        .map(|derivative| input[derivative.len()..].trim_start())?;
+    if !rest.starts_with('=') || rest.starts_with("==") {
+        return None;
+    }
+
+    Some(parse(&rest[1..], table).and_then(|expression| match expression.expr_type() {
+        ExprType::ExprImplicit => Err("Unexpected = in differential equation"),
+        _ => Ok(expression),
+    }))
+}
+
+// Recognize the definition of a function, like f(x) = x^2 + 1, as opposed to expressions and implicit functions.
+// The name of the function must not be a builtin symbol.
+pub fn parse_definition<S: Semantics>(input: &str, table: &S) -> Option<Result<Definition, &'static str>> {
+    // The head of the definition ends at the first =, which must not be part of ==
+    let equal = input.find('=')?;
+    let (head, body) = (&input[..equal], input[equal + 1..].trim());
+    if body.starts_with('=') {
+        return None;
+    }
+
+    let mut tokenizer = Tokenizer::new(head, table);
+    let name = match tokenizer.next_token() {
+        Token::Name(name) => name,
+        _ => return None,
+    };
+    match tokenizer.next_token() {
+        Token::LeftParen => (),
+        _ => return None,
+    }
+
+    let mut parameters: Vec<String> = Vec::new();
+    loop {
+        let parameter = match tokenizer.next_token() {
+            Token::XVar => String::from("x"),
+            Token::YVar => String::from("y"),
+            Token::ZVar => String::from("z"),
+            Token::Name(parameter) => parameter,
+            _ => return None,
+        };
+        if parameters.contains(&parameter) {
+            return Some(Err("Repeated parameter name"));
+        }
+        parameters.push(parameter);
+
+        match tokenizer.next_token() {
+            Token::Comma => (),
+            Token::RightParen => break,
+            _ => return None,
+        }
+    }
+    match tokenizer.next_token() {
+        Token::Eof => (),
+        _ => return None,
+    }
+
+    if body.is_empty() {
+        return Some(Err("Missing body of function definition"));
+    }
+    Some(Ok(Definition {
+        name,
+        parameters,
+        body: String::from(body),
+    }))
+}
+
 impl<'s, S: Semantics> Parser<'s, S> {
+    fn new(input: &'s str, table: &'s S) -> Parser<'s, S> {
+        let mut tokenizer = Tokenizer::new(input, table);
+        let look_ahead = tokenizer.next_token();
+        Parser {
+            tokenizer,
+            table,
+            look_ahead,
+            operations: Vec::new(),
+            has_x: false,
+            is_3d: false,
+            call_stack: Vec::new(),
+            indices: Vec::new(),
+            terms: 1,
+        }
+    }
+
+    fn expr_type(&self) -> ExprType {
+        if self.is_3d {
+            ExprType::Expr3d
+        } else if self.has_x {
+            ExprType::Expr2d
+        } else {
+            // No variables at all, it can only be evaluated
+            ExprType::Constant
+        }
+    }
+
     fn parse_expr(&mut self, curr_prec: u32) -> Result<(), &'static str> {
         self.parse_prefix()?;
 
@@ -195,6 +529,10 @@ impl<'s, S: Semantics> Parser<'s, S> {
                 Token::Eof => return Ok(()),
                 Token::Equal => return Ok(()),
                 Token::RightParen => return Ok(()),
+                Token::Comma => return Ok(()),
+                Token::RightBrace => return Ok(()),
+                Token::Colon => return Ok(()),
+                Token::Semicolon => return Ok(()),
                 Token::Operator(name) => {
                     let op: &BinaryOp<S::Number>;
                     let is_implicit_product: bool;
@@ -262,6 +600,11 @@ impl<'s, S: Semantics> Parser<'s, S> {
     fn parse_prefix(&mut self) -> Result<(), &'static str> {
         match self.look_ahead {
             Token::Operator(ref name) => {
+                if let Some(function) = self.table.lookup_function(&name) {
+                    self.next_token();
+                    return self.parse_arguments(function);
+                }
+
                 // Check if the operator is a constant or an unary const.
                 // If it is both, it is an error!
                 match (self.table.lookup_const(&name), self.table.lookup_unary(&name)) {
@@ -285,17 +628,62 @@ impl<'s, S: Semantics> Parser<'s, S> {
                 self.operations.push(self.table.number(n));
                 Ok(())
             },
+            Token::Name(ref name) => {
+                // Indices of the enclosing sums and products, and parameters of the function being parsed
+                if let Some(depth) = self.index(name) {
+                    self.next_token();
+                    self.operations.push(Operation::Index(depth));
+                    return Ok(());
+                }
+
+                let table = self.table;
+                match table.lookup_definition(name) {
+                    Some(definition) => {
+                        self.next_token();
+                        self.parse_call(definition)
+                    },
+                    None => Err("Unrecognized identifier"),
+                }
+            },
             Token::XVar => {
                 self.next_token();
-                self.operations.push(self.table.xvar());
+                match self.index("x") {
+                    Some(depth) => self.operations.push(Operation::Index(depth)),
+                    None => {
+                        self.operations.push(self.table.xvar());
+                        self.has_x = true;
+                    },
+                }
                 Ok(())
             },
             Token::YVar => {
                 self.next_token();
-                self.operations.push(self.table.yvar());
-                self.is_3d = true;
+                match self.index("y") {
+                    Some(depth) => self.operations.push(Operation::Index(depth)),
+                    None => {
+                        self.operations.push(self.table.yvar());
+                        self.is_3d = true;
+                    },
+                }
                 Ok(())
             },
+            Token::ZVar => {
+                self.next_token();
+                match (self.index("z"), self.table.zvar()) {
+                    (Some(depth), _) => self.operations.push(Operation::Index(depth)),
+                    (None, Some(zvar)) => {
+                        // z depends on both x and y
+                        self.operations.push(zvar);
+                        self.has_x = true;
+                        self.is_3d = true;
+                    },
+                    (None, None) => return Err("Unrecognized identifier"),
+                }
+                Ok(())
+            },
+            Token::LeftBrace => self.parse_piecewise(),
+            Token::Sum => self.parse_reduction("+", 0.0),
+            Token::Product => self.parse_reduction("*", 1.0),
             Token::LeftParen => {
                 self.next_token();
                 self.parse_expr(0)?;
@@ -314,7 +702,327 @@ impl<'s, S: Semantics> Parser<'s, S> {
         }
     }
 
+    // Parse the arguments of a function call: (arg1, arg2, ...)
+    fn parse_arguments(&mut self, function: &FunctionOp<S::Number>) -> Result<(), &'static str> {
+        match self.look_ahead {
+            Token::LeftParen => self.next_token(),
+            _ => return Err("Missing ( after function name"),
+        }
+
+        let mut arguments = 0;
+        loop {
+            self.parse_expr(0)?;
+            arguments += 1;
+            match self.look_ahead {
+                Token::Comma => self.next_token(),
+                Token::RightParen => {
+                    self.next_token();
+                    break;
+                },
+                _ => return Err("Missing )"),
+            }
+        }
+
+        if arguments != function.arity {
+            return Err("Wrong number of arguments");
+        }
+        self.operations.push(function.operation());
+        Ok(())
+    }
+
+    // The depth of the innermost index or parameter with the given name
+    fn index(&self, name: &str) -> Option<usize> {
+        self.indices.iter().rposition(|index| index == name)
+    }
+
+    // Inline a call to a function defined by the user: parse the arguments, which are computed once,
+    // then the body of the function reading them in place of the parameters
+    fn parse_call(&mut self, definition: &'s Definition) -> Result<(), &'static str> {
+        if self.call_stack.contains(&definition.name) {
+            return Err("Recursive function definition");
+        }
+
+        match self.look_ahead {
+            Token::LeftParen => self.next_token(),
+            _ => return Err("Missing ( after function name"),
+        }
+
+        let mut arguments = 0;
+        loop {
+            self.parse_expr(0)?;
+            arguments += 1;
+            match self.look_ahead {
+                Token::Comma => self.next_token(),
+                Token::RightParen => {
+                    self.next_token();
+                    break;
+                },
+                _ => return Err("Missing )"),
+            }
+        }
+
+        if arguments != definition.parameters.len() {
+            return Err("Wrong number of arguments");
+        }
+
+        let mut body = Parser::new(&definition.body, self.table);
+        body.call_stack = self.call_stack.clone();
+        body.call_stack.push(definition.name.clone());
+        // The indices of the enclosing sums are out of scope in the body,
+        // but the sums in the body are nested inside them, and so are the arguments
+        body.indices = vec![String::new(); self.indices.len()];
+        body.indices.extend(definition.parameters.iter().cloned());
+        body.terms = self.terms;
+
+        body.parse_expr(0)?;
+        match body.look_ahead {
+            Token::Eof => (),
+            _ => return Err("Unexpected token at end of function body"),
+        }
+
+        self.operations.push(Operation::Call(body.operations, arguments));
+        self.has_x |= body.has_x;
+        self.is_3d |= body.is_3d;
+        Ok(())
+    }
+
+    // Parse a piecewise function: { value : condition ; value : condition ; value : otherwise }
+    // which is equivalent to the nested conditionals if(condition, value, if(condition, value, value)).
+    // Without the otherwise piece, the function is undefined where none of the conditions holds
+    fn parse_piecewise(&mut self) -> Result<(), &'static str> {
+        self.next_token();
+        let conditional = match self.table.lookup_function("if") {
+            Some(conditional) => conditional,
+            None => return Err("Piecewise functions are not supported"),
+        };
+
+        let mut pieces = 0;
+        let mut has_otherwise = false;
+        loop {
+            let start = self.operations.len();
+            self.parse_expr(0)?;
+            match self.look_ahead {
+                Token::Colon => self.next_token(),
+                _ => return Err("Missing : in piecewise function"),
+            }
+
+            match self.look_ahead {
+                Token::Otherwise => {
+                    self.next_token();
+                    has_otherwise = true;
+                },
+                _ => {
+                    let value_length = self.operations.len() - start;
+                    self.parse_expr(0)?;
+                    // The conditional wants the condition before the value
+                    self.operations[start..].rotate_left(value_length);
+                    pieces += 1;
+                },
+            }
+
+            match self.look_ahead {
+                // The otherwise piece must be the last one
+                Token::Semicolon if !has_otherwise => self.next_token(),
+                Token::RightBrace => {
+                    self.next_token();
+                    break;
+                },
+                _ => return Err("Missing } at the end of piecewise function"),
+            }
+        }
+
+        if !has_otherwise {
+            self.operations.push(self.table.undefined());
+        }
+        for _ in 0..pieces {
+            self.operations.push(conditional.operation());
+        }
+        Ok(())
+    }
+
+    // Parse a sum or a product over a range of integers: sum(index, first, last, term),
+    // where the term is an expression of the index
+    fn parse_reduction(&mut self, operator: &str, identity: f64) -> Result<(), &'static str> {
+        self.next_token();
+        match self.look_ahead {
+            Token::LeftParen => self.next_token(),
+            _ => return Err("Missing ( after sum or product"),
+        }
+
+        let index = match self.look_ahead {
+            Token::Name(ref index) => index.clone(),
+            _ => return Err("Missing index of sum or product"),
+        };
+        self.next_token();
+        self.expect_comma()?;
+        let first = self.parse_bound()?;
+        self.expect_comma()?;
+        let last = self.parse_bound()?;
+        self.expect_comma()?;
+        let terms = self.terms * (last - first + 1).max(0);
+        if terms > MAX_TERMS {
+            return Err("Too many terms in sum or product");
+        }
+
+        let start = self.operations.len();
+        self.indices.push(index);
+        let enclosing_terms = std::mem::replace(&mut self.terms, terms);
+        let term = self.parse_expr(0);
+        self.terms = enclosing_terms;
+        self.indices.pop();
+        term?;
+        let body = self.operations.split_off(start);
+
+        match self.look_ahead {
+            Token::RightParen => self.next_token(),
+            _ => return Err("Missing )"),
+        }
+
+        // We are sure + and * are binary operators
+        let combine = self.table.lookup_binary(operator).unwrap().semantics;
+        self.operations.push(Operation::Reduction(Reduction {
+            body,
+            first,
+            last,
+            combine,
+            identity: S::Number::from(identity),
+        }));
+        Ok(())
+    }
+
+    // The bounds of sums and products are integers, possibly negative
+    fn parse_bound(&mut self) -> Result<i64, &'static str> {
+        let negative = match self.look_ahead {
+            Token::Operator(ref name) if name == "-" => true,
+            _ => false,
+        };
+        if negative {
+            self.next_token();
+        }
+
+        match self.look_ahead {
+            Token::Number(literal) if literal.exact && literal.value.fract() == 0.0 && literal.value.abs() <= i32::max_value() as f64 => {
+                self.next_token();
+                let bound = literal.value as i64;
+                Ok(if negative { -bound } else { bound })
+            },
+            _ => Err("Bounds of sums and products must be integers"),
+        }
+    }
+
+    fn expect_comma(&mut self) -> Result<(), &'static str> {
+        match self.look_ahead {
+            Token::Comma => {
+                self.next_token();
+                Ok(())
+            },
+            _ => Err("Missing , in sum or product"),
+        }
+    }
+
     fn next_token(&mut self) {
         self.look_ahead = self.tokenizer.next_token();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator_tables::default_operator_table;
+
+    // The tokens of the input, numbers by their value and whether it is exact
+    fn tokens(input: &str) -> Vec<String> {
+        let table = default_operator_table();
+        let mut tokenizer = Tokenizer::new(input, &table);
+        let mut tokens = Vec::new();
+        loop {
+            tokens.push(match tokenizer.next_token() {
+                Token::Number(literal) => format!("{}{}", literal.value, if literal.exact { "" } else { "~" }),
+                Token::Operator(symbol) => symbol,
+                Token::XVar => String::from("x"),
+                Token::Error(_) => String::from("error"),
+                Token::Eof => return tokens,
+                _ => String::from("other"),
+            });
+        }
+    }
+
+    #[test]
+    fn number_literals_are_lexed() {
+        let cases = [(".5", "0.5"), ("12", "12"), ("0.25", "0.25"), ("1e-3", "0.001~"), ("2.5E10", "25000000000"), ("1e+2", "100"),
+                     ("0.1", "0.1~"), ("1_000_000", "1000000"), ("0.000_1", "0.0001~"), ("1.2_5", "1.25"), ("1e1_0", "10000000000")];
+        for (input, token) in cases.iter() {
+            assert_eq!(tokens(input), vec![*token], "wrong token for {}", input);
+        }
+    }
+
+    #[test]
+    fn e_without_exponent_is_the_constant() {
+        assert_eq!(tokens("2e"), vec!["2", "e"]);
+        assert_eq!(tokens("2e-x"), vec!["2", "e", "-", "x"]);
+        assert_eq!(tokens("3e+"), vec!["3", "e", "+"]);
+        let value = parse("2e", &default_operator_table()).unwrap().eval_constant();
+        assert_eq!(value, 2.0 * std::f64::consts::E);
+    }
+
+    #[test]
+    fn misplaced_separators_are_errors() {
+        for input in &["1_", "1__0", "1_.5", "1._5", "1.", "1.e3"] {
+            assert!(tokens(input).contains(&String::from("error")), "{} should not be lexed", input);
+        }
+    }
+
+    #[test]
+    fn long_literals_are_rounded() {
+        // more digits than a f64 holds: the value is correctly rounded and not exact
+        assert_eq!(tokens("0.123456789012345678901234567890"), vec!["0.12345678901234568~"]);
+        assert_eq!(tokens("123456789012345678901234567890"), vec!["123456789012345680000000000000~"]);
+        assert_eq!(tokens("9007199254740993"), vec!["9007199254740992~"]);
+        // significant digits are counted without the trailing zeros
+        assert_eq!(tokens("9007199254740992"), vec!["9007199254740992"]);
+        assert_eq!(tokens("1000000000000000000000000000000e-30"), vec!["1"]);
+        assert_eq!(tokens("0.50000000000000000000000000"), vec!["0.5"]);
+    }
+
+    #[test]
+    fn expressions_without_variables_are_constant() {
+        let table = default_operator_table();
+        for input in &["2+3", "pi * 2", "sin(1) / 3", "sum(k, 1, 3, k)", "if(1 < 2, 3, 4)"] {
+            assert!(matches!(parse(input, &table).unwrap().expr_type(), ExprType::Constant), "{} should be constant", input);
+        }
+        for input in &["x+1", "sum(k, 1, 3, k*x)", "if(x < 2, 3, 4)"] {
+            assert!(matches!(parse(input, &table).unwrap().expr_type(), ExprType::Expr2d), "{} should be a function of x", input);
+        }
+        assert!(matches!(parse("x*y", &table).unwrap().expr_type(), ExprType::Expr3d));
+        assert!(matches!(parse("2 = 3", &table).unwrap().expr_type(), ExprType::ExprImplicit));
+    }
+
+    #[test]
+    fn vector_fields_are_recognized() {
+        let (p, q) = parse_vector_field("(-y, x)", &default_operator_table()).unwrap().unwrap();
+        assert_eq!((p.eval_3d(2.0, 3.0), q.eval_3d(2.0, 3.0)), (-3.0, 2.0));
+
+        // without a comma it is an expression in parentheses
+        for input in &["(x + y)", "x, y", "(x + y) * 2"] {
+            assert!(parse_vector_field(input, &default_operator_table()).is_none(), "{} is not a vector field", input);
+        }
+        for input in &["(x, y", "(x, y) + 1", "(x, )", "(x, y, 1)"] {
+            assert!(parse_vector_field(input, &default_operator_table()).unwrap().is_err(), "{} should be an error", input);
+        }
+    }
+
+    #[test]
+    fn slope_fields_are_recognized() {
+        for input in &["dy/dx = x*y", "y' = x*y", "  dy/dx=x*y"] {
+            let slope = parse_slope_field(input, &default_operator_table()).unwrap().unwrap();
+            assert_eq!(slope.eval_3d(2.0, 3.0), 6.0, "wrong slope for {}", input);
+        }
+
+        for input in &["y = x*y", "dy/dx == x", "dy/dx"] {
+            assert!(parse_slope_field(input, &default_operator_table()).is_none(), "{} is not a slope field", input);
+        }
+        for input in &["dy/dx = ", "y' = x = y", "dy/dx = (x"] {
+            assert!(parse_slope_field(input, &default_operator_table()).unwrap().is_err(), "{} should be an error", input);
+        }
+    }
+}
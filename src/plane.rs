@@ -0,0 +1,143 @@
+// The view of the plotters coloring every point of the plane, and the grid of points they evaluate the function on
+
+pub struct Camera {
+    pub position: (f32, f32),
+    pub size: (f32, f32)
+}
+
+impl Camera {
+    // Centered in the origin and 10 units wide, with the aspect ratio of the screen
+    pub fn new(screen_size: (usize, usize)) -> Camera {
+        let start_x_range = 10.0;
+        let size: (f32, f32) = (start_x_range, start_x_range * screen_size.1 as f32 / screen_size.0 as f32);
+        Camera { position: (0.0, 0.0), size }
+    }
+
+    pub fn zoom(&mut self, delta: f32) {
+        self.size.0 *= 1.03f32.powf(delta);
+        self.size.1 *= 1.03f32.powf(delta);
+    }
+
+    // move by a number of pixels
+    pub fn translate(&mut self, delta_x: f32, delta_y: f32, screen_size: (usize, usize)) {
+        self.position.0 += delta_x * self.size.0 / screen_size.0 as f32;
+        self.position.1 += delta_y * self.size.1 / screen_size.1 as f32;
+    }
+
+    // project a point to normalized coordinates [-1,1]
+    pub fn to_normalized_coordinates(&self, point: (f32, f32)) -> (f32, f32) {
+        let x_proj = 2.0*(point.0 - self.position.0)/self.size.0;
+        let y_proj = 2.0*(point.1 - self.position.1)/self.size.1;
+        (x_proj, y_proj)
+    }
+
+    // map a point in screen coordinates (origin at the top left corner) to world coordinates
+    pub fn to_world_coordinates(&self, screen_position: (f64, f64), screen_size: (usize, usize)) -> (f32, f32) {
+        let x = self.position.0 + (screen_position.0 as f32 / screen_size.0 as f32 - 0.5) * self.size.0;
+        let y = self.position.1 + (0.5 - screen_position.1 as f32 / screen_size.1 as f32) * self.size.1;
+        (x, y)
+    }
+}
+
+// The points covering the view cell_size pixels apart, from the bottom left corner to the top right one
+pub struct Grid {
+    pub columns: usize,
+    pub rows: usize,
+    start: (f32, f32),
+    step: (f32, f32),
+}
+
+impl Grid {
+    pub fn new(camera: &Camera, screen_size: (usize, usize), cell_size: usize) -> Grid {
+        let columns = screen_size.0 / cell_size + 1;
+        let rows = screen_size.1 / cell_size + 1;
+        Grid {
+            columns,
+            rows,
+            start: (camera.position.0 - camera.size.0 / 2.0, camera.position.1 - camera.size.1 / 2.0),
+            step: (camera.size.0 / (columns - 1) as f32, camera.size.1 / (rows - 1) as f32),
+        }
+    }
+
+    fn point(&self, i: usize, j: usize) -> (f32, f32) {
+        (self.start.0 + i as f32 * self.step.0, self.start.1 + j as f32 * self.step.1)
+    }
+
+    // The coordinates of the points, a column after the other
+    pub fn points(&self) -> (Vec<f64>, Vec<f64>) {
+        let mut xs = Vec::with_capacity(self.columns * self.rows);
+        let mut ys = Vec::with_capacity(self.columns * self.rows);
+        for i in 0..self.columns {
+            for j in 0..self.rows {
+                let (x, y) = self.point(i, j);
+                xs.push(x as f64);
+                ys.push(y as f64);
+            }
+        }
+        (xs, ys)
+    }
+
+    // Color the squares between the points, given the colors of the points in the order of points().
+    // Returns the positions of the triangles in normalized coordinates and the colors of their vertices,
+    // which are interpolated in between
+    pub fn triangles(&self, camera: &Camera, colors: &[(f32, f32, f32)]) -> (Vec<f32>, Vec<f32>) {
+        let mut positions: Vec<f32> = Vec::with_capacity((self.columns - 1) * (self.rows - 1) * 2 * 3 * 3);
+        let mut vertex_colors: Vec<f32> = Vec::with_capacity(positions.capacity());
+        let mut add_vertex = |i: usize, j: usize| {
+            let (x, y) = camera.to_normalized_coordinates(self.point(i, j));
+            positions.extend_from_slice(&[x, y, 0.0]);
+            let color = colors[i * self.rows + j];
+            vertex_colors.extend_from_slice(&[color.0, color.1, color.2]);
+        };
+
+        for i in 0..self.columns-1 {
+            for j in 0..self.rows-1 {
+                add_vertex(i, j);
+                add_vertex(i + 1, j + 1);
+                add_vertex(i, j + 1);
+
+                add_vertex(i, j);
+                add_vertex(i + 1, j);
+                add_vertex(i + 1, j + 1);
+            }
+        }
+
+        (positions, vertex_colors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_grid_covers_the_view() {
+        let screen_size = (8, 4);
+        let camera = Camera::new(screen_size);
+        let grid = Grid::new(&camera, screen_size, 4);
+        assert_eq!((grid.columns, grid.rows), (3, 2));
+
+        let (xs, ys) = grid.points();
+        assert_eq!(xs, vec![-5.0, -5.0, 0.0, 0.0, 5.0, 5.0]);
+        assert_eq!(ys, vec![-2.5, 2.5, -2.5, 2.5, -2.5, 2.5]);
+
+        // every point gets the color of its column
+        let colors: Vec<(f32, f32, f32)> = (0..6).map(|point| ((point / 2) as f32, 0.0, 0.0)).collect();
+        let (positions, vertex_colors) = grid.triangles(&camera, &colors);
+        assert_eq!(positions.len(), 2 * 2 * 3 * 3);
+        assert_eq!(&positions[..9], &[-1.0, -1.0, 0.0, 0.0, 1.0, 0.0, -1.0, 1.0, 0.0]);
+        assert_eq!(&vertex_colors[..9], &[0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn the_camera_follows_the_screen() {
+        let screen_size = (200, 100);
+        let mut camera = Camera::new(screen_size);
+        assert_eq!(camera.to_world_coordinates((0.0, 0.0), screen_size), (-5.0, 2.5));
+        assert_eq!(camera.to_normalized_coordinates((5.0, -2.5)), (1.0, -1.0));
+
+        camera.translate(20.0, -10.0, screen_size);
+        assert_eq!(camera.position, (1.0, -0.5));
+        assert_eq!(camera.to_world_coordinates((100.0, 50.0), screen_size), (1.0, -0.5));
+    }
+}
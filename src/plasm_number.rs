@@ -0,0 +1,409 @@
+use std::ops::{Add, Sub, Mul, Div, Neg};
+use honestintervals::IntervalSet;
+use crate::number_literal::{NumberLiteral, next_up, next_down};
+use crate::interval;
+use crate::special;
+
+// The numbers expressions can be evaluated on.
+// Every math function supported by plasm is part of this trait,
+// so that a single operator table can be built for all kinds of numbers.
+pub trait PlasmNumber: Clone + From<f64>
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> + Div<Output = Self> + Neg<Output = Self> {
+
+    fn from_literal(literal: NumberLiteral) -> Self;
+
+    fn pi() -> Self;
+    fn e() -> Self;
+
+    fn pow(self, exponent: Self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn log10(self) -> Self;
+    fn log2(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn asin(self) -> Self;
+    fn acos(self) -> Self;
+    fn atan(self) -> Self;
+    fn abs(self) -> Self;
+    fn signum(self) -> Self;
+    fn sinh(self) -> Self;
+    fn cosh(self) -> Self;
+    fn tanh(self) -> Self;
+    fn floor(self) -> Self;
+    fn ceil(self) -> Self;
+    fn round(self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn gamma(self) -> Self;
+    fn lgamma(self) -> Self;
+    fn erf(self) -> Self;
+    fn erfc(self) -> Self;
+    // Bessel function of the first kind of integer order n
+    fn besselj(n: Self, x: Self) -> Self;
+    fn airy(self) -> Self;
+    fn zeta(self) -> Self;
+    fn lambert_w(self) -> Self;
+
+    // Comparisons and logical operators give 1 for true and 0 for false,
+    // any number other than 0 is true
+    fn less(self, other: Self) -> Self;
+    fn less_equal(self, other: Self) -> Self;
+    fn equal(self, other: Self) -> Self;
+    fn logical_not(self) -> Self;
+    fn logical_and(self, other: Self) -> Self;
+    fn logical_or(self, other: Self) -> Self;
+    // then if the condition is true, otherwise if it is false
+    fn select(condition: Self, then: Self, otherwise: Self) -> Self;
+    // The value where a piecewise function is not defined
+    fn undefined() -> Self;
+}
+
+fn truth(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
+
+impl PlasmNumber for f64 {
+    // floating point numbers are the closest to the literal
+    fn from_literal(literal: NumberLiteral) -> f64 {
+        literal.value
+    }
+
+    fn pi() -> f64 {
+        std::f64::consts::PI
+    }
+
+    fn e() -> f64 {
+        std::f64::consts::E
+    }
+
+    fn pow(self, exponent: f64) -> f64 {
+        f64::powf(self, exponent)
+    }
+
+    fn exp(self) -> f64 {
+        f64::exp(self)
+    }
+
+    fn ln(self) -> f64 {
+        f64::ln(self)
+    }
+
+    fn log10(self) -> f64 {
+        f64::log10(self)
+    }
+
+    fn log2(self) -> f64 {
+        f64::log2(self)
+    }
+
+    fn sqrt(self) -> f64 {
+        f64::sqrt(self)
+    }
+
+    fn sin(self) -> f64 {
+        f64::sin(self)
+    }
+
+    fn cos(self) -> f64 {
+        f64::cos(self)
+    }
+
+    fn tan(self) -> f64 {
+        f64::tan(self)
+    }
+
+    fn asin(self) -> f64 {
+        f64::asin(self)
+    }
+
+    fn acos(self) -> f64 {
+        f64::acos(self)
+    }
+
+    fn atan(self) -> f64 {
+        f64::atan(self)
+    }
+
+    fn abs(self) -> f64 {
+        f64::abs(self)
+    }
+
+    fn signum(self) -> f64 {
+        f64::signum(self)
+    }
+
+    fn sinh(self) -> f64 {
+        f64::sinh(self)
+    }
+
+    fn cosh(self) -> f64 {
+        f64::cosh(self)
+    }
+
+    fn tanh(self) -> f64 {
+        f64::tanh(self)
+    }
+
+    fn floor(self) -> f64 {
+        f64::floor(self)
+    }
+
+    fn ceil(self) -> f64 {
+        f64::ceil(self)
+    }
+
+    fn round(self) -> f64 {
+        f64::round(self)
+    }
+
+    fn min(self, other: f64) -> f64 {
+        f64::min(self, other)
+    }
+
+    fn max(self, other: f64) -> f64 {
+        f64::max(self, other)
+    }
+
+    fn gamma(self) -> f64 {
+        special::gamma(self)
+    }
+
+    fn lgamma(self) -> f64 {
+        special::lgamma(self)
+    }
+
+    fn erf(self) -> f64 {
+        special::erf(self)
+    }
+
+    fn erfc(self) -> f64 {
+        special::erfc(self)
+    }
+
+    fn besselj(n: f64, x: f64) -> f64 {
+        special::besselj(n, x)
+    }
+
+    fn airy(self) -> f64 {
+        special::airy(self).0
+    }
+
+    fn zeta(self) -> f64 {
+        special::zeta(self)
+    }
+
+    fn lambert_w(self) -> f64 {
+        special::lambert_w(self)
+    }
+
+    fn less(self, other: f64) -> f64 {
+        truth(self < other)
+    }
+
+    fn less_equal(self, other: f64) -> f64 {
+        truth(self <= other)
+    }
+
+    fn equal(self, other: f64) -> f64 {
+        truth(self == other)
+    }
+
+    fn logical_not(self) -> f64 {
+        truth(self == 0.0)
+    }
+
+    fn logical_and(self, other: f64) -> f64 {
+        truth(self != 0.0 && other != 0.0)
+    }
+
+    fn logical_or(self, other: f64) -> f64 {
+        truth(self != 0.0 || other != 0.0)
+    }
+
+    fn select(condition: f64, then: f64, otherwise: f64) -> f64 {
+        if condition.is_nan() {
+            condition
+        } else if condition != 0.0 {
+            then
+        } else {
+            otherwise
+        }
+    }
+
+    fn undefined() -> f64 {
+        std::f64::NAN
+    }
+}
+
+impl PlasmNumber for IntervalSet<f64> {
+    // literals which are not exactly representable are enclosed in an interval
+    fn from_literal(literal: NumberLiteral) -> IntervalSet<f64> {
+        let (lower, upper) = literal.enclosure();
+        IntervalSet::new(lower, upper)
+    }
+
+    // The floating point approximations of the constants are within one ulp of the exact value
+    fn pi() -> IntervalSet<f64> {
+        IntervalSet::new(next_down(std::f64::consts::PI), next_up(std::f64::consts::PI))
+    }
+
+    fn e() -> IntervalSet<f64> {
+        IntervalSet::new(next_down(std::f64::consts::E), next_up(std::f64::consts::E))
+    }
+
+    fn pow(self, exponent: IntervalSet<f64>) -> IntervalSet<f64> {
+        interval::pow(self, exponent)
+    }
+
+    fn exp(self) -> IntervalSet<f64> {
+        interval::exp(self)
+    }
+
+    fn ln(self) -> IntervalSet<f64> {
+        interval::ln(self)
+    }
+
+    fn log10(self) -> IntervalSet<f64> {
+        interval::log10(self)
+    }
+
+    fn log2(self) -> IntervalSet<f64> {
+        interval::log2(self)
+    }
+
+    fn sqrt(self) -> IntervalSet<f64> {
+        interval::sqrt(self)
+    }
+
+    fn sin(self) -> IntervalSet<f64> {
+        interval::sin(self)
+    }
+
+    fn cos(self) -> IntervalSet<f64> {
+        interval::cos(self)
+    }
+
+    fn tan(self) -> IntervalSet<f64> {
+        interval::tan(self)
+    }
+
+    fn asin(self) -> IntervalSet<f64> {
+        interval::asin(self)
+    }
+
+    fn acos(self) -> IntervalSet<f64> {
+        interval::acos(self)
+    }
+
+    fn atan(self) -> IntervalSet<f64> {
+        interval::atan(self)
+    }
+
+    fn abs(self) -> IntervalSet<f64> {
+        interval::abs(self)
+    }
+
+    fn signum(self) -> IntervalSet<f64> {
+        interval::signum(self)
+    }
+
+    fn sinh(self) -> IntervalSet<f64> {
+        interval::sinh(self)
+    }
+
+    fn cosh(self) -> IntervalSet<f64> {
+        interval::cosh(self)
+    }
+
+    fn tanh(self) -> IntervalSet<f64> {
+        interval::tanh(self)
+    }
+
+    fn floor(self) -> IntervalSet<f64> {
+        interval::floor(self)
+    }
+
+    fn ceil(self) -> IntervalSet<f64> {
+        interval::ceil(self)
+    }
+
+    fn round(self) -> IntervalSet<f64> {
+        interval::round(self)
+    }
+
+    fn min(self, other: IntervalSet<f64>) -> IntervalSet<f64> {
+        interval::min(self, other)
+    }
+
+    fn max(self, other: IntervalSet<f64>) -> IntervalSet<f64> {
+        interval::max(self, other)
+    }
+
+    fn gamma(self) -> IntervalSet<f64> {
+        interval::gamma(self)
+    }
+
+    fn lgamma(self) -> IntervalSet<f64> {
+        interval::lgamma(self)
+    }
+
+    fn erf(self) -> IntervalSet<f64> {
+        interval::erf(self)
+    }
+
+    fn erfc(self) -> IntervalSet<f64> {
+        interval::erfc(self)
+    }
+
+    fn besselj(n: IntervalSet<f64>, x: IntervalSet<f64>) -> IntervalSet<f64> {
+        interval::besselj(n, x)
+    }
+
+    fn airy(self) -> IntervalSet<f64> {
+        interval::airy(self)
+    }
+
+    fn zeta(self) -> IntervalSet<f64> {
+        interval::zeta(self)
+    }
+
+    fn lambert_w(self) -> IntervalSet<f64> {
+        interval::lambert_w(self)
+    }
+
+    fn less(self, other: IntervalSet<f64>) -> IntervalSet<f64> {
+        interval::less(self, other)
+    }
+
+    fn less_equal(self, other: IntervalSet<f64>) -> IntervalSet<f64> {
+        interval::less_equal(self, other)
+    }
+
+    fn equal(self, other: IntervalSet<f64>) -> IntervalSet<f64> {
+        interval::equal(self, other)
+    }
+
+    fn logical_not(self) -> IntervalSet<f64> {
+        interval::logical_not(self)
+    }
+
+    fn logical_and(self, other: IntervalSet<f64>) -> IntervalSet<f64> {
+        interval::logical_and(self, other)
+    }
+
+    fn logical_or(self, other: IntervalSet<f64>) -> IntervalSet<f64> {
+        interval::logical_or(self, other)
+    }
+
+    fn select(condition: IntervalSet<f64>, then: IntervalSet<f64>, otherwise: IntervalSet<f64>) -> IntervalSet<f64> {
+        interval::select(condition, then, otherwise)
+    }
+
+    // Undefined everywhere, the empty set
+    fn undefined() -> IntervalSet<f64> {
+        interval::empty()
+    }
+}
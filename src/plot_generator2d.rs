@@ -1,7 +1,22 @@
 use crate::expression::Expression;
 use honestintervals::IntervalSet;
-use std::collections::VecDeque;
+use crate::parallel;
+use std::collections::HashMap;
 
+// Number of bisection steps refining the points where an implicit curve crosses the edges of the cells
+const ROOT_REFINEMENT_STEPS: u32 = 8;
+// Shortest segment of an implicit curve relative to its cell, shorter ones are points where the curve touches a corner
+const MIN_SEGMENT_LENGTH: f64 = 0.01;
+
+// Number of times a step of an explicit function is halved around an asymptote or a jump
+const ASYMPTOTE_SUBDIVISIONS: u32 = 20;
+// Number of interval evaluations allowed when splitting a step, enough for a couple of asymptotes or jumps
+const STEP_EVALUATIONS: u32 = 4 * ASYMPTOTE_SUBDIVISIONS;
+
+// Number of interval evaluations after which the implicit curves of a view are not refined further
+pub const MAX_IMPLICIT_EVALUATIONS: u32 = 100_000;
+
+#[derive(Clone)]
 pub struct Rectangle {
     pub x_start: f64,
     pub y_start: f64,
@@ -9,100 +24,808 @@ pub struct Rectangle {
     pub y_end: f64,
 }
 
-pub fn generate_2dplot_implicit(expression: &Expression<IntervalSet<f64>>, display_info: Rectangle, resolution: u32) -> Vec<Rectangle> {
-    let smallest_quadrant = (display_info.x_end - display_info.x_start) / resolution as f64;
-    let max_rectangles = 10000;
-    let mut queue: VecDeque<Rectangle> = VecDeque::new();
-    queue.push_back(display_info);
+// The cells containing an implicit curve, all as small as a pixel unless the budget ran out
+pub struct ImplicitPlot {
+    pub rectangles: Vec<Rectangle>,
+    // true when the budget of evaluations was hit, leaving some cells bigger than a pixel
+    pub budget_exceeded: bool,
+    // the interval evaluations spent out of the budget
+    pub evaluations: u32,
+}
 
-    let has_zero = |quadrant: &Rectangle| {
-        let x_interval = IntervalSet::new(quadrant.x_start, quadrant.x_end);
-        let y_interval = IntervalSet::new(quadrant.y_start, quadrant.y_end);
-        let eval = expression.eval_implicit(x_interval, y_interval);
-        eval.has_zero()
-    };
+// Split the view in a quadtree, keeping the cells where the interval evaluation does not exclude a zero.
+// Cells are refined a depth at a time, all the cells of a depth in parallel, until they are as small as a pixel,
+// so that when the budget of evaluations runs out the curve is equally coarse everywhere,
+// and no cell that may contain it is lost. The cells are in the same order however they are refined
+pub fn generate_2dplot_implicit(expression: &Expression<IntervalSet<f64>>, display_info: Rectangle, resolution: u32, budget: u32) -> ImplicitPlot {
+    let mut refinement = ImplicitRefinement::new(display_info, resolution, budget);
+    let mut allowance = u32::MAX;
+    loop {
+        if let Some(plot) = refinement.advance(expression, &mut allowance) {
+            return plot;
+        }
+    }
+}
 
-    while let Some(rect) = queue.pop_front() {
-        if rect.y_end - rect.y_start < smallest_quadrant || 
-            rect.x_end - rect.x_start < smallest_quadrant ||
-            queue.len() > max_rectangles as usize {
-            break;
+// The quadtree of generate_2dplot_implicit refined a few cells at a time, so that it can be spread over several frames
+pub struct ImplicitRefinement {
+    smallest_quadrant: f64,
+    budget: u32,
+    evaluations: u32,
+    // the cells of the current depth, all of the same size
+    cells: Vec<Rectangle>,
+    // the cells of the current depth which are split within the budget, and those split so far
+    splittable: usize,
+    split: usize,
+    // the quadrants of the split cells which may contain the curve
+    quadrants: Vec<Rectangle>,
+}
+
+impl ImplicitRefinement {
+    pub fn new(display_info: Rectangle, resolution: u32, budget: u32) -> ImplicitRefinement {
+        ImplicitRefinement {
+            smallest_quadrant: (display_info.x_end - display_info.x_start) / resolution as f64,
+            budget,
+            evaluations: 0,
+            cells: vec![display_info],
+            splittable: 0,
+            split: 0,
+            quadrants: Vec::new(),
         }
+    }
 
-        let x_half = (rect.x_start + rect.x_end) / 2.0;
-        let y_half = (rect.y_start + rect.y_end) / 2.0;
+    // Split the cells spending about the allowance of evaluations, which is decreased by those spent,
+    // the plot once it is complete. At least a cell is split every time
+    pub fn advance(&mut self, expression: &Expression<IntervalSet<f64>>, allowance: &mut u32) -> Option<ImplicitPlot> {
+        let has_zero = |quadrant: &Rectangle| {
+            let x_interval = IntervalSet::new(quadrant.x_start, quadrant.x_end);
+            let y_interval = IntervalSet::new(quadrant.y_start, quadrant.y_end);
+            let eval = expression.eval_implicit(x_interval, y_interval);
+            eval.has_zero()
+        };
 
-        let quadrant = Rectangle {
-            x_start: x_half,
-            y_start: y_half,
-            x_end: rect.x_end,
-            y_end: rect.y_end
+        let split = |rect: &Rectangle| {
+            let x_half = (rect.x_start + rect.x_end) / 2.0;
+            let y_half = (rect.y_start + rect.y_end) / 2.0;
+            let quadrants = vec![
+                Rectangle { x_start: x_half, y_start: y_half, x_end: rect.x_end, y_end: rect.y_end },
+                Rectangle { x_start: rect.x_start, y_start: y_half, x_end: x_half, y_end: rect.y_end },
+                Rectangle { x_start: rect.x_start, y_start: rect.y_start, x_end: x_half, y_end: y_half },
+                Rectangle { x_start: x_half, y_start: rect.y_start, x_end: rect.x_end, y_end: y_half },
+            ];
+            quadrants.into_iter().filter(|quadrant| has_zero(quadrant)).collect::<Vec<Rectangle>>()
         };
-        if has_zero(&quadrant) {
-            queue.push_back(quadrant)
-        }
-    
-        let quadrant = Rectangle {
-            x_start: rect.x_start,
-            y_start: y_half,
-            x_end: x_half,
-            y_end: rect.y_end
+
+        loop {
+            if self.split == 0 {
+                let cell = match self.cells.first() {
+                    Some(cell) => cell,
+                    None => return Some(ImplicitPlot { rectangles: Vec::new(), budget_exceeded: false, evaluations: self.evaluations }),
+                };
+                if cell.x_end - cell.x_start <= self.smallest_quadrant && cell.y_end - cell.y_start <= self.smallest_quadrant {
+                    let rectangles = std::mem::take(&mut self.cells);
+                    return Some(ImplicitPlot { rectangles, budget_exceeded: false, evaluations: self.evaluations });
+                }
+
+                // the cells which can't be split within the budget are kept as they are
+                self.splittable = (((self.budget - self.evaluations) / 4) as usize).min(self.cells.len());
+            }
+
+            if self.split < self.splittable {
+                if *allowance == 0 {
+                    return None;
+                }
+                let count = ((*allowance / 4) as usize).max(1).min(self.splittable - self.split);
+                let cells = &self.cells[self.split..self.split + count];
+                self.quadrants.extend(parallel::map(cells, &split).into_iter().flatten());
+                self.split += count;
+                self.evaluations += 4 * count as u32;
+                *allowance = allowance.saturating_sub(4 * count as u32);
+                continue;
+            }
+
+            let kept = self.cells.split_off(self.splittable);
+            self.cells = std::mem::take(&mut self.quadrants);
+            self.split = 0;
+            if !kept.is_empty() {
+                self.cells.extend(kept);
+                let rectangles = std::mem::take(&mut self.cells);
+                return Some(ImplicitPlot { rectangles, budget_exceeded: true, evaluations: self.evaluations });
+            }
+        }
+    }
+}
+
+// A corner of a cell, identified by the bits of its coordinates so that adjacent cells share it
+type Corner = (u64, u64);
+
+// The value of the implicit function in a point, NaN where it is not defined
+fn value_at(expression: &Expression<IntervalSet<f64>>, x: f64, y: f64) -> f64 {
+    let values: Vec<(f64, f64)> = expression.eval_implicit(IntervalSet::singleton(x), IntervalSet::singleton(y)).into();
+    values.first().map_or(f64::NAN, |value| (value.0 + value.1) / 2.0)
+}
+
+// Connect the cells containing an implicit curve in ordered polylines, with marching squares.
+// The curve crosses the edges of the cells where the function changes sign, in a point refined by bisection,
+// and the crossings on the edges shared by adjacent cells are joined. Closed curves start and end in the same point.
+// Returns the polylines and the indices of the cells they don't go through, like those of tangent curves,
+// where the function touches zero without changing sign
+pub fn implicit_polylines(expression: &Expression<IntervalSet<f64>>, cells: &[Rectangle]) -> (Vec<Vec<(f64, f64)>>, Vec<usize>) {
+    let mut tracing = ContourTracing::new(cells.len());
+    let mut allowance = u32::MAX;
+    tracing.advance(expression, cells, &mut allowance);
+    tracing.polylines()
+}
+
+fn corner(x: f64, y: f64) -> Corner {
+    (x.to_bits(), y.to_bits())
+}
+
+// The marching squares of implicit_polylines done a few cells at a time, so that they can be spread over several frames
+pub struct ContourTracing {
+    values: HashMap<Corner, f64>,
+    crossings: HashMap<(Corner, Corner), Option<(f64, f64)>>,
+    segments: Vec<((Corner, Corner), (Corner, Corner))>,
+    crossed_cells: Vec<bool>,
+    // the cells traced so far
+    traced: usize,
+    evaluations: u32,
+}
+
+impl ContourTracing {
+    pub fn new(cells: usize) -> ContourTracing {
+        ContourTracing {
+            values: HashMap::new(),
+            crossings: HashMap::new(),
+            segments: Vec::new(),
+            crossed_cells: vec![false; cells],
+            traced: 0,
+            evaluations: 0,
+        }
+    }
+
+    // Trace the cells spending about the allowance of evaluations, which is decreased by those spent,
+    // true once all the cells are traced. At least a cell is traced every time
+    pub fn advance(&mut self, expression: &Expression<IntervalSet<f64>>, cells: &[Rectangle], allowance: &mut u32) -> bool {
+        while self.traced < cells.len() {
+            if *allowance == 0 {
+                return false;
+            }
+            let evaluations = self.evaluations;
+            self.trace(expression, self.traced, &cells[self.traced]);
+            self.traced += 1;
+            *allowance = allowance.saturating_sub(self.evaluations - evaluations);
+        }
+        true
+    }
+
+    fn value(&mut self, expression: &Expression<IntervalSet<f64>>, x: f64, y: f64) -> f64 {
+        let evaluations = &mut self.evaluations;
+        *self.values.entry(corner(x, y)).or_insert_with(|| {
+            *evaluations += 1;
+            value_at(expression, x, y)
+        })
+    }
+
+    fn trace(&mut self, expression: &Expression<IntervalSet<f64>>, index: usize, cell: &Rectangle) {
+        let corners = [(cell.x_start, cell.y_start), (cell.x_end, cell.y_start), (cell.x_end, cell.y_end), (cell.x_start, cell.y_end)];
+        let corner_values: Vec<f64> = corners.iter().map(|point| self.value(expression, point.0, point.1)).collect();
+
+        // The edges, counterclockwise from the bottom one, which the curve crosses
+        let mut crossed = Vec::with_capacity(4);
+        for i in 0..4 {
+            let (start, end) = (corner_values[i], corner_values[(i + 1) % 4]);
+            if start.is_finite() && end.is_finite() && (start > 0.0) != (end > 0.0) {
+                let (a, b) = (corners[i], corners[(i + 1) % 4]);
+                // Adjacent cells go along their common edge in opposite directions
+                let edge = if corner(a.0, a.1) < corner(b.0, b.1) { (a, b) } else { (b, a) };
+                crossed.push((i, edge));
+            }
+        }
+
+        let edge_key = |edge: ((f64, f64), (f64, f64))| (corner((edge.0).0, (edge.0).1), corner((edge.1).0, (edge.1).1));
+        for (_, edge) in crossed.iter() {
+            let (a, b) = *edge;
+            let evaluations = &mut self.evaluations;
+            self.crossings.entry(edge_key(*edge)).or_insert_with(|| {
+                *evaluations += ROOT_REFINEMENT_STEPS + 1;
+                refine_root(expression, a, b)
+            });
+        }
+
+        let keys: Vec<(Corner, Corner)> = crossed.iter().map(|(_, edge)| edge_key(*edge)).collect();
+        let cell_segments = match crossed.len() {
+            2 => vec![(keys[0], keys[1])],
+            4 => {
+                // A saddle: the center tells which opposite corners are connected
+                let center = self.value(expression, (cell.x_start + cell.x_end) / 2.0, (cell.y_start + cell.y_end) / 2.0);
+                if (center > 0.0) == (corner_values[0] > 0.0) {
+                    vec![(keys[0], keys[1]), (keys[2], keys[3])]
+                } else {
+                    vec![(keys[3], keys[0]), (keys[1], keys[2])]
+                }
+            },
+            _ => Vec::new(),
         };
-        if has_zero(&quadrant) {
-            queue.push_back(quadrant)
-        }
-    
-        let quadrant = Rectangle {
-            x_start: rect.x_start,
-            y_start: rect.y_start,
-            x_end: x_half,
-            y_end: y_half
+
+        // Where the function is zero in a corner, the crossings of the two edges meeting there are the same point:
+        // the segment joins the polylines of the adjacent cells, but the curve may just touch the corner
+        let min_length = (cell.x_end - cell.x_start).max(cell.y_end - cell.y_start) * MIN_SEGMENT_LENGTH;
+        for (start, end) in cell_segments {
+            if let (Some(a), Some(b)) = (self.crossings[&start], self.crossings[&end]) {
+                if (a.0 - b.0).hypot(a.1 - b.1) >= min_length {
+                    self.crossed_cells[index] = true;
+                }
+            }
+            self.segments.push((start, end));
+        }
+    }
+
+    // The polylines of the cells traced and the indices of the cells they don't go through
+    pub fn polylines(&self) -> (Vec<Vec<(f64, f64)>>, Vec<usize>) {
+        let segments = &self.segments;
+
+        // Follow the segments through their common crossings
+        let mut adjacent: HashMap<(Corner, Corner), Vec<usize>> = HashMap::new();
+        for (i, (start, end)) in segments.iter().enumerate() {
+            adjacent.entry(*start).or_default().push(i);
+            adjacent.entry(*end).or_default().push(i);
+        }
+
+        let mut used = vec![false; segments.len()];
+        let walk = |mut crossing: (Corner, Corner), used: &mut Vec<bool>| {
+            let mut path = Vec::new();
+            while let Some(&next) = adjacent[&crossing].iter().find(|segment| !used[**segment]) {
+                used[next] = true;
+                crossing = if segments[next].0 == crossing { segments[next].1 } else { segments[next].0 };
+                path.push(crossing);
+            }
+            path
         };
-        if has_zero(&quadrant) {
-            queue.push_back(quadrant)
-        }
-    
-        let quadrant = Rectangle {
-            x_start: x_half,
-            y_start: rect.y_start,
-            x_end: rect.x_end,
-            y_end: y_half
+
+        let mut polylines = Vec::new();
+        for i in 0..segments.len() {
+            if used[i] {
+                continue;
+            }
+            used[i] = true;
+
+            let (start, end) = segments[i];
+            let forward = walk(end, &mut used);
+            let backward = walk(start, &mut used);
+            let path = backward.into_iter().rev().chain(vec![start, end]).chain(forward);
+
+            let polyline: Vec<(f64, f64)> = path.filter_map(|crossing| self.crossings[&crossing]).collect();
+            if polyline.len() >= 2 {
+                polylines.push(polyline);
+            }
+        }
+
+        let uncrossed = (0..self.crossed_cells.len()).filter(|index| !self.crossed_cells[*index]).collect();
+        (polylines, uncrossed)
+    }
+}
+
+// Join the polylines which end where another one starts, like the pieces of a curve crossing adjacent tiles.
+// Polylines are reversed when needed, closed curves start and end in the same point
+pub fn join_polylines(polylines: Vec<Vec<(f64, f64)>>) -> Vec<Vec<(f64, f64)>> {
+    let key = |point: &(f64, f64)| (point.0.to_bits(), point.1.to_bits());
+    let mut polylines: Vec<Option<Vec<(f64, f64)>>> = polylines.into_iter()
+        .filter(|polyline| !polyline.is_empty())
+        .map(Some)
+        .collect();
+
+    let mut ends: HashMap<Corner, Vec<usize>> = HashMap::new();
+    for (i, polyline) in polylines.iter().enumerate() {
+        let polyline = polyline.as_ref().unwrap();
+        ends.entry(key(&polyline[0])).or_default().push(i);
+        ends.entry(key(polyline.last().unwrap())).or_default().push(i);
+    }
+
+    let mut joined = Vec::new();
+    for i in 0..polylines.len() {
+        let mut polyline = match polylines[i].take() {
+            Some(polyline) => polyline,
+            None => continue,
         };
-        if has_zero(&quadrant) {
-            queue.push_back(quadrant)
+
+        // extend the end, then the start by reversing it, and reverse it back
+        for _ in 0..2 {
+            loop {
+                let end = key(polyline.last().unwrap());
+                let next = ends.get(&end).and_then(|candidates| candidates.iter().find(|j| polylines[**j].is_some()).cloned());
+                match next {
+                    Some(j) => {
+                        let mut other = polylines[j].take().unwrap();
+                        if key(&other[0]) != end {
+                            other.reverse();
+                        }
+                        polyline.extend(other.into_iter().skip(1));
+                    },
+                    None => break,
+                }
+            }
+            polyline.reverse();
         }
+        joined.push(polyline);
     }
-    queue.into()
+    joined
 }
 
-// Given the DisplayInfo, it returns an approximation of the plot
-// consistings as a list of rectangles that should be displayed
-pub fn generate_2dplot(expression: &Expression<IntervalSet<f64>>, display_info: Rectangle, resolution: u32) -> Vec<Rectangle> {
-    let mut rectangles = Vec::new();
+// The point where the function changes sign between a and b, by bisection
+fn refine_root(expression: &Expression<IntervalSet<f64>>, a: (f64, f64), b: (f64, f64)) -> Option<(f64, f64)> {
+    let point = |t: f64| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+    let positive = |t: f64| {
+        let (x, y) = point(t);
+        value_at(expression, x, y) > 0.0
+    };
+
+    let (mut low, mut high) = (0.0, 1.0);
+    let low_positive = positive(low);
+    for _ in 0..ROOT_REFINEMENT_STEPS {
+        let middle = (low + high) / 2.0;
+        if positive(middle) == low_positive {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+
+    let (x, y) = point((low + high) / 2.0);
+    if x.is_finite() && y.is_finite() { Some((x, y)) } else { None }
+}
+
+// A part of a resolution step, with the interval evaluation of the function over it
+struct Step {
+    x_start: f64,
+    x_end: f64,
+    enclosure: Vec<(f64, f64)>,
+}
+
+impl Step {
+    fn new(expression: &Expression<IntervalSet<f64>>, x_start: f64, x_end: f64) -> Step {
+        Step {
+            x_start,
+            x_end,
+            enclosure: expression.eval_2d(IntervalSet::new(x_start, x_end)).into(),
+        }
+    }
+
+    // The function is continuous in the step if its values are a single bounded interval
+    fn is_continuous(&self) -> bool {
+        self.enclosure.len() == 1 && self.enclosure[0].0.is_finite() && self.enclosure[0].1.is_finite()
+    }
+
+    fn is_unbounded(&self) -> bool {
+        self.enclosure.iter().any(|interval| interval.0.is_infinite() || interval.1.is_infinite())
+    }
+}
+
+// Split a step in parts, from left to right, halving the parts where the function is defined but not continuous,
+// as around asymptotes and jumps, until they are continuous or as small as allowed.
+// The budget of evaluations stops functions which are discontinuous almost everywhere
+fn continuous_parts(expression: &Expression<IntervalSet<f64>>, step: Step, depth: u32, budget: &mut u32, parts: &mut Vec<Step>) {
+    if step.enclosure.is_empty() || step.is_continuous() || depth == ASYMPTOTE_SUBDIVISIONS || *budget < 2 {
+        parts.push(step);
+        return;
+    }
+    *budget -= 2;
+
+    let x_half = (step.x_start + step.x_end) / 2.0;
+    continuous_parts(expression, Step::new(expression, step.x_start, x_half), depth + 1, budget, parts);
+    continuous_parts(expression, Step::new(expression, x_half, step.x_end), depth + 1, budget, parts);
+}
+
+// The value of an explicit function in a point, NaN where it is not defined
+fn value_of(expression: &Expression<IntervalSet<f64>>, x: f64) -> f64 {
+    let values: Vec<(f64, f64)> = expression.eval_2d(IntervalSet::singleton(x)).into();
+    values.first().map_or(f64::NAN, |value| (value.0 + value.1) / 2.0)
+}
+
+// The resolution steps from first to last, excluded, split in continuous parts, each with the value at its end,
+// and the evaluations spent
+fn explicit_steps(expression: &Expression<IntervalSet<f64>>, display_info: &Rectangle, resolution: u32, first: u32, last: u32) -> (Vec<(Step, f64)>, u32) {
     let step = (display_info.x_end - display_info.x_start) / resolution as f64;
-    let mut x_0 = display_info.x_start;
-    while x_0 < display_info.x_end {
+    let steps = parallel::map_range((last - first) as usize, |i| {
+        let i = first as usize + i;
+        // consecutive steps share their ends exactly, so that no jump falls between them
+        let x_0 = display_info.x_start + step * i as f64;
+        let x_1 = display_info.x_start + step * (i + 1) as f64;
+        let mut budget = STEP_EVALUATIONS;
+        let mut parts = Vec::new();
+        continuous_parts(expression, Step::new(expression, x_0, x_1), 0, &mut budget, &mut parts);
+        let evaluations = 1 + STEP_EVALUATIONS - budget + parts.len() as u32;
+        let parts: Vec<(Step, f64)> = parts.into_iter().map(|part| {
+            let end = value_of(expression, part.x_end);
+            (part, end)
+        }).collect();
+        (parts, evaluations)
+    });
+    let evaluations: u32 = steps.iter().map(|(_, evaluations)| evaluations).sum();
+    (steps.into_iter().flat_map(|(parts, _)| parts).collect(), evaluations)
+}
+
+// The resolution steps of an explicit function sampled a few at a time, so that they can be spread over several frames.
+// The same samples give both its polylines and its rectangles
+pub struct ExplicitSampling {
+    display_info: Rectangle,
+    resolution: u32,
+    // the value at the start of the view
+    start: f64,
+    // the continuous parts of the steps sampled so far, with the values at their ends
+    parts: Vec<(Step, f64)>,
+    sampled: u32,
+}
 
-        let x_1 = x_0 + step;
-        let x_interval = IntervalSet::new(x_0, x_1);
-        let y_intervals: Vec<(f64, f64)> = expression.eval_2d(x_interval).into();
+impl ExplicitSampling {
+    pub fn new(expression: &Expression<IntervalSet<f64>>, display_info: Rectangle, resolution: u32) -> ExplicitSampling {
+        let start = value_of(expression, display_info.x_start);
+        ExplicitSampling { display_info, resolution, start, parts: Vec::new(), sampled: 0 }
+    }
 
-        for interval in y_intervals {
-            if (interval.0 > display_info.y_end && interval.1 > display_info.y_end) ||
-                (interval.0 < display_info.y_start && interval.1 < display_info.y_start) {
-                    continue;
+    // Sample the steps spending about the allowance of evaluations, which is decreased by those spent,
+    // true once all the steps are sampled. At least a step is sampled every time
+    pub fn advance(&mut self, expression: &Expression<IntervalSet<f64>>, allowance: &mut u32) -> bool {
+        while self.sampled < self.resolution {
+            if *allowance == 0 {
+                return false;
             }
+            // as many steps as the allowance would cover in the worst case
+            let count = (*allowance / STEP_EVALUATIONS).max(1).min(self.resolution - self.sampled);
+            let (parts, evaluations) = explicit_steps(expression, &self.display_info, self.resolution, self.sampled, self.sampled + count);
+            self.parts.extend(parts);
+            self.sampled += count;
+            *allowance = allowance.saturating_sub(evaluations);
+        }
+        true
+    }
 
-            rectangles.push(Rectangle {
-                x_start: x_0,
-                y_start: interval.0.max(display_info.y_start).min(display_info.y_end),
-                x_end: x_1,
-                y_end: interval.1.max(display_info.y_start).min(display_info.y_end)
-            });
+    // The points of the function at the ends of the continuous parts of the steps, joined in polylines.
+    // Two consecutive points are joined only when the interval evaluation between them is a single bounded interval,
+    // so that the curve is not connected across asymptotes and jumps, and gets as close to them as the smallest parts.
+    // The smallest parts where the function is still unbounded are its vertical asymptotes.
+    // Points far above or below the view are brought closer, leaving the visible part of the curve unchanged
+    pub fn polylines(&self) -> ExplicitPlot {
+        let display_info = &self.display_info;
+        let height = display_info.y_end - display_info.y_start;
+        let (y_min, y_max) = (display_info.y_start - height, display_info.y_end + height);
+
+        let mut polylines = Vec::new();
+        let mut asymptotes: Vec<f64> = Vec::new();
+        let mut polyline: Vec<(f64, f64)> = Vec::new();
+        let mut start = self.start;
+        for (part, end) in self.parts.iter() {
+            let end = *end;
+
+            if part.is_continuous() && start.is_finite() && end.is_finite() {
+                if polyline.is_empty() {
+                    polyline.push((part.x_start, start.max(y_min).min(y_max)));
+                }
+                polyline.push((part.x_end, end.max(y_min).min(y_max)));
+            } else {
+                if !polyline.is_empty() {
+                    polylines.push(std::mem::take(&mut polyline));
+                }
+                if !part.is_continuous() && part.is_unbounded() {
+                    let x = (part.x_start + part.x_end) / 2.0;
+                    // the asymptote can be found in consecutive smallest parts
+                    match asymptotes.last() {
+                        Some(last) if x - last <= 2.0 * (part.x_end - part.x_start) => (),
+                        _ => asymptotes.push(x),
+                    }
+                }
+            }
+
+            start = end;
+        }
+        if !polyline.is_empty() {
+            polylines.push(polyline);
+        }
+
+        ExplicitPlot { polylines, asymptotes }
+    }
+
+    // The enclosures of the function over the continuous parts of the steps, clipped to the view.
+    // The smallest parts where it is still unbounded are left out, instead of joining the branches of the asymptote
+    pub fn rectangles(&self) -> Vec<Rectangle> {
+        let display_info = &self.display_info;
+        let mut rectangles = Vec::new();
+        for (part, _) in self.parts.iter() {
+            if part.is_unbounded() {
+                continue;
+            }
+
+            for interval in part.enclosure.iter() {
+                if (interval.0 > display_info.y_end && interval.1 > display_info.y_end) ||
+                    (interval.0 < display_info.y_start && interval.1 < display_info.y_start) {
+                        continue;
+                }
+
+                rectangles.push(Rectangle {
+                    x_start: part.x_start,
+                    y_start: interval.0.max(display_info.y_start).min(display_info.y_end),
+                    x_end: part.x_end,
+                    y_end: interval.1.max(display_info.y_start).min(display_info.y_end)
+                });
+            }
+        }
+
+        rectangles
+    }
+}
+
+// The plot of an explicit function, with the x of its vertical asymptotes
+pub struct ExplicitPlot {
+    pub polylines: Vec<Vec<(f64, f64)>>,
+    pub asymptotes: Vec<f64>,
+}
+
+// The polylines of an explicit function over the view, see ExplicitSampling::polylines
+pub fn generate_2dplot_polylines(expression: &Expression<IntervalSet<f64>>, display_info: &Rectangle, resolution: u32) -> ExplicitPlot {
+    let mut sampling = ExplicitSampling::new(expression, display_info.clone(), resolution);
+    let mut allowance = u32::MAX;
+    sampling.advance(expression, &mut allowance);
+    sampling.polylines()
+}
+
+// Given the DisplayInfo, it returns an approximation of the plot
+// consistings as a list of rectangles that should be displayed, see ExplicitSampling::rectangles
+pub fn generate_2dplot(expression: &Expression<IntervalSet<f64>>, display_info: Rectangle, resolution: u32) -> Vec<Rectangle> {
+    let mut sampling = ExplicitSampling::new(expression, display_info, resolution);
+    let mut allowance = u32::MAX;
+    sampling.advance(expression, &mut allowance);
+    sampling.rectangles()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator_tables::parse_interval;
+
+    fn square(size: f64) -> Rectangle {
+        Rectangle { x_start: -size, y_start: -size, x_end: size, y_end: size }
+    }
+
+    fn polylines(input: &str, view: Rectangle, resolution: u32) -> Vec<Vec<(f64, f64)>> {
+        let expression = parse_interval(input);
+        let plot = generate_2dplot_implicit(&expression, view, resolution, MAX_IMPLICIT_EVALUATIONS);
+        implicit_polylines(&expression, &plot.rectangles).0
+    }
+
+    #[test]
+    fn closed_curves_are_single_polylines() {
+        let polylines = polylines("x^2 + y^2 = 1", square(2.0), 64);
+        assert_eq!(polylines.len(), 1);
+        let polyline = &polylines[0];
+        assert!(polyline.iter().all(|(x, y)| (x.hypot(*y) - 1.0).abs() < 4.0 / 64.0));
+        let length: f64 = polyline.windows(2).map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1)).sum();
+        assert!((length - 2.0 * std::f64::consts::PI).abs() < 0.01, "length {}", length);
+    }
+
+    #[test]
+    fn saddles_keep_the_branches_apart() {
+        // the branches of the hyperbola pass close to each other through the cells around the origin
+        let polylines = polylines("x y = 0.0001", square(1.0), 64);
+        assert_eq!(polylines.len(), 2);
+        for polyline in polylines {
+            assert!(polyline.iter().all(|(x, _)| *x > 0.0) || polyline.iter().all(|(x, _)| *x < 0.0));
+        }
+    }
+
+    #[test]
+    fn crossing_lines_are_split_at_the_saddle() {
+        let polylines = polylines("(x - 0.3) (y - 0.2) = 0", square(1.0), 64);
+        let length: f64 = polylines.iter()
+            .flat_map(|polyline| polyline.windows(2).map(|pair| (pair[1].0 - pair[0].0).hypot(pair[1].1 - pair[0].1)))
+            .sum();
+        assert!((length - 4.0).abs() < 0.1, "length {}", length);
+        for polyline in polylines {
+            assert!(polyline.iter().all(|(x, y)| ((x - 0.3) * (y - 0.2)).abs() < 0.01));
+        }
+    }
+
+    #[test]
+    fn cells_are_refined_to_the_size_of_a_pixel() {
+        let expression = parse_interval("x^2 + y^2 = 1");
+        let plot = generate_2dplot_implicit(&expression, square(2.0), 64, MAX_IMPLICIT_EVALUATIONS);
+        assert!(!plot.budget_exceeded);
+        assert!(plot.evaluations < MAX_IMPLICIT_EVALUATIONS);
+        let pixel = 4.0 / 64.0;
+        assert!(plot.rectangles.iter().all(|cell| cell.x_end - cell.x_start <= pixel && cell.y_end - cell.y_start <= pixel));
+        // no cell far from the circle is kept
+        assert!(plot.rectangles.iter().all(|cell| {
+            let (x, y) = ((cell.x_start + cell.x_end) / 2.0, (cell.y_start + cell.y_end) / 2.0);
+            (x.hypot(y) - 1.0).abs() < 2.0 * pixel
+        }));
+    }
+
+    #[test]
+    fn dense_curves_exceed_the_budget() {
+        let expression = parse_interval("sin(100*x) = sin(100*y)");
+        let plot = generate_2dplot_implicit(&expression, square(10.0), 1920, MAX_IMPLICIT_EVALUATIONS);
+        assert!(plot.budget_exceeded);
+        assert!(plot.evaluations <= MAX_IMPLICIT_EVALUATIONS);
+        // the cells are still equally coarse everywhere, none is lost
+        let sizes: Vec<f64> = plot.rectangles.iter().map(|cell| cell.x_end - cell.x_start).collect();
+        let smallest = sizes.iter().cloned().fold(f64::INFINITY, f64::min);
+        assert!(sizes.iter().all(|size| *size <= 2.0 * smallest));
+
+        // a smaller budget is exceeded by simpler curves
+        let plot = generate_2dplot_implicit(&parse_interval("x^2 + y^2 = 1"), square(2.0), 64, 40);
+        assert!(plot.budget_exceeded && plot.evaluations <= 40);
+    }
+
+    #[test]
+    fn tangent_branches_keep_their_cells() {
+        // the function doesn't change sign across x = 0, below y = 1 no polyline goes through its cells
+        let expression = parse_interval("x^2 * (y - 1) = 0");
+        let cells = generate_2dplot_implicit(&expression, square(2.0), 64, MAX_IMPLICIT_EVALUATIONS).rectangles;
+        let (polylines, uncrossed) = implicit_polylines(&expression, &cells);
+        assert!(polylines.iter().flatten().all(|(x, y)| (y - 1.0).abs() < 0.1 || x.abs() < 0.1));
+        for y in [-1.9, -1.0, 0.0, 0.5].iter() {
+            assert!(uncrossed.iter().map(|index| &cells[*index])
+                        .any(|cell| cell.x_start <= 0.0 && 0.0 <= cell.x_end && cell.y_start <= *y && *y <= cell.y_end),
+                    "no cell of the branch at y = {}", y);
+        }
+    }
+
+    #[test]
+    fn continuous_functions_are_single_polylines() {
+        let plot = generate_2dplot_polylines(&parse_interval("x"), &square(1.0), 100);
+        assert_eq!(plot.polylines.len(), 1);
+        assert!(plot.asymptotes.is_empty());
+        let polyline = &plot.polylines[0];
+        assert_eq!(polyline.len(), 101);
+        assert_eq!(polyline[0], (-1.0, -1.0));
+        assert!(polyline.iter().all(|(x, y)| (x - y).abs() < 1e-12));
+
+        // far outside the view the points are brought closer
+        let plot = generate_2dplot_polylines(&parse_interval("1000*x"), &square(1.0), 100);
+        assert!(plot.polylines[0].iter().all(|(_, y)| y.abs() <= 3.0));
+    }
+
+    #[test]
+    fn steps_are_split_in_continuous_parts() {
+        let expression = parse_interval("floor(x)");
+        let mut budget = STEP_EVALUATIONS;
+        let mut parts = Vec::new();
+        continuous_parts(&expression, Step::new(&expression, 0.5, 1.7), 0, &mut budget, &mut parts);
+        assert_eq!(budget, STEP_EVALUATIONS - 2 * ASYMPTOTE_SUBDIVISIONS);
+        // the parts cover the step from left to right, only the smallest one around the jump is not continuous
+        assert_eq!(parts[0].x_start, 0.5);
+        assert_eq!(parts.last().unwrap().x_end, 1.7);
+        assert!(parts.windows(2).all(|pair| pair[0].x_end == pair[1].x_start));
+        let jumps: Vec<&Step> = parts.iter().filter(|part| !part.is_continuous()).collect();
+        assert_eq!(jumps.len(), 1);
+        assert!(jumps[0].x_start <= 1.0 && 1.0 <= jumps[0].x_end && !jumps[0].is_unbounded());
+
+        // continuous steps are not split
+        let expression = parse_interval("x^2");
+        let mut parts = Vec::new();
+        continuous_parts(&expression, Step::new(&expression, 0.5, 1.7), 0, &mut budget, &mut parts);
+        assert_eq!(parts.len(), 1);
+
+        // without budget the step is left as it is
+        let expression = parse_interval("1/x");
+        let mut budget = 0;
+        let mut parts = Vec::new();
+        continuous_parts(&expression, Step::new(&expression, -0.5, 0.5), 0, &mut budget, &mut parts);
+        assert_eq!(parts.len(), 1);
+    }
+
+    #[test]
+    fn asymptotes_are_found_once() {
+        let plot = generate_2dplot_polylines(&parse_interval("tan(x)"), &square(2.0), 100);
+        let half_pi = std::f64::consts::FRAC_PI_2;
+        assert_eq!(plot.asymptotes.len(), 2, "{:?}", plot.asymptotes);
+        assert!((plot.asymptotes[0] + half_pi).abs() < 1e-6 && (plot.asymptotes[1] - half_pi).abs() < 1e-6);
+        assert_eq!(plot.polylines.len(), 3);
+
+        // the asymptote is on the edge between two steps, it is found in both
+        let plot = generate_2dplot_polylines(&parse_interval("1/x"), &square(1.0), 100);
+        assert_eq!(plot.asymptotes.len(), 1, "{:?}", plot.asymptotes);
+        assert!(plot.asymptotes[0].abs() < 1e-6);
+        assert_eq!(plot.polylines.len(), 2);
+
+        let plot = generate_2dplot_polylines(&parse_interval("log(x)"), &square(1.0), 100);
+        assert_eq!(plot.asymptotes.len(), 1, "{:?}", plot.asymptotes);
+        assert!(plot.asymptotes[0].abs() < 1e-6);
+        assert_eq!(plot.polylines.len(), 1);
+        assert!(plot.polylines[0].iter().all(|(x, _)| *x > 0.0));
+    }
+
+    #[test]
+    fn jumps_split_the_plot_without_asymptotes() {
+        let plot = generate_2dplot_polylines(&parse_interval("floor(x)"), &square(2.0), 100);
+        assert!(plot.asymptotes.is_empty(), "{:?}", plot.asymptotes);
+        assert_eq!(plot.polylines.len(), 4);
+        for (polyline, step) in plot.polylines.iter().zip([-2.0, -1.0, 0.0, 1.0].iter()) {
+            assert!(polyline.iter().all(|(_, y)| y == step), "{:?}", polyline);
+        }
+    }
+
+    #[test]
+    fn polylines_are_joined_at_their_common_ends() {
+        let pieces = vec![
+            vec![(1.0, 0.0), (2.0, 1.0)],
+            vec![(0.0, 0.0), (1.0, 0.0)],
+            // reversed
+            vec![(3.0, 3.0), (2.0, 1.0)],
+            vec![(5.0, 5.0), (6.0, 6.0)],
+        ];
+        let joined = join_polylines(pieces);
+        assert_eq!(joined.len(), 2);
+        assert_eq!(joined[0], vec![(0.0, 0.0), (1.0, 0.0), (2.0, 1.0), (3.0, 3.0)]);
+        assert_eq!(joined[1], vec![(5.0, 5.0), (6.0, 6.0)]);
+    }
+
+    // The corners of the cells, to compare plots
+    fn corners(rectangles: &[Rectangle]) -> Vec<(f64, f64, f64, f64)> {
+        rectangles.iter().map(|rect| (rect.x_start, rect.y_start, rect.x_end, rect.y_end)).collect()
+    }
+
+    #[test]
+    fn plots_generated_a_few_evaluations_at_a_time_are_the_same() {
+        // with the budget running out in the middle of a depth too
+        for &(input, budget) in [("x^2 + y^2 = 4", MAX_IMPLICIT_EVALUATIONS), ("sin(100*x) = sin(100*y)", 2000)].iter() {
+            let expression = parse_interval(input);
+            let whole = generate_2dplot_implicit(&expression, square(10.0), 512, budget);
+            let mut refinement = ImplicitRefinement::new(square(10.0), 512, budget);
+            let mut advances = 1;
+            let plot = loop {
+                if let Some(plot) = refinement.advance(&expression, &mut 50) {
+                    break plot;
+                }
+                advances += 1;
+            };
+            assert!(advances > 10);
+            assert_eq!(corners(&plot.rectangles), corners(&whole.rectangles), "different cells of {}", input);
+            assert_eq!((plot.budget_exceeded, plot.evaluations), (whole.budget_exceeded, whole.evaluations));
+
+            let mut tracing = ContourTracing::new(plot.rectangles.len());
+            while !tracing.advance(&expression, &plot.rectangles, &mut 50) {}
+            assert_eq!(tracing.polylines(), implicit_polylines(&expression, &whole.rectangles));
         }
 
-        x_0 += step;
+        let expression = parse_interval("sin(1/x) + floor(x)");
+        let mut sampling = ExplicitSampling::new(&expression, square(3.0), 512);
+        let mut allowance = 100;
+        assert!(!sampling.advance(&expression, &mut allowance));
+        assert_eq!(allowance, 0);
+        while !sampling.advance(&expression, &mut 100) {}
+        let whole = generate_2dplot_polylines(&expression, &square(3.0), 512);
+        assert_eq!(sampling.polylines().polylines, whole.polylines);
+        assert_eq!(sampling.polylines().asymptotes, whole.asymptotes);
+        assert_eq!(corners(&sampling.rectangles()), corners(&generate_2dplot(&expression, square(3.0), 512)));
     }
 
-    return rectangles
+    // On a single thread rayon goes through the items in order, as the loops without the parallel feature
+    #[cfg(feature = "parallel")]
+    fn serially<R: Send, F: FnOnce() -> R + Send>(f: F) -> R {
+        rayon::ThreadPoolBuilder::new().num_threads(1).build().unwrap().install(f)
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_plots_are_the_serial_ones() {
+        let threads = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+
+        // with the budget running out in the middle of a depth too
+        for &(input, budget) in [("x^2 + y^2 = 4", MAX_IMPLICIT_EVALUATIONS), ("sin(100*x) = sin(100*y)", 2000)].iter() {
+            let expression = parse_interval(input);
+            let parallel = threads.install(|| generate_2dplot_implicit(&expression, square(10.0), 512, budget));
+            let serial = serially(|| generate_2dplot_implicit(&expression, square(10.0), 512, budget));
+            assert_eq!(corners(&parallel.rectangles), corners(&serial.rectangles), "different cells of {}", input);
+            assert_eq!((parallel.budget_exceeded, parallel.evaluations), (serial.budget_exceeded, serial.evaluations));
+        }
+
+        let expression = parse_interval("sin(1/x) + floor(x)");
+        let parallel = threads.install(|| generate_2dplot_polylines(&expression, &square(3.0), 512));
+        let serial = serially(|| generate_2dplot_polylines(&expression, &square(3.0), 512));
+        assert_eq!(parallel.polylines, serial.polylines);
+        assert_eq!(parallel.asymptotes, serial.asymptotes);
+        let parallel = threads.install(|| generate_2dplot(&expression, square(3.0), 512));
+        let serial = serially(|| generate_2dplot(&expression, square(3.0), 512));
+        assert_eq!(corners(&parallel), corners(&serial));
+    }
 }
@@ -0,0 +1,307 @@
+use crate::expression::Expression;
+use crate::tile_cache::{Tile, TileGeneration, TileKey};
+use honestintervals::IntervalSet;
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+// Tiles are generated away from the render loop on desktop, so that heavy plots don't block drawing and input.
+// The plotter asks for the tiles it misses, each request replacing the jobs not started yet,
+// and collects the generated tiles every frame. Moving to new curves cancels the jobs of the old ones,
+// and the tiles of the old curves are dropped. Tiles are generated a number of evaluations at a time,
+// so that cancelling stops the tile being generated too, and on the web a heavy tile is spread over several frames
+
+// A tile of a curve to be generated
+pub struct Job {
+    pub curve: usize,
+    pub key: TileKey,
+    pub expression: Arc<Expression<IntervalSet<f64>>>,
+    // the evaluations allowed to implicit curves
+    pub budget: u32,
+}
+
+// A tile generated for the curves of the given generation
+pub struct Generated {
+    pub generation: u64,
+    pub curve: usize,
+    pub key: TileKey,
+    pub tile: Tile,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::thread_worker::PlotWorker;
+#[cfg(target_arch = "wasm32")]
+pub use self::frame_worker::PlotWorker;
+
+// The jobs left to a worker, in order, all of the same generation
+struct Queue {
+    generation: u64,
+    jobs: VecDeque<Job>,
+    // the job being generated, with the part of its tile generated so far
+    current: Option<(Job, TileGeneration)>,
+    // the tiles generated since the last request, which the following one may still ask for
+    done: HashSet<(usize, TileKey)>,
+}
+
+impl Queue {
+    fn new() -> Queue {
+        Queue { generation: 0, jobs: VecDeque::new(), current: None, done: HashSet::new() }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.jobs.is_empty() && self.current.is_none()
+    }
+
+    // Only the newest request counts, its jobs replace those not started yet.
+    // The tile being generated is kept only if it is asked for again
+    fn replace(&mut self, generation: u64, jobs: Vec<Job>) {
+        let mut done = std::mem::take(&mut self.done);
+        // the tiles of the previous curves are not those of the new ones
+        if generation != self.generation {
+            done.clear();
+            self.current = None;
+        }
+        let current = self.current.as_ref().map(|(job, _)| (job.curve, job.key));
+        if let Some(current) = current {
+            if !jobs.iter().any(|job| (job.curve, job.key) == current) {
+                self.current = None;
+            }
+        }
+
+        self.generation = generation;
+        self.jobs = jobs.into_iter()
+            .filter(|job| !done.contains(&(job.curve, job.key)) && Some((job.curve, job.key)) != current)
+            .collect();
+    }
+
+    // Go on with the jobs spending about the allowance of evaluations, which is decreased by those spent,
+    // the tile once one is generated. The jobs of a previous generation are dropped
+    fn advance(&mut self, current_generation: u64, allowance: &mut u32) -> Option<Generated> {
+        if self.generation != current_generation {
+            self.jobs.clear();
+            self.current = None;
+        }
+
+        if self.current.is_none() {
+            let job = self.jobs.pop_front()?;
+            let generation = TileGeneration::new(&job.expression, job.key, job.budget);
+            self.current = Some((job, generation));
+        }
+        let (job, generation) = self.current.as_mut().unwrap();
+        let tile = generation.advance(&job.expression, allowance)?;
+
+        let (job, _) = self.current.take().unwrap();
+        self.done.insert((job.curve, job.key));
+        Some(Generated { generation: self.generation, curve: job.curve, key: job.key, tile })
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod thread_worker {
+    use super::{Generated, Job, Queue};
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+
+    // Number of evaluations between the checks for new requests and cancellations
+    const EVALUATIONS_PER_CHECK: u32 = 10_000;
+
+    struct Request {
+        generation: u64,
+        jobs: Vec<Job>,
+    }
+
+    // On desktop the tiles are generated by a thread, which stops when the worker is dropped
+    pub struct PlotWorker {
+        generation: Arc<AtomicU64>,
+        requests: mpsc::Sender<Request>,
+        results: mpsc::Receiver<Generated>,
+    }
+
+    impl PlotWorker {
+        pub fn new() -> PlotWorker {
+            let generation = Arc::new(AtomicU64::new(0));
+            let (requests, request_receiver) = mpsc::channel();
+            let (result_sender, results) = mpsc::channel();
+
+            let current_generation = generation.clone();
+            thread::spawn(move || work(request_receiver, result_sender, current_generation));
+
+            PlotWorker { generation, requests, results }
+        }
+
+        // Cancel the jobs of the current curves, discarding the tile being generated
+        pub fn next_generation(&mut self) {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+        }
+
+        // Replace the jobs not started yet, they are done in order
+        pub fn request(&mut self, jobs: Vec<Job>) {
+            let generation = self.generation.load(Ordering::SeqCst);
+            // the thread only stops when the worker is dropped
+            self.requests.send(Request { generation, jobs }).unwrap();
+        }
+
+        // The tiles of the current curves generated since the last call
+        pub fn poll(&mut self) -> Vec<Generated> {
+            let generation = self.generation.load(Ordering::SeqCst);
+            self.results.try_iter().filter(|generated| generated.generation == generation).collect()
+        }
+    }
+
+    fn work(requests: mpsc::Receiver<Request>, results: mpsc::Sender<Generated>, current_generation: Arc<AtomicU64>) {
+        let mut queue = Queue::new();
+
+        loop {
+            let request = if queue.is_empty() {
+                match requests.recv() {
+                    Ok(request) => Some(request),
+                    Err(_) => return,
+                }
+            } else {
+                None
+            };
+
+            if let Some(request) = requests.try_iter().last().or(request) {
+                queue.replace(request.generation, request.jobs);
+            }
+
+            let mut allowance = EVALUATIONS_PER_CHECK;
+            let generated = match queue.advance(current_generation.load(Ordering::SeqCst), &mut allowance) {
+                Some(generated) => generated,
+                None => continue,
+            };
+            if generated.generation != current_generation.load(Ordering::SeqCst) {
+                continue;
+            }
+            if results.send(generated).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod frame_worker {
+    use super::{Generated, Job, Queue};
+
+    // Number of evaluations spent generating tiles every frame
+    const EVALUATIONS_PER_POLL: u32 = 10_000;
+
+    // Without threads in the web build, the tiles are generated in the render loop, a number of evaluations every frame,
+    // so that heavy tiles take several frames instead of delaying one
+    pub struct PlotWorker {
+        generation: u64,
+        queue: Queue,
+    }
+
+    impl PlotWorker {
+        pub fn new() -> PlotWorker {
+            PlotWorker { generation: 0, queue: Queue::new() }
+        }
+
+        pub fn next_generation(&mut self) {
+            self.generation += 1;
+        }
+
+        pub fn request(&mut self, jobs: Vec<Job>) {
+            self.queue.replace(self.generation, jobs);
+        }
+
+        pub fn poll(&mut self) -> Vec<Generated> {
+            let mut allowance = EVALUATIONS_PER_POLL;
+            let mut generated = Vec::new();
+            while allowance > 0 {
+                match self.queue.advance(self.generation, &mut allowance) {
+                    Some(tile) => generated.push(tile),
+                    None => break,
+                }
+            }
+            generated
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator_tables::parse_interval;
+
+    // The tiles of a curve crossing all of them, a few along the x axis
+    fn jobs(curve: usize, xs: &[i64]) -> Vec<Job> {
+        let expression = Arc::new(parse_interval("x y = 0.01"));
+        xs.iter().map(|x| Job { curve, key: TileKey { level: -2, x: *x, y: 0 }, expression: expression.clone(), budget: 1000 }).collect()
+    }
+
+    fn generated(queue: &mut Queue, generation: u64) -> Vec<(usize, i64)> {
+        std::iter::from_fn(|| queue.advance(generation, &mut 1_000_000)).map(|generated| (generated.curve, generated.key.x)).collect()
+    }
+
+    #[test]
+    fn newer_requests_replace_the_queued_jobs() {
+        let mut queue = Queue::new();
+        queue.replace(0, jobs(0, &[0, 1, 2]));
+        assert_eq!(queue.advance(0, &mut 1_000_000).unwrap().key.x, 0);
+
+        // the tile already generated is not generated again
+        queue.replace(0, jobs(0, &[0, 5]).into_iter().chain(jobs(1, &[0])).collect());
+        assert_eq!(generated(&mut queue, 0), vec![(0, 5), (1, 0)]);
+    }
+
+    #[test]
+    fn jobs_of_previous_generations_are_dropped() {
+        let mut queue = Queue::new();
+        queue.replace(1, jobs(0, &[0, 1, 2]));
+        assert!(queue.advance(2, &mut 1_000_000).is_none());
+        assert!(queue.is_empty());
+
+        // the same tile of new curves is generated again
+        queue.replace(2, jobs(0, &[0]));
+        assert!(queue.advance(2, &mut 1_000_000).is_some());
+        queue.replace(3, jobs(0, &[0]));
+        assert_eq!(generated(&mut queue, 3), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn tiles_are_generated_a_few_evaluations_at_a_time() {
+        let mut queue = Queue::new();
+        queue.replace(0, jobs(0, &[0, 1]));
+        let mut allowance = 10;
+        assert!(queue.advance(0, &mut allowance).is_none());
+        assert_eq!(allowance, 0);
+
+        // asked for again, the tile being generated goes on first
+        queue.replace(0, jobs(0, &[1, 0]));
+        let mut advances = 1;
+        let generated_tile = loop {
+            if let Some(generated) = queue.advance(0, &mut 10) {
+                break generated;
+            }
+            advances += 1;
+        };
+        assert!(advances > 10);
+        assert_eq!(generated_tile.key.x, 0);
+        let job = &jobs(0, &[0])[0];
+        assert_eq!(generated_tile.tile.polylines, Tile::generate(&job.expression, job.key, job.budget).polylines);
+
+        // not asked for again, it is dropped
+        assert!(queue.advance(0, &mut 10).is_none());
+        queue.replace(0, jobs(0, &[2]));
+        assert_eq!(generated(&mut queue, 0), vec![(0, 2)]);
+    }
+
+    #[test]
+    fn tiles_of_previous_generations_are_dropped() {
+        let mut queue = Queue::new();
+        queue.replace(0, jobs(0, &[0, 1]));
+        assert!(queue.advance(0, &mut 10).is_none());
+
+        // the tile being generated when the curves change is dropped with the jobs left
+        assert!(queue.advance(1, &mut 1_000_000).is_none());
+        assert!(queue.is_empty());
+
+        // the tiles of the new curves belong to their generation
+        queue.replace(1, jobs(1, &[6]));
+        let generated = queue.advance(1, &mut 1_000_000).unwrap();
+        assert_eq!((generated.generation, generated.curve, generated.key.x), (1, 1, 6));
+    }
+}
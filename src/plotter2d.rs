@@ -4,49 +4,502 @@ use three_d::Program;
 use crate::plotter;
 use crate::plotter::Plotter;
 use crate::plot_generator2d;
+use crate::analysis;
+use crate::dual::Dual;
+use crate::field::Field;
+use crate::ode;
+use crate::stroke;
+use crate::tile_cache::{TileCache, TileKey};
+use crate::plot_worker::{Job, PlotWorker};
+use crate::glsl;
 use honestintervals::IntervalSet;
+use log::info;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
 
 const LINE_WIDTH: f32 = 0.008;
+const ASYMPTOTE_WIDTH: f32 = 0.004;
+const MARKER_SIZE: f32 = 0.02;
+
+// Number of subintervals used to compute the enclosure of an integral
+const INTEGRATION_SUBDIVISIONS: u32 = 2000;
+
+// Maximum distance in pixels between the cursor and a point of interest to select it
+const SELECTION_RADIUS: f32 = 10.0;
+
+// Number of arrows of a field along the x axis
+const FIELD_ARROWS: usize = 25;
+// Length of the arrows relative to the distance between them
+const ARROW_LENGTH: f64 = 0.8;
+// Length of the arrow heads relative to the arrows
+const ARROW_HEAD_LENGTH: f64 = 0.25;
+
+// Maximum error and maximum length of the steps of trajectories, relative to the width of the view
+const TRAJECTORY_TOLERANCE: f64 = 1e-5;
+const TRAJECTORY_MAX_STEP: f64 = 0.005;
+
+// Number of frames the view has to stay still before the points of interest and the trajectories are computed again,
+// while panning and zooming the previous ones are only moved with it
+const SETTLE_FRAMES: u32 = 10;
+
+// Curves drawn as lines through their points, in world coordinates
+type Polylines = Vec<Vec<(f64, f64)>>;
+
+// A function to be plotted, along with its derivative, if available, used to analyse it
+pub struct Curve {
+    // shared with the worker generating its tiles
+    pub expression: Arc<Expression<IntervalSet<f64>>>,
+    pub derivative: Option<Expression<Dual>>,
+    pub style: stroke::LineStyle,
+    // whether the vertical asymptotes are drawn
+    pub asymptotes: bool,
+    // the function in GLSL, when it is asked to be evaluated by a shader and can be
+    pub shader: Option<String>,
+}
+
+impl Curve {
+    // Implicit curves written like gpu(x^2 + y^2 = 1) and drawn with solid lines are drawn by a shader on every pixel,
+    // when they can. Their tiles are still generated, to trace and analyse them
+    fn drawn_by_shader(&self) -> bool {
+        let implicit = match self.expression.expr_type() {
+            ExprType::ExprImplicit => true,
+            _ => false,
+        };
+        let solid = match self.style {
+            stroke::LineStyle::Solid => true,
+            _ => false,
+        };
+        implicit && solid && self.shader.is_some()
+    }
+}
+
+// The area under a curve between two x values, x_end is None while it is being selected
+pub struct ShadedArea {
+    curve: usize,
+    pub x_start: f64,
+    pub x_end: Option<f64>,
+    pub integral: Option<(f64, f64)>,
+}
+
+impl fmt::Display for ShadedArea {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.x_end, self.integral) {
+            (None, _) => write!(f, "integrating from x = {:.6}, right click to select the end", self.x_start),
+            (Some(x_end), Some((lower, upper))) => write!(f, "integral from {:.6} to {:.6} = {:.6} (enclosed in [{}, {}])",
+                                                          self.x_start, x_end, (lower + upper) / 2.0, lower, upper),
+            (Some(x_end), None) => write!(f, "integral from {:.6} to {:.6} is not defined", self.x_start, x_end),
+        }
+    }
+}
+
+// A trajectory of the field through the point chosen by the user
+struct Trajectory {
+    start: (f64, f64),
+    points: Vec<(f64, f64)>,
+}
 
 pub struct Plotter2d {
     plot: Plot,
+    gl: Gl,
     program: Program,
-    expression: Expression<IntervalSet<f64>>,
+    line_program: Program,
+    curves: Vec<Curve>,
+    // the programs drawing the curves drawn by shaders
+    curve_programs: Vec<Option<Program>>,
+    // a square covering the screen, where the shaders draw the curves
+    screen_buffer: VertexBuffer,
+    // the tiles of the plot of every curve
+    tiles: Vec<TileCache>,
+    // true while some tiles of the view are missing and drawn from other levels
+    refining: bool,
+    worker: PlotWorker,
+    // the tiles last asked to the worker, by curve
+    requested: Vec<(usize, TileKey)>,
+    field: Option<Field>,
+    trajectories: Vec<Trajectory>,
     camera: Camera,
-    screen_size: (usize, usize)
+    screen_size: (usize, usize),
+    trace_point: Option<(f64, f64)>,
+    points_of_interest: Vec<analysis::PointOfInterest>,
+    // the points of interest in the columns of explicit tiles of the current level, found once for every column
+    column_points: HashMap<TileKey, Vec<Vec<analysis::PointOfInterest>>>,
+    shaded_area: Option<ShadedArea>,
+    // the frames left before the view counts as settled
+    unsettled_frames: u32,
 }
 
 impl Plotter2d {
-    pub fn new(gl: &Gl, expression: Expression<IntervalSet<f64>>, screen_size: (usize, usize)) -> Plotter2d {
+    pub fn new(gl: &Gl, curves: Vec<Curve>, screen_size: (usize, usize)) -> Plotter2d {
 
         let program = Program::from_source(gl,
             include_str!("../assets/shaders/color.vert"),
             include_str!("../assets/shaders/color.frag")).unwrap();
-                
+        let line_program = Program::from_source(gl,
+            include_str!("../assets/shaders/line.vert"),
+            include_str!("../assets/shaders/line.frag")).unwrap();
+
         let start_x_range = 10.0;
         let camera_size: (f32, f32) = (start_x_range, start_x_range * screen_size.1 as f32 / screen_size.0 as f32);
         let camera = Camera {position: (0.0, 0.0), size: camera_size };
-        let plot = Plot::new(gl, &expression, screen_size.0 as u32, &camera);
+        let plot = Plot::new(gl, &camera);
+        let tiles = curves.iter().map(|_| TileCache::new()).collect();
+        let screen_buffer = VertexBuffer::new_with_static_f32(gl, &[-1.0, -1.0, 0.0, 1.0, 1.0, 0.0, -1.0, 1.0, 0.0,
+                                                                  -1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0, 1.0, 0.0]).unwrap();
 
-        Plotter2d {
+        let mut plotter = Plotter2d {
+            gl: gl.clone(),
             plot,
             program,
-            expression,
+            line_program,
+            curves,
+            curve_programs: Vec::new(),
+            screen_buffer,
+            tiles,
+            refining: false,
+            worker: PlotWorker::new(),
+            requested: Vec::new(),
+            field: None,
+            trajectories: Vec::new(),
             camera,
-            screen_size
-        }
+            screen_size,
+            trace_point: None,
+            points_of_interest: Vec::new(),
+            column_points: HashMap::new(),
+            shaded_area: None,
+            unsettled_frames: 0,
+        };
+        plotter.compile_curve_programs();
+        plotter.update_view();
+
+        plotter
     }
 
-    pub fn set_expression(&mut self, expression: Expression<IntervalSet<f64>>) {
-        self.expression = expression;
+    // The tiles of the new curves start from scratch, those of the old ones still being generated are cancelled
+    pub fn set_curves(&mut self, curves: Vec<Curve>) {
+        self.worker.next_generation();
+        self.requested.clear();
+        self.tiles = curves.iter().map(|_| TileCache::new()).collect();
+        self.curves = curves;
+        self.compile_curve_programs();
+        self.trace_point = None;
+        self.shaded_area = None;
+        self.column_points.clear();
+        self.plot.invalidate();
         self.update_view();
     }
+
+    // The curves whose shader does not compile are drawn from their tiles
+    fn compile_curve_programs(&mut self) {
+        let gl = &self.gl;
+        self.curve_programs = self.curves.iter_mut()
+            .map(|curve| {
+                if !curve.drawn_by_shader() {
+                    return None;
+                }
+                let fragment_shader = glsl::program_source(include_str!("../assets/shaders/implicit.frag"), &[curve.shader.as_ref().unwrap()]);
+                match Program::from_source(gl, include_str!("../assets/shaders/plane.vert"), &fragment_shader) {
+                    Ok(program) => Some(program),
+                    Err(err) => {
+                        info!("Could not compile the shader of the curve, it is drawn from its tiles: {:?}", err);
+                        curve.shader = None;
+                        None
+                    }
+                }
+            })
+            .collect();
+    }
+
+    // True while some tiles of the view are still being generated or the view is settling, call refine in the following frames
+    pub fn is_refining(&self) -> bool {
+        self.refining || self.unsettled_frames > 0
+    }
+
+    // Draw the tiles generated since the last call, returns whether there were any.
+    // Once the view settles, its points of interest and trajectories are computed
+    pub fn refine(&mut self) -> bool {
+        if self.unsettled_frames > 0 {
+            self.unsettled_frames -= 1;
+            if self.unsettled_frames == 0 {
+                self.update_trajectories();
+                self.update_points_of_interest();
+            }
+        }
+
+        let mut received = false;
+        for generated in self.worker.poll() {
+            self.tiles[generated.curve].insert(generated.key, generated.tile);
+            received = true;
+        }
+        if received {
+            self.update_plot();
+        }
+        received
+    }
+
+    // Ask the worker for the missing tiles of the view, coarse previews first, and rebuild the curves
+    // when the tiles drawing them change. Panning inside the same tiles only moves the curves already built
+    fn update_plot(&mut self) {
+        let view = self.camera.view();
+        let resolution = self.screen_size.0 as u32;
+
+        let mut refining = false;
+        let mut tiles = Vec::with_capacity(self.curves.len());
+        let mut previews = Vec::new();
+        let mut missing = Vec::new();
+        for (i, (curve, cache)) in self.curves.iter().zip(self.tiles.iter_mut()).enumerate() {
+            let coverage = cache.cover(&curve.expression, &view, resolution);
+            refining |= !coverage.complete;
+            tiles.push(coverage.tiles);
+            previews.extend(coverage.previews.into_iter().map(|key| (i, key)));
+            missing.extend(coverage.missing.into_iter().map(|key| (i, key)));
+        }
+        self.refining = refining;
+
+        previews.extend(missing);
+        if previews != self.requested {
+            let jobs = previews.iter()
+                .map(|(curve, key)| Job { curve: *curve, key: *key, expression: self.curves[*curve].expression.clone(), budget: key.implicit_budget(&view) })
+                .collect();
+            self.worker.request(jobs);
+            self.requested = previews;
+        }
+
+        if self.plot.needs_rebuild(&tiles, &self.camera) {
+            self.plot.update_curves(&self.curves, &self.tiles, tiles, resolution, &self.camera);
+            self.plot.update_area(self.shaded_area.as_ref(), &self.camera);
+        }
+    }
+
+    // True when some implicit curve was too complex to be refined to the size of a pixel
+    // in the current view, so that it is drawn coarser
+    pub fn budget_exceeded(&self) -> bool {
+        self.plot.budget_exceeded
+    }
+
+    // The vector or slope field drawn below the curves.
+    // The trajectories are kept when the field changes, they go through the same points of the new field
+    pub fn set_field(&mut self, field: Option<Field>) {
+        self.field = field;
+        if self.field.is_none() {
+            self.trajectories.clear();
+        }
+        self.plot.update_field(self.field.as_ref(), &self.camera);
+        self.update_trajectories();
+    }
+
+    // Start a trajectory of the field from the point under the cursor and return the point
+    pub fn add_trajectory(&mut self, screen_position: (f64, f64)) -> Option<(f64, f64)> {
+        self.field.as_ref()?;
+        let (x, y) = self.camera.to_world_coordinates(screen_position, self.screen_size);
+        let start = (x as f64, y as f64);
+        self.trajectories.push(Trajectory { start, points: Vec::new() });
+        self.update_trajectories();
+        Some(start)
+    }
+
+    // The trajectory whose starting point is under the cursor, if any
+    pub fn trajectory_at(&self, screen_position: (f64, f64)) -> Option<usize> {
+        let distance = |trajectory: &Trajectory| {
+            let (x, y) = self.camera.to_screen_coordinates((trajectory.start.0 as f32, trajectory.start.1 as f32), self.screen_size);
+            ((x - screen_position.0 as f32).powi(2) + (y - screen_position.1 as f32).powi(2)).sqrt()
+        };
+
+        self.trajectories.iter().enumerate()
+            .map(|(i, trajectory)| (i, distance(trajectory)))
+            .filter(|(_, distance)| *distance < SELECTION_RADIUS)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(i, _)| i)
+    }
+
+    // Move the starting point of a trajectory under the cursor
+    pub fn move_trajectory(&mut self, index: usize, screen_position: (f64, f64)) {
+        let (x, y) = self.camera.to_world_coordinates(screen_position, self.screen_size);
+        self.trajectories[index].start = (x as f64, y as f64);
+        self.update_trajectories();
+    }
+
+    pub fn remove_trajectory(&mut self, index: usize) {
+        self.trajectories.remove(index);
+        self.update_trajectories();
+    }
+
+    // Integrate the trajectories of the field until they leave the view
+    fn update_trajectories(&mut self) {
+        let width = self.camera.size.0 as f64;
+        let bounds = ode::Bounds {
+            x_start: (self.camera.position.0 - self.camera.size.0 / 2.0) as f64,
+            x_end: (self.camera.position.0 + self.camera.size.0 / 2.0) as f64,
+            y_start: (self.camera.position.1 - self.camera.size.1 / 2.0) as f64,
+            y_end: (self.camera.position.1 + self.camera.size.1 / 2.0) as f64,
+            tolerance: TRAJECTORY_TOLERANCE * width,
+            max_step: TRAJECTORY_MAX_STEP * width,
+        };
+
+        if let Some(field) = &self.field {
+            for trajectory in self.trajectories.iter_mut() {
+                trajectory.points = ode::trajectory(field, trajectory.start, &bounds);
+            }
+        }
+        self.plot.update_trajectories(&self.trajectories, &self.camera);
+    }
+
+    // Snap the point under the cursor to the closest plotted curve and return its coordinates.
+    // For explicit functions this is (x, f(x)), for implicit ones the closest point of the curve.
+    pub fn trace(&mut self, screen_position: (f64, f64)) -> Option<(f64, f64)> {
+        let (x, y) = self.camera.to_world_coordinates(screen_position, self.screen_size);
+        let (x, y) = (x as f64, y as f64);
+
+        let candidates = self.curves.iter().zip(self.plot.rectangles.iter()).zip(self.plot.polylines.iter())
+            .filter_map(|((curve, rectangles), polylines)| {
+                match curve.expression.expr_type() {
+                    ExprType::Expr2d | ExprType::Constant => closest_value(&curve.expression, x, y).map(|value| (x, value)),
+                    ExprType::ExprImplicit if !polylines.is_empty() => Plot::nearest_polyline_point(polylines, (x, y)),
+                    ExprType::ExprImplicit => Plot::nearest_point(rectangles, (x, y)),
+                    ExprType::Expr3d => None,
+                }
+            });
+
+        self.trace_point = candidates.min_by(|a, b| {
+            let dist_a = (a.0 - x).powi(2) + (a.1 - y).powi(2);
+            let dist_b = (b.0 - x).powi(2) + (b.1 - y).powi(2);
+            dist_a.partial_cmp(&dist_b).unwrap()
+        });
+
+        self.plot.trace_marker.update(&self.trace_point.into_iter().collect::<Vec<_>>(), &self.camera);
+        self.trace_point
+    }
+
+    // Select the bounds of the area under the explicit curve closest to the cursor:
+    // the first call sets the start, the second one sets the end and computes the integral
+    pub fn select_integration_bound(&mut self, screen_position: (f64, f64)) -> Option<&ShadedArea> {
+        let (x, y) = self.camera.to_world_coordinates(screen_position, self.screen_size);
+        let (x, y) = (x as f64, y as f64);
+
+        self.shaded_area = match self.shaded_area.take() {
+            Some(ShadedArea { curve, x_start, x_end: None, .. }) => {
+                let integral = analysis::integrate(&self.curves[curve].expression, x_start, x, INTEGRATION_SUBDIVISIONS);
+                Some(ShadedArea { curve, x_start, x_end: Some(x), integral })
+            },
+            _ => {
+                self.curves.iter().enumerate()
+                    .filter_map(|(i, curve)| match curve.expression.expr_type() {
+                        ExprType::Expr2d | ExprType::Constant => closest_value(&curve.expression, x, y).map(|value| (i, (value - y).abs())),
+                        _ => None,
+                    })
+                    .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                    .map(|(curve, _)| ShadedArea { curve, x_start: x, x_end: None, integral: None })
+            }
+        };
+
+        self.plot.update_area(self.shaded_area.as_ref(), &self.camera);
+        self.shaded_area.as_ref()
+    }
+
+    // Returns the point of interest (root, extremum, intersection) under the cursor, if any
+    pub fn point_of_interest_at(&self, screen_position: (f64, f64)) -> Option<&analysis::PointOfInterest> {
+        let distance = |point: &analysis::PointOfInterest| {
+            let (x, y) = self.camera.to_screen_coordinates((point.x() as f32, point.y as f32), self.screen_size);
+            ((x - screen_position.0 as f32).powi(2) + (y - screen_position.1 as f32).powi(2)).sqrt()
+        };
+
+        self.points_of_interest.iter()
+            .map(|point| (point, distance(point)))
+            .filter(|(_, distance)| *distance < SELECTION_RADIUS)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(point, _)| point)
+    }
+
+    // Find roots and extrema of every explicit function and the intersections between them
+    // in the current viewport. They are searched in the columns of the explicit tiles, and only
+    // the columns uncovered by panning are searched again
+    fn update_points_of_interest(&mut self) {
+        let view = self.camera.view();
+        let columns = TileCache::columns(&view, self.screen_size.0 as u32);
+
+        let explicit_curves: Vec<&Curve> = self.curves.iter()
+            .filter(|curve| match curve.expression.expr_type() {
+                ExprType::Expr2d | ExprType::Constant => true,
+                _ => false,
+            })
+            .collect();
+
+        // the columns of other levels are not needed until zooming back
+        let level = columns[0].level;
+        self.column_points.retain(|key, _| key.level == level);
+        for key in columns.iter() {
+            if !self.column_points.contains_key(key) {
+                let column = key.rectangle(true);
+                let points = find_points_of_interest(&explicit_curves, (column.x_start, column.x_end), key.step());
+                self.column_points.insert(*key, points);
+            }
+        }
+
+        let mut points = Vec::new();
+        for search in 0..self.column_points[&columns[0]].len() {
+            let mut found: Vec<analysis::PointOfInterest> = columns.iter()
+                .flat_map(|key| self.column_points[key][search].iter().cloned())
+                .collect();
+            // a point on the common edge of two columns is found in both
+            found.sort_by(|a, b| a.x().partial_cmp(&b.x()).unwrap());
+            found.dedup_by(|next, point| next.kind == point.kind && next.x_start <= point.x_end);
+            points.extend(found);
+        }
+
+        // Keep only visible points
+        points.retain(|point| point.x() >= view.x_start && point.x() <= view.x_end && point.y >= view.y_start && point.y <= view.y_end);
+        self.points_of_interest = points;
+        self.update_points_marker();
+    }
+
+    fn update_points_marker(&mut self) {
+        let markers: Vec<(f64, f64)> = self.points_of_interest.iter().map(|point| (point.x(), point.y)).collect();
+        self.plot.points_marker.update(&markers, &self.camera);
+    }
+}
+
+// The points of interest of the explicit curves in the range, by search: the roots and the extrema of every curve,
+// then the intersections of every pair of curves
+fn find_points_of_interest(curves: &[&Curve], x_range: (f64, f64), tolerance: f64) -> Vec<Vec<analysis::PointOfInterest>> {
+    let mut searches = Vec::new();
+    for (i, curve) in curves.iter().enumerate() {
+        searches.push(analysis::find_roots(&curve.expression, curve.derivative.as_ref(), x_range, tolerance));
+
+        searches.push(match &curve.derivative {
+            Some(derivative) => analysis::find_extrema(&curve.expression, derivative, x_range, tolerance),
+            None => Vec::new(),
+        });
+
+        for other in curves[i+1..].iter() {
+            searches.push(analysis::find_intersections(&curve.expression, curve.derivative.as_ref(),
+                                                       &other.expression, other.derivative.as_ref(),
+                                                       x_range, tolerance));
+        }
+    }
+    searches
+}
+
+// The function could be multivalued, returns its value in x closest to y
+fn closest_value(expression: &Expression<IntervalSet<f64>>, x: f64, y: f64) -> Option<f64> {
+    let y_intervals: Vec<(f64, f64)> = expression.eval_2d(IntervalSet::singleton(x)).into();
+
+    y_intervals.into_iter()
+        .map(|interval| (interval.0 + interval.1) / 2.0)
+        .filter(|value| value.is_finite())
+        .min_by(|a, b| (a - y).abs().partial_cmp(&(b - y).abs()).unwrap())
 }
 
 impl plotter::Plotter for Plotter2d {
 
     fn update_view(&mut self) {
-        self.plot.update_positions(&self.expression, self.screen_size.0 as u32, &self.camera)
+        self.update_plot();
+        self.plot.update_axis(&self.camera);
+        self.plot.trace_marker.update(&self.trace_point.into_iter().collect::<Vec<_>>(), &self.camera);
+        self.plot.update_area(self.shaded_area.as_ref(), &self.camera);
+        self.plot.update_field(self.field.as_ref(), &self.camera);
+        self.plot.update_trajectories(&self.trajectories, &self.camera);
+        self.update_points_marker();
+        self.unsettled_frames = SETTLE_FRAMES;
     }
 
     fn zoom(&mut self, delta: f32) {
@@ -62,94 +515,530 @@ impl plotter::Plotter for Plotter2d {
     }
 
     fn render(&self, gl: &Gl, _renderer: &mut DeferredPipeline) {
+        // the edges of the curves are transparent
+        state::blend(gl, state::BlendType::SrcAlphaOneMinusSrcAlpha);
         Screen::write(gl, 0, 0, self.screen_size.0, self.screen_size.1, Some(&vec4(0.9, 0.9, 0.9, 1.0)), None, &|| {
-            self.plot.draw(&self.program);
+            self.plot.draw(&self.program, &self.line_program, &self.camera);
+
+            let view = self.camera.view();
+            let half_width = LINE_WIDTH * self.screen_size.0 as f32 / 4.0;
+            for program in self.curve_programs.iter().flatten() {
+                program.add_uniform_vec4("view", &vec4(view.x_start as f32, view.y_start as f32, view.x_end as f32, view.y_end as f32)).unwrap();
+                program.add_uniform_vec4("color", &vec4(0.5, 0.3, 0.1, 1.0)).unwrap();
+                program.add_uniform_vec4("width", &vec4(half_width, 0.0, 0.0, 0.0)).unwrap();
+                program.use_attribute_vec3_float(&self.screen_buffer, "position").unwrap();
+                program.draw_arrays(6);
+            }
         }).unwrap();
+        state::blend(gl, state::BlendType::None);
     }
 }
 
+#[derive(Clone, Copy)]
 struct Camera {
     position: (f32, f32),
     size: (f32, f32)
 }
 
-impl Camera { 
+impl Camera {
+    // the visible part of the plane
+    fn view(&self) -> plot_generator2d::Rectangle {
+        plot_generator2d::Rectangle {
+            x_start: (self.position.0 - self.size.0 / 2.0) as f64,
+            x_end: (self.position.0 + self.size.0 / 2.0) as f64,
+            y_start: (self.position.1 - self.size.1 / 2.0) as f64,
+            y_end: (self.position.1 + self.size.1 / 2.0) as f64,
+        }
+    }
+
     // project a point to normalized coordinates [-1,1]
     fn to_normalized_coordinates(&self, point: (f32, f32)) -> (f32, f32) {
         let x_proj = 2.0*(point.0 - self.position.0)/self.size.0;
         let y_proj = 2.0*(point.1 - self.position.1)/self.size.1;
         (x_proj, y_proj)
     }
+
+    // map a point in screen coordinates (origin at the top left corner) to world coordinates
+    fn to_world_coordinates(&self, screen_position: (f64, f64), screen_size: (usize, usize)) -> (f32, f32) {
+        let x = self.position.0 + (screen_position.0 as f32 / screen_size.0 as f32 - 0.5) * self.size.0;
+        let y = self.position.1 + (0.5 - screen_position.1 as f32 / screen_size.1 as f32) * self.size.1;
+        (x, y)
+    }
+
+    // inverse of to_world_coordinates
+    fn to_screen_coordinates(&self, point: (f32, f32), screen_size: (usize, usize)) -> (f32, f32) {
+        let x = ((point.0 - self.position.0) / self.size.0 + 0.5) * screen_size.0 as f32;
+        let y = (0.5 - (point.1 - self.position.1) / self.size.1) * screen_size.1 as f32;
+        (x, y)
+    }
+}
+
+// A set of points drawn as small squares
+struct Markers {
+    buffer: VertexBuffer,
+    buffer_size: u32,
+    color: Vec4,
+}
+
+impl Markers {
+    fn new(gl: &Gl, color: Vec4) -> Markers {
+        let buffer = VertexBuffer::new_with_static_f32(&gl, &[]).unwrap();
+
+        Markers {
+            buffer,
+            buffer_size: 0,
+            color,
+        }
+    }
+
+    fn update(&mut self, markers: &[(f64, f64)], camera: &Camera) {
+        let mut positions: Vec<f32> = Vec::with_capacity(markers.len()*2*3*3);
+
+        for marker in markers {
+            let (x, y) = camera.to_normalized_coordinates((marker.0 as f32, marker.1 as f32));
+            let half_width = MARKER_SIZE / 2.0;
+            let half_height = half_width * camera.size.0 / camera.size.1;
+
+            positions.extend_from_slice(&[x - half_width, y - half_height, 0.0,
+                                        x + half_width, y + half_height, 0.0,
+                                        x - half_width, y + half_height, 0.0,
+
+                                        x - half_width, y - half_height, 0.0,
+                                        x + half_width, y - half_height, 0.0,
+                                        x + half_width, y + half_height, 0.0]);
+        }
+
+        self.buffer.fill_with_static_f32(&positions);
+        self.buffer_size = (positions.len() / 3) as u32;
+    }
+
+    fn draw(&self, program: &Program) {
+        if self.buffer_size > 0 {
+            program.use_attribute_vec3_float(&self.buffer, "position").unwrap();
+            program.add_uniform_vec4("color", &self.color).unwrap();
+            program.draw_arrays(self.buffer_size);
+        }
+    }
 }
 
 struct Plot {
     position_buffer: VertexBuffer,
     position_buffer_size: u32,
+    line_buffer: VertexBuffer,
+    line_edge_buffer: VertexBuffer,
+    line_buffer_size: u32,
+    asymptote_buffer: VertexBuffer,
+    asymptote_edge_buffer: VertexBuffer,
+    asymptote_buffer_size: u32,
     axis_buffer: VertexBuffer,
+    area_buffer: VertexBuffer,
+    area_buffer_size: u32,
+    field_buffer: VertexBuffer,
+    field_buffer_size: u32,
+    trajectory_buffer: VertexBuffer,
+    trajectory_buffer_size: u32,
+    trajectory_marker: Markers,
+    trace_marker: Markers,
+    points_marker: Markers,
+    // the camera the curves were built for, they are moved to the current one when drawn
+    anchor: Camera,
+    // the tiles the curves were built from, None when they must be rebuilt
+    tiles: Option<Vec<Vec<TileKey>>>,
+    // the rectangles of the plot of every curve
+    rectangles: Vec<Vec<plot_generator2d::Rectangle>>,
+    // the polylines drawing every curve
+    polylines: Vec<Polylines>,
+    // the indices of the rectangles of every curve which are drawn as they are, where there are no polylines
+    loose_rectangles: Vec<Vec<usize>>,
+    // true when some implicit curve could not be refined to the size of a pixel
+    budget_exceeded: bool,
 }
 
 impl Plot {
 
-    fn new(gl: &Gl, expression: &Expression<IntervalSet<f64>>, resolution: u32, camera: &Camera) -> Plot {
-        let positions = Plot::generate_positions(expression, resolution, camera);
+    fn new(gl: &Gl, camera: &Camera) -> Plot {
         let axis_points = Plot::generate_axis_lines(camera);
 
-        let position_buffer = VertexBuffer::new_with_static_f32(&gl, &positions).unwrap();
-        let position_buffer_size = (positions.len() / 3) as u32;
+        let position_buffer = VertexBuffer::new_with_static_f32(&gl, &[]).unwrap();
+        let line_buffer = VertexBuffer::new_with_static_f32(gl, &[]).unwrap();
+        let line_edge_buffer = VertexBuffer::new_with_static_f32(gl, &[]).unwrap();
+        let asymptote_buffer = VertexBuffer::new_with_static_f32(gl, &[]).unwrap();
+        let asymptote_edge_buffer = VertexBuffer::new_with_static_f32(gl, &[]).unwrap();
         let axis_buffer = VertexBuffer::new_with_static_f32(&gl, &axis_points).unwrap();
+        let area_buffer = VertexBuffer::new_with_static_f32(&gl, &[]).unwrap();
+        let field_buffer = VertexBuffer::new_with_static_f32(&gl, &[]).unwrap();
+        let trajectory_buffer = VertexBuffer::new_with_static_f32(&gl, &[]).unwrap();
+        let trajectory_marker = Markers::new(gl, vec4(0.1, 0.5, 0.2, 1.0));
+        let trace_marker = Markers::new(gl, vec4(0.8, 0.1, 0.1, 1.0));
+        let points_marker = Markers::new(gl, vec4(0.1, 0.3, 0.8, 1.0));
 
         Plot {
             position_buffer,
-            position_buffer_size,
-            axis_buffer
+            position_buffer_size: 0,
+            line_buffer,
+            line_edge_buffer,
+            line_buffer_size: 0,
+            asymptote_buffer,
+            asymptote_edge_buffer,
+            asymptote_buffer_size: 0,
+            axis_buffer,
+            area_buffer,
+            area_buffer_size: 0,
+            field_buffer,
+            field_buffer_size: 0,
+            trajectory_buffer,
+            trajectory_buffer_size: 0,
+            trajectory_marker,
+            trace_marker,
+            points_marker,
+            anchor: *camera,
+            tiles: None,
+            rectangles: Vec::new(),
+            polylines: Vec::new(),
+            loose_rectangles: Vec::new(),
+            budget_exceeded: false,
         }
     }
 
-    fn update_positions(&mut self, expression: &Expression<IntervalSet<f64>>, resolution: u32, camera: &Camera) {
-        let positions = Plot::generate_positions(expression, resolution, camera);
-        let axis_positions = Plot::generate_axis_lines(camera);
-        
+    fn invalidate(&mut self) {
+        self.tiles = None;
+    }
+
+    // The curves are rebuilt when they are drawn from other tiles, when the view is zoomed,
+    // and when it moved so far that the parts clipped above and below it would show
+    fn needs_rebuild(&self, tiles: &[Vec<TileKey>], camera: &Camera) -> bool {
+        self.tiles.as_deref() != Some(tiles) ||
+            self.anchor.size != camera.size ||
+            (camera.position.0 - self.anchor.position.0).abs() > camera.size.0 / 2.0 ||
+            (camera.position.1 - self.anchor.position.1).abs() > camera.size.1 / 2.0
+    }
+
+    // Build the curves from their tiles, in normalized coordinates of the given camera.
+    // Explicit functions are clipped to the view and to a margin as high as the view above and below it
+    fn update_curves(&mut self, curves: &[Curve], caches: &[TileCache], tiles: Vec<Vec<TileKey>>, resolution: u32, camera: &Camera) {
+        let view = camera.view();
+        let height = view.y_end - view.y_start;
+        let (y_min, y_max) = (view.y_start - height, view.y_end + height);
+
+        self.rectangles.clear();
+        self.polylines.clear();
+        self.loose_rectangles.clear();
+        self.budget_exceeded = false;
+        let mut asymptotes = Vec::new();
+
+        for ((curve, cache), keys) in curves.iter().zip(caches.iter()).zip(tiles.iter()) {
+            let explicit = match curve.expression.expr_type() {
+                ExprType::Expr2d | ExprType::Constant => true,
+                _ => false,
+            };
+
+            let mut rectangles = Vec::new();
+            let mut loose = Vec::new();
+            let mut polylines = Vec::new();
+            for key in keys {
+                let tile = cache.get(key);
+                self.budget_exceeded |= tile.budget_exceeded && !curve.drawn_by_shader();
+                if curve.asymptotes {
+                    asymptotes.extend_from_slice(&tile.asymptotes);
+                }
+
+                let mut is_loose = vec![false; tile.rectangles.len()];
+                for index in tile.loose.iter() {
+                    is_loose[*index] = true;
+                }
+                for (index, rectangle) in tile.rectangles.iter().enumerate() {
+                    if explicit && (rectangle.y_start > y_max || rectangle.y_end < y_min) {
+                        continue;
+                    }
+                    if is_loose[index] {
+                        loose.push(rectangles.len());
+                    }
+                    let mut rectangle = rectangle.clone();
+                    rectangle.y_start = rectangle.y_start.max(y_min);
+                    rectangle.y_end = rectangle.y_end.min(y_max);
+                    rectangles.push(rectangle);
+                }
+
+                if explicit {
+                    polylines.extend(tile.polylines.iter()
+                        .map(|polyline| polyline.iter().map(|point| (point.0, point.1.max(y_min).min(y_max))).collect()));
+                } else {
+                    polylines.extend(tile.polylines.iter().cloned());
+                }
+            }
+
+            self.rectangles.push(rectangles);
+            self.polylines.push(plot_generator2d::join_polylines(polylines));
+            // the curves drawn by shaders are kept to be traced
+            self.loose_rectangles.push(if curve.drawn_by_shader() { Vec::new() } else { loose });
+        }
+
+        // adjacent tiles can find the same asymptote on their common edge
+        let pixel = (view.x_end - view.x_start) / resolution as f64;
+        asymptotes.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        asymptotes.dedup_by(|a, b| (*a - *b).abs() <= 2.0 * pixel);
+
+        let positions = Plot::generate_positions(&self.rectangles, &self.loose_rectangles, camera);
+        let lines = Plot::generate_lines(curves, &self.polylines, resolution, camera);
+        let asymptote_lines = Plot::generate_asymptote_lines(&asymptotes, resolution, camera);
+
         self.position_buffer.fill_with_static_f32(&positions);
         self.position_buffer_size = (positions.len() / 3) as u32;
+        self.line_buffer.fill_with_static_f32(&lines.positions);
+        self.line_edge_buffer.fill_with_static_f32(&lines.edges);
+        self.line_buffer_size = (lines.positions.len() / 3) as u32;
+        self.asymptote_buffer.fill_with_static_f32(&asymptote_lines.positions);
+        self.asymptote_edge_buffer.fill_with_static_f32(&asymptote_lines.edges);
+        self.asymptote_buffer_size = (asymptote_lines.positions.len() / 3) as u32;
+
+        self.anchor = *camera;
+        self.tiles = Some(tiles);
+    }
+
+    fn update_axis(&mut self, camera: &Camera) {
+        let axis_positions = Plot::generate_axis_lines(camera);
         self.axis_buffer.fill_with_static_f32(&axis_positions);
     }
 
-    fn draw(&self, program: &Program) {
+    // Shade the visible part of the area between the curve and the x axis, under the polylines drawing the curve
+    fn update_area(&mut self, area: Option<&ShadedArea>, camera: &Camera) {
+        let mut positions: Vec<f32> = Vec::new();
+
+        if let Some(ShadedArea { curve, x_start, x_end: Some(x_end), .. }) = area {
+            let view = camera.view();
+            let start = x_start.min(*x_end).max(view.x_start);
+            let end = x_start.max(*x_end).min(view.x_end);
+
+            for polyline in self.polylines[*curve].iter() {
+                for segment in polyline.windows(2) {
+                    // joined polylines may go from right to left
+                    let (a, b) = if segment[0].0 <= segment[1].0 { (segment[0], segment[1]) } else { (segment[1], segment[0]) };
+                    if b.0 <= start || a.0 >= end || a.0 == b.0 {
+                        continue;
+                    }
+
+                    // the part of the segment between the bounds
+                    let (x_0, x_1) = (a.0.max(start), b.0.min(end));
+                    let value = |x: f64| (a.1 + (b.1 - a.1) * (x - a.0) / (b.0 - a.0)).max(view.y_start).min(view.y_end);
+                    let (left, bottom) = camera.to_normalized_coordinates((x_0 as f32, 0.0));
+                    let (right, _) = camera.to_normalized_coordinates((x_1 as f32, 0.0));
+                    let (_, left_top) = camera.to_normalized_coordinates((x_0 as f32, value(x_0) as f32));
+                    let (_, right_top) = camera.to_normalized_coordinates((x_1 as f32, value(x_1) as f32));
+
+                    positions.extend_from_slice(&[left, bottom, 0.0,
+                                                right, right_top, 0.0,
+                                                left, left_top, 0.0,
+
+                                                left, bottom, 0.0,
+                                                right, bottom, 0.0,
+                                                right, right_top, 0.0]);
+                }
+            }
+        }
+
+        self.area_buffer.fill_with_static_f32(&positions);
+        self.area_buffer_size = (positions.len() / 3) as u32;
+    }
+
+    // Draw the field as arrows on a grid fixed in the plane, so that they don't move while panning.
+    // The arrows of vector fields are as long as the magnitude of the vectors relative to the biggest visible one,
+    // the ones of slope fields are segments all of the same length
+    fn update_field(&mut self, field: Option<&Field>, camera: &Camera) {
+        let mut positions: Vec<f32> = Vec::new();
+
+        if let Some(field) = field {
+            let spacing = (camera.size.0 / FIELD_ARROWS as f32) as f64;
+            let x_view_start = (camera.position.0 - camera.size.0 / 2.0) as f64;
+            let x_view_end = (camera.position.0 + camera.size.0 / 2.0) as f64;
+            let y_view_start = (camera.position.1 - camera.size.1 / 2.0) as f64;
+            let y_view_end = (camera.position.1 + camera.size.1 / 2.0) as f64;
+
+            let mut arrows = Vec::new();
+            let mut x = (x_view_start / spacing).ceil() * spacing;
+            while x <= x_view_end {
+                let mut y = (y_view_start / spacing).ceil() * spacing;
+                while y <= y_view_end {
+                    let (dx, dy) = field.direction(x, y);
+                    let magnitude = dx.hypot(dy);
+                    if magnitude.is_finite() && magnitude > 0.0 {
+                        arrows.push((x, y, dx / magnitude, dy / magnitude, magnitude));
+                    }
+                    y += spacing;
+                }
+                x += spacing;
+            }
+
+            let max_magnitude = arrows.iter().fold(0.0f64, |max, arrow| max.max(arrow.4));
+            let mut add_segment = |start: (f64, f64), end: (f64, f64)| {
+                let start = camera.to_normalized_coordinates((start.0 as f32, start.1 as f32));
+                let end = camera.to_normalized_coordinates((end.0 as f32, end.1 as f32));
+                positions.extend_from_slice(&[start.0, start.1, 0.0, end.0, end.1, 0.0]);
+            };
+
+            for (x, y, dx, dy, magnitude) in arrows {
+                let length = if field.has_magnitude() {
+                    ARROW_LENGTH * spacing * magnitude / max_magnitude
+                } else {
+                    ARROW_LENGTH * spacing
+                };
+                let (half_x, half_y) = (dx * length / 2.0, dy * length / 2.0);
+                let tip = (x + half_x, y + half_y);
+                add_segment((x - half_x, y - half_y), tip);
+
+                if field.has_magnitude() {
+                    // The head is made of the arrow rotated by 150 degrees both ways
+                    let head = ARROW_HEAD_LENGTH * length;
+                    let (cos, sin) = (-(3.0f64).sqrt() / 2.0, 0.5);
+                    add_segment(tip, (tip.0 + head * (dx * cos - dy * sin), tip.1 + head * (dx * sin + dy * cos)));
+                    add_segment(tip, (tip.0 + head * (dx * cos + dy * sin), tip.1 + head * (-dx * sin + dy * cos)));
+                }
+            }
+        }
+
+        self.field_buffer.fill_with_static_f32(&positions);
+        self.field_buffer_size = (positions.len() / 3) as u32;
+    }
+
+    // Draw the trajectories as polylines, with a marker on their starting points
+    fn update_trajectories(&mut self, trajectories: &[Trajectory], camera: &Camera) {
+        let mut positions: Vec<f32> = Vec::new();
+        for trajectory in trajectories {
+            for segment in trajectory.points.windows(2) {
+                let start = camera.to_normalized_coordinates((segment[0].0 as f32, segment[0].1 as f32));
+                let end = camera.to_normalized_coordinates((segment[1].0 as f32, segment[1].1 as f32));
+                positions.extend_from_slice(&[start.0, start.1, 0.0, end.0, end.1, 0.0]);
+            }
+        }
+
+        self.trajectory_buffer.fill_with_static_f32(&positions);
+        self.trajectory_buffer_size = (positions.len() / 3) as u32;
+        let starts: Vec<(f64, f64)> = trajectories.iter().map(|trajectory| trajectory.start).collect();
+        self.trajectory_marker.update(&starts, camera);
+    }
+
+    // Returns the center of the rectangle closest to the given point
+    fn nearest_point(rectangles: &[plot_generator2d::Rectangle], point: (f64, f64)) -> Option<(f64, f64)> {
+        rectangles.iter()
+            .map(|rect| ((rect.x_start + rect.x_end) / 2.0, (rect.y_start + rect.y_end) / 2.0))
+            .min_by(|a, b| {
+                let dist_a = (a.0 - point.0).powi(2) + (a.1 - point.1).powi(2);
+                let dist_b = (b.0 - point.0).powi(2) + (b.1 - point.1).powi(2);
+                dist_a.partial_cmp(&dist_b).unwrap()
+            })
+    }
+
+    // Returns the vertex of the polylines closest to the given point
+    fn nearest_polyline_point(polylines: &[Vec<(f64, f64)>], point: (f64, f64)) -> Option<(f64, f64)> {
+        polylines.iter().flatten()
+            .min_by(|a, b| {
+                let dist_a = (a.0 - point.0).powi(2) + (a.1 - point.1).powi(2);
+                let dist_b = (b.0 - point.0).powi(2) + (b.1 - point.1).powi(2);
+                dist_a.partial_cmp(&dist_b).unwrap()
+            })
+            .cloned()
+    }
+
+    fn draw(&self, program: &Program, line_program: &Program, camera: &Camera) {
+        // the curves are moved from the camera they were built for to the current one
+        let offset = camera.to_normalized_coordinates(self.anchor.position);
+        let pan = Mat4::from_translation(vec3(offset.0, offset.1, 0.0));
+
         program.add_uniform_mat4("worldViewProjectionMatrix", &Mat4::identity()).unwrap();
 
+        // draw the shaded area below everything else
+        if self.area_buffer_size > 0 {
+            program.use_attribute_vec3_float(&self.area_buffer, "position").unwrap();
+            program.add_uniform_vec4("color", &vec4(0.85, 0.75, 0.55, 1.0)).unwrap();
+            program.draw_arrays(self.area_buffer_size);
+        }
+
+        if self.field_buffer_size > 0 {
+            program.use_attribute_vec3_float(&self.field_buffer, "position").unwrap();
+            program.add_uniform_vec4("color", &vec4(0.4, 0.55, 0.7, 1.0)).unwrap();
+            program.draw_arrays_mode(self.field_buffer_size, consts::LINES);
+        }
+
+        line_program.add_uniform_mat4("worldViewProjectionMatrix", &pan).unwrap();
+        if self.asymptote_buffer_size > 0 {
+            line_program.use_attribute_vec3_float(&self.asymptote_buffer, "position").unwrap();
+            line_program.use_attribute_vec3_float(&self.asymptote_edge_buffer, "edge").unwrap();
+            line_program.add_uniform_vec4("color", &vec4(0.45, 0.45, 0.45, 1.0)).unwrap();
+            line_program.draw_arrays(self.asymptote_buffer_size);
+        }
+
+        program.add_uniform_mat4("worldViewProjectionMatrix", &pan).unwrap();
         program.use_attribute_vec3_float(&self.position_buffer, "position").unwrap();
         program.add_uniform_vec4("color", &vec4(0.5, 0.3, 0.1, 1.0)).unwrap();
         program.draw_arrays(self.position_buffer_size);
+        program.add_uniform_mat4("worldViewProjectionMatrix", &Mat4::identity()).unwrap();
+
+        if self.line_buffer_size > 0 {
+            line_program.use_attribute_vec3_float(&self.line_buffer, "position").unwrap();
+            line_program.use_attribute_vec3_float(&self.line_edge_buffer, "edge").unwrap();
+            line_program.add_uniform_vec4("color", &vec4(0.5, 0.3, 0.1, 1.0)).unwrap();
+            line_program.draw_arrays(self.line_buffer_size);
+        }
+
+        if self.trajectory_buffer_size > 0 {
+            program.use_attribute_vec3_float(&self.trajectory_buffer, "position").unwrap();
+            program.add_uniform_vec4("color", &vec4(0.1, 0.5, 0.2, 1.0)).unwrap();
+            program.draw_arrays_mode(self.trajectory_buffer_size, consts::LINES);
+        }
 
         // draw axis
         program.use_attribute_vec3_float(&self.axis_buffer, "position").unwrap();
         program.add_uniform_vec4("color", &vec4(0.2, 0.2, 0.2, 1.0)).unwrap();
         program.draw_arrays_mode(4, consts::LINES);
+
+        self.points_marker.draw(program);
+        self.trajectory_marker.draw(program);
+        self.trace_marker.draw(program);
     }
 
-    fn generate_positions(expression: &Expression<IntervalSet<f64>>, resolution: u32, camera: &Camera) -> Vec<f32> {
+    // the polylines stroked with the style of their curves
+    fn generate_lines(curves: &[Curve], polylines: &[Polylines], resolution: u32, camera: &Camera) -> stroke::Strokes {
+        // the resolution is the width of the screen in pixels
+        let screen_size = (resolution as f32, resolution as f32 * camera.size.1 / camera.size.0);
+        let width = LINE_WIDTH * screen_size.0 / 2.0;
 
-        let display_info = plot_generator2d::Rectangle {
-            x_start: (camera.position.0 - camera.size.0 / 2.0) as f64,
-            x_end: (camera.position.0 + camera.size.0 / 2.0) as f64,
-            y_start: (camera.position.1 - camera.size.1 / 2.0) as f64,
-            y_end: (camera.position.1 + camera.size.1 / 2.0) as f64,
-        };
+        let mut lines = stroke::Strokes::new(screen_size);
+        for (curve, polylines) in curves.iter().zip(polylines.iter()).filter(|(curve, _)| !curve.drawn_by_shader()) {
+            for polyline in polylines {
+                let points: Vec<(f32, f32)> = polyline.iter()
+                    .map(|point| camera.to_normalized_coordinates((point.0 as f32, point.1 as f32)))
+                    .collect();
+                lines.add(&points, width, &curve.style);
+            }
+        }
+        lines
+    }
 
-        let rectangles = match expression.expr_type() {
-            ExprType::Expr2d => plot_generator2d::generate_2dplot(expression, display_info, resolution),
-            ExprType::ExprImplicit => plot_generator2d::generate_2dplot_implicit(expression, display_info, resolution),
-            ExprType::Expr3d => panic!("expected 2d expression, found 3d expression"),
-        };
+    // the asymptotes are dashed vertical lines across the view and the margins above and below it
+    fn generate_asymptote_lines(asymptotes: &[f64], resolution: u32, camera: &Camera) -> stroke::Strokes {
+        let screen_size = (resolution as f32, resolution as f32 * camera.size.1 / camera.size.0);
+        let width = ASYMPTOTE_WIDTH * screen_size.0 / 2.0;
+
+        let mut lines = stroke::Strokes::new(screen_size);
+        for asymptote in asymptotes {
+            let (x, _) = camera.to_normalized_coordinates((*asymptote as f32, 0.0));
+            lines.add(&[(x, -3.0), (x, 3.0)], width, &stroke::LineStyle::Dashed);
+        }
+        lines
+    }
+
+    // the loose rectangles are drawn widened to the width of the line
+    fn generate_positions(rectangles: &[Vec<plot_generator2d::Rectangle>], loose_rectangles: &[Vec<usize>], camera: &Camera) -> Vec<f32> {
+        let n_rectangles: usize = loose_rectangles.iter().map(|loose| loose.len()).sum();
+        let mut positions: Vec<f32> = Vec::with_capacity(n_rectangles*2*3*3);
 
-        let mut positions: Vec<f32> = Vec::with_capacity(rectangles.len()*2*3*3);
-        
         let mut add_position = |x: f32, y: f32| {
             positions.push(x);
             positions.push(y);
             positions.push(0.0);
         };
 
+        let rectangles = rectangles.iter().zip(loose_rectangles.iter())
+            .flat_map(|(rectangles, loose)| loose.iter().map(move |index| &rectangles[*index]));
         for rectangle in rectangles {
 
             let (x_start, y_start) = camera.to_normalized_coordinates((rectangle.x_start as f32, rectangle.y_start as f32));
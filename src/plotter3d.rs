@@ -1,42 +1,89 @@
 use three_d::*;
 use crate::expression::Expression;
 use crate::plotter::Plotter;
+use crate::colormap::{Colormap, Levels};
+use crate::heatmap::Surface;
+use crate::text;
+use crate::parallel;
+use crate::glsl;
+use log::info;
+use std::collections::HashMap;
 
 const RESOLUTION: usize = 50;
 
+// Number of points along the sides of the grid of the surfaces drawn by shaders
+const SHADER_RESOLUTION: usize = 1000;
+
+// Intensity of the ambient light and direction of the directional light
+const AMBIENT_INTENSITY: f32 = 0.7;
+const LIGHT_DIRECTION: (f32, f32, f32) = (1.0, -1.0, 1.0);
+
+// Number of bands of the same color the surface is cut into by height
+const COLOR_BANDS: usize = 32;
+
+// Height in normalized coordinates of the floor the contour lines are projected on
+const FLOOR_HEIGHT: f32 = -1.0;
+const CONTOUR_WIDTH: f32 = 0.003;
+
+// The color legend is a bar on the right of the screen, in normalized coordinates, with labels next to it
+const LEGEND_LEFT: f32 = 0.85;
+const LEGEND_RIGHT: f32 = 0.88;
+const LEGEND_BOTTOM: f32 = -0.8;
+const LEGEND_TOP: f32 = 0.8;
+const LEGEND_LABEL_HEIGHT: f32 = 10.0;
+
 pub struct Plotter3d {
+    gl: Gl,
     plot: Plot,
+    // Surfaces without contour lines are drawn by a shader, when the function can be translated to GLSL.
+    // The plot on the CPU still gives their range and the points traced
+    shader_surface: Option<ShaderSurface>,
     expression: Expression<f64>,
+    colormap: Colormap,
+    levels: Levels,
+    // Whether the contour lines are drawn, otherwise the levels are only written on the legend
+    show_contours: bool,
     camera: Camera,
     screen_size: (usize, usize),
     projection: three_d::Camera,
     ambient_light: AmbientLight,
     directional_light: DirectionalLight,
-    axis: Axis
+    axis: Axis,
+    legend: Legend,
 }
 
 impl Plotter3d {
-    pub fn new(gl: &Gl, expression: Expression<f64>, screen_size: (usize, usize)) -> Plotter3d {
+    pub fn new(gl: &Gl, expression: Expression<f64>, shader: Option<String>, screen_size: (usize, usize)) -> Plotter3d {
 
         let camera = Camera {position: (0.0, 0.0, 0.0), size: 10.0};
         let projection = three_d::Camera::new_perspective(gl, vec3(1.0, 1.0, 1.0), vec3(0.0, -0.3, 0.0), vec3(0.0, 1.0, 0.0),
                                                         degrees(45.0), screen_size.0 as f32/screen_size.1 as f32, 0.1, 10.0);
 
-        let ambient_light = AmbientLight::new(&gl, 0.7, &vec3(1.0, 1.0, 1.0)).unwrap();
-        let directional_light = DirectionalLight::new(&gl, 0.8, &vec3(0.5, 1.0, 1.0), &vec3(1.0, -1.0, 1.0)).unwrap();
+        let ambient_light = AmbientLight::new(&gl, AMBIENT_INTENSITY, &vec3(1.0, 1.0, 1.0)).unwrap();
+        let directional_light = DirectionalLight::new(&gl, 0.8, &vec3(0.5, 1.0, 1.0), &vec3(LIGHT_DIRECTION.0, LIGHT_DIRECTION.1, LIGHT_DIRECTION.2)).unwrap();
         let plot = Plot::new(gl, &expression, RESOLUTION, &camera);
         let axis = Axis::new(gl);
+        let legend = Legend::new(gl);
+
+        let shader_surface = shader.and_then(|function| ShaderSurface::new(gl, &function, &Colormap::Viridis));
 
-        let plotter = Plotter3d {
+        let mut plotter = Plotter3d {
+            gl: gl.clone(),
             plot,
+            shader_surface,
             expression,
+            colormap: Colormap::Viridis,
+            levels: Levels::new(Vec::new()),
+            show_contours: false,
             camera,
             screen_size,
             projection,
             ambient_light,
             directional_light,
-            axis
+            axis,
+            legend,
         };
+        plotter.update_view();
 
         plotter
     }
@@ -45,16 +92,60 @@ impl Plotter3d {
         self.projection.rotate(delta, 0.0);
     }
 
-    pub fn set_expression(&mut self, expression: Expression<f64>) {
+    // A surface colored by height with the default colormap and without contour lines,
+    // drawn by a shader when its function in GLSL is given
+    pub fn set_expression(&mut self, expression: Expression<f64>, shader: Option<String>) {
+        self.shader_surface = shader.and_then(|function| ShaderSurface::new(&self.gl, &function, &Colormap::Viridis));
         self.expression = expression;
+        self.colormap = Colormap::Viridis;
+        self.levels = Levels::new(Vec::new());
+        self.show_contours = false;
+        self.update_view();
+    }
+
+    pub fn set_surface(&mut self, surface: Surface) {
+        self.shader_surface = None;
+        self.expression = surface.function;
+        self.colormap = surface.colormap;
+        self.levels = surface.levels;
+        self.show_contours = true;
         self.update_view();
     }
+
+    // Cast a ray from the cursor into the scene and return the point (x, y, f(x,y))
+    // of the surface which is hit first, if any
+    pub fn trace(&self, screen_position: (f64, f64)) -> Option<(f64, f64, f64)> {
+        let view_projection = self.projection.get_projection() * self.projection.get_view();
+        let inverse = view_projection.invert()?;
+
+        // Unproject the cursor position on the far plane to get the ray direction
+        let x_ndc = 2.0 * screen_position.0 as f32 / self.screen_size.0 as f32 - 1.0;
+        let y_ndc = 1.0 - 2.0 * screen_position.1 as f32 / self.screen_size.1 as f32;
+        let far_point = inverse * vec4(x_ndc, y_ndc, 1.0, 1.0);
+        let far_point = far_point.truncate() / far_point.w;
+
+        let origin = *self.projection.position();
+        let direction = (far_point - origin).normalize();
+
+        let hit = self.plot.ray_intersection(origin, direction)?;
+
+        // The mesh lives in normalized coordinates with the y and z axes swapped
+        let (x, y, _) = self.camera.to_world_coordinates((hit.x, -hit.z, hit.y));
+        let (x, y) = (x as f64, y as f64);
+        Some((x, y, self.expression.eval_3d(x, y)))
+    }
 }
 
 impl Plotter for Plotter3d {
 
     fn update_view(&mut self) {
         self.plot.update_positions(&self.expression, RESOLUTION, &self.camera);
+        let (min, max) = self.plot.range;
+        let levels = self.levels.values(min, max);
+        if self.shader_surface.is_none() {
+            self.plot.update_colors(&self.colormap, if self.show_contours { &levels[..] } else { &[] }, &self.camera);
+        }
+        self.legend.update(&self.colormap, &levels, (min, max), self.screen_size);
     }
 
     fn zoom(&mut self, delta: f32) {
@@ -71,12 +162,23 @@ impl Plotter for Plotter3d {
     fn render(&self, gl: &Gl, renderer: &mut DeferredPipeline) {
 
         renderer.geometry_pass(self.screen_size.0, self.screen_size.1, &|| {
-            self.plot.render(&self.projection);
+            if self.shader_surface.is_none() {
+                self.plot.render(&self.projection);
+            }
             self.axis.render(&self.projection);
         }).unwrap();
 
         Screen::write(&gl, 0, 0, self.screen_size.0, self.screen_size.1, Some(&vec4(0.9, 0.9, 0.9, 1.0)), None, &|| {
             renderer.light_pass(&self.projection, Some(&self.ambient_light), &[&self.directional_light], &[], &[]).unwrap();
+            if let Some(surface) = &self.shader_surface {
+                // the surface is hidden by the axis in front of it
+                state::depth_write(gl, true);
+                state::depth_test(gl, state::DepthTestType::LessOrEqual);
+                let normalization = self.colormap.normalization(self.plot.range.0, self.plot.range.1);
+                surface.render(&self.projection, &self.camera, normalization);
+                state::depth_test(gl, state::DepthTestType::None);
+            }
+            self.legend.render();
         }).unwrap();
 
     }
@@ -95,12 +197,132 @@ impl Camera {
         let z_proj = 2.0*(point.2 - self.position.2)/self.size;
         (x_proj, y_proj, z_proj)
     }
+
+    // inverse of to_normalized_coordinates
+    fn to_world_coordinates(&self, point: (f32, f32, f32)) -> (f32, f32, f32) {
+        let x = point.0 * self.size / 2.0 + self.position.0;
+        let y = point.1 * self.size / 2.0 + self.position.1;
+        let z = point.2 * self.size / 2.0 + self.position.2;
+        (x, y, z)
+    }
+}
+
+// The points of the surface on a square grid starting from origin, a row of constant x after the other.
+// The rows are evaluated in parallel, each one in a batch
+pub fn sample_grid(expression: &Expression<f64>, count: usize, origin: (f32, f32), step: f32) -> Vec<(f32, f32, f32)> {
+    let rows = parallel::map_range(count, |i| {
+        let x = origin.0 + step * i as f32;
+        let xs = vec![x as f64; count];
+        let ys: Vec<f64> = (0..count).map(|j| (origin.1 + step * j as f32) as f64).collect();
+        let mut heights = vec![0.0; count];
+        expression.eval_batch(&xs, &ys, &mut heights);
+
+        ys.iter().zip(heights.iter())
+            .map(|(y, height)| (x, *y as f32, *height as f32))
+            .collect::<Vec<_>>()
+    });
+    rows.into_iter().flatten().collect()
+}
+
+// The triangles of a square grid of count points per side, a row after the other: each square has 2 triangles
+fn grid_triangles(count: usize) -> Vec<u32> {
+    let n_triangles = (count-1)*(count-1)*2;
+    let n_vertices = n_triangles * 3; // 3 vertices per triangle
+    let mut indices: Vec<u32> = Vec::with_capacity(n_vertices);
+
+    let to_vec_index = |pos: (usize, usize)| {
+        (count * pos.0 + pos.1) as u32
+    };
+
+    for i in 0..count-1 {
+        for j in 0..count-1 {
+            // first triangle
+            indices.push(to_vec_index((i, j)));
+            indices.push(to_vec_index((i, j+1)));
+            indices.push(to_vec_index((i+1, j+1)));
+
+            // second triangle
+            indices.push(to_vec_index((i+1, j)));
+            indices.push(to_vec_index((i, j)));
+            indices.push(to_vec_index((i+1, j+1)));
+        }
+    }
+    indices
+}
+
+// A surface whose heights are computed by the vertex shader on a fine grid, covering the view.
+// It is lit and colored by height in the fragment shader
+struct ShaderSurface {
+    program: Program,
+    grid_buffer: VertexBuffer,
+    index_buffer: ElementBuffer,
+}
+
+impl ShaderSurface {
+    // None when the shaders do not compile, then the surface is drawn from the plot on the CPU
+    fn new(gl: &Gl, function: &str, colormap: &Colormap) -> Option<ShaderSurface> {
+        let vertex_shader = glsl::program_source(include_str!("../assets/shaders/surface.vert"), &[function]);
+        let fragment_shader = glsl::program_source(include_str!("../assets/shaders/surface.frag"), &[&glsl::colormap_source(colormap)]);
+        let program = match Program::from_source(gl, &vertex_shader, &fragment_shader) {
+            Ok(program) => program,
+            Err(err) => {
+                info!("Could not compile the shader of the surface, it is computed on the CPU: {:?}", err);
+                return None;
+            }
+        };
+
+        // the points of the grid go from 0 to 1 along both sides, the shader places them in the view
+        let last = (SHADER_RESOLUTION - 1) as f32;
+        let mut grid: Vec<f32> = Vec::with_capacity(SHADER_RESOLUTION * SHADER_RESOLUTION * 3);
+        for i in 0..SHADER_RESOLUTION {
+            for j in 0..SHADER_RESOLUTION {
+                grid.extend_from_slice(&[i as f32 / last, j as f32 / last, 0.0]);
+            }
+        }
+
+        Some(ShaderSurface {
+            program,
+            grid_buffer: VertexBuffer::new_with_static_f32(gl, &grid).unwrap(),
+            index_buffer: ElementBuffer::new_with_u32(gl, &grid_triangles(SHADER_RESOLUTION)).unwrap(),
+        })
+    }
+
+    // The fraction of the colormap of a height is normalization.0 * height + normalization.1
+    fn render(&self, projection: &three_d::Camera, camera: &Camera, normalization: (f64, f64)) {
+        let corner = (camera.position.0 - camera.size / 2.0, camera.position.1 - camera.size / 2.0);
+        let light = vec3(LIGHT_DIRECTION.0, LIGHT_DIRECTION.1, LIGHT_DIRECTION.2);
+
+        self.program.add_uniform_mat4("worldViewProjectionMatrix", &(projection.get_projection() * projection.get_view())).unwrap();
+        self.program.add_uniform_vec4("grid", &vec4(corner.0, corner.1, camera.size, camera.size / (SHADER_RESOLUTION - 1) as f32)).unwrap();
+        self.program.add_uniform_vec4("camera", &vec4(camera.position.0, camera.position.1, camera.position.2, camera.size)).unwrap();
+        self.program.add_uniform_vec4("normalization", &vec4(normalization.0 as f32, normalization.1 as f32, 0.0, 0.0)).unwrap();
+        self.program.add_uniform_vec4("light", &light.extend(AMBIENT_INTENSITY)).unwrap();
+        self.program.use_attribute_vec3_float(&self.grid_buffer, "position").unwrap();
+        self.program.draw_elements(&self.index_buffer);
+    }
+}
+
+// A point of the surface cut in bands: a vertex of the grid,
+// or the point where the edge between two vertices crosses the level with the given index
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Vertex {
+    Grid(u32),
+    Crossing(u32, u32, usize),
 }
 
 struct Plot {
-    plot_mesh: Mesh,
+    gl: Gl,
+    // The surface is made of bands of the same color between consecutive heights
+    bands: Vec<Mesh>,
+    // The contour lines on the surface and their projections on the floor, one for each level
+    contours: Option<Edges>,
+    floor_contours: Vec<Edges>,
     plot_indices: Vec<u32>,
-    grid: Edges
+    plot_positions: Vec<f32>,
+    plot_normals: Vec<f32>,
+    grid: Edges,
+    // The smallest and the biggest height of the surface
+    range: (f64, f64),
 }
 
 impl Plot {
@@ -108,36 +330,11 @@ impl Plot {
     fn new(gl: &Gl, expression: &Expression<f64>, count: usize, camera: &Camera) -> Plot {
 
         let positions = Plot::generate_grid_positions(expression, count, camera);
-    
-        // generate triangles: each square in the grid has 2 triangles
-        let n_triangles = (count-1)*(count-1)*2;
-        let n_vertices = n_triangles * 3; // 3 vertices per triangle
-        let mut plot_indices: Vec<u32> = Vec::with_capacity(n_vertices);
+        let plot_indices = grid_triangles(count);
 
         let to_vec_index = |pos: (usize, usize)| {
             (count * pos.0 + pos.1) as u32
         };
-    
-        for i in 0..count-1 {
-            for j in 0..count-1 {
-                // first triangle
-                plot_indices.push(to_vec_index((i, j)));
-                plot_indices.push(to_vec_index((i, j+1)));
-                plot_indices.push(to_vec_index((i+1, j+1)));
-
-                // second triangle
-                plot_indices.push(to_vec_index((i+1, j)));
-                plot_indices.push(to_vec_index((i, j)));
-                plot_indices.push(to_vec_index((i+1, j+1)));
-            }
-        }
-
-        let cpu_mesh = CPUMesh::new_with_computed_normals(&plot_indices, &positions).unwrap();
-        let mut plot_mesh = cpu_mesh.to_mesh(gl).unwrap();
-        plot_mesh.diffuse_intensity = 0.5;
-        plot_mesh.specular_intensity = 0.2;
-        plot_mesh.specular_power = 20.0;
-        plot_mesh.color = vec3(0.6, 1.0, 0.6);
 
 
         // generate grid wireframe
@@ -160,42 +357,259 @@ impl Plot {
         let mut grid = Edges::new(gl, &grid_indices, &positions, 0.001);
         grid.color = vec3(0.6, 0.6, 0.6);
 
+        let plot_normals = Plot::compute_normals(&plot_indices, &positions);
         Plot {
-            plot_mesh,
+            gl: gl.clone(),
+            bands: Vec::new(),
+            contours: None,
+            floor_contours: Vec::new(),
             plot_indices,
-            grid
+            plot_positions: positions,
+            plot_normals,
+            grid,
+            range: (0.0, 0.0),
         }
     }
 
     fn update_positions(&mut self, expression: &Expression<f64>, count: usize, camera: &Camera) {
         let positions = Plot::generate_grid_positions(expression, count, camera);
-        self.plot_mesh.update_positions(&positions).unwrap();
-        self.plot_mesh.update_normals(&Plot::compute_normals(&self.plot_indices, &positions)).unwrap();
+        self.plot_normals = Plot::compute_normals(&self.plot_indices, &positions);
         self.grid.update_positions(&positions);
+
+        let to_world = |height: f32| camera.to_world_coordinates((0.0, 0.0, height)).2 as f64;
+        self.range = positions.iter().skip(1).step_by(3)
+            .filter(|height| height.is_finite())
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), height| (min.min(to_world(*height)), max.max(to_world(*height))));
+        self.plot_positions = positions;
+    }
+
+    // Cut the surface in bands colored by height and draw the contour lines at the given levels
+    fn update_colors(&mut self, colormap: &Colormap, levels: &[(f64, String)], camera: &Camera) {
+        let (min, max) = self.range;
+        let to_normalized = |height: f64| camera.to_normalized_coordinates((0.0, 0.0, height as f32)).2;
+        let color = |height: f64| {
+            let (red, green, blue) = colormap.color(colormap.normalize(height, min, max));
+            vec3(red, green, blue)
+        };
+
+        // The outer bands extend to any height, so that every triangle belongs to some band
+        let mut boundaries = vec![f32::NEG_INFINITY];
+        if max > min {
+            boundaries.extend((1..COLOR_BANDS).map(|i| to_normalized(min + (max - min) * i as f64 / COLOR_BANDS as f64)));
+        }
+        boundaries.push(f32::INFINITY);
+
+        let bands = Plot::generate_bands(&self.plot_indices, &self.plot_positions, &self.plot_normals, &boundaries);
+        self.bands = bands.into_iter().enumerate()
+            .filter(|(_, (_, _, indices))| !indices.is_empty())
+            .map(|(i, (positions, normals, indices))| {
+                let mut mesh = CPUMesh::new_with_computed_normals(&indices, &positions).unwrap().to_mesh(&self.gl).unwrap();
+                mesh.update_normals(&normals).unwrap();
+                mesh.diffuse_intensity = 0.5;
+                mesh.specular_intensity = 0.2;
+                mesh.specular_power = 20.0;
+                mesh.color = color(min + (max - min) * (i as f64 + 0.5) / (boundaries.len() - 1) as f64);
+                mesh
+            })
+            .collect();
+
+        let segments: Vec<(f64, Vec<f32>)> = levels.iter()
+            .map(|(level, _)| (*level, Plot::generate_contour(&self.plot_indices, &self.plot_positions, to_normalized(*level))))
+            .filter(|(_, positions)| !positions.is_empty())
+            .collect();
+        let gl = &self.gl.clone();
+        let edges = |positions: &[f32]| {
+            let indices: Vec<u32> = (0..positions.len() as u32 / 6).flat_map(|i| vec![2 * i, 2 * i + 1, 2 * i + 1]).collect();
+            Edges::new(gl, &indices, positions, CONTOUR_WIDTH)
+        };
+
+        let all_segments: Vec<f32> = segments.iter().flat_map(|(_, positions)| positions.iter().cloned()).collect();
+        self.contours = if all_segments.is_empty() {
+            None
+        } else {
+            let mut contours = edges(&all_segments);
+            contours.color = vec3(0.2, 0.2, 0.2);
+            Some(contours)
+        };
+
+        self.floor_contours = segments.iter()
+            .map(|(level, positions)| {
+                let floor: Vec<f32> = positions.chunks(3).flat_map(|point| vec![point[0], FLOOR_HEIGHT, point[2]]).collect();
+                let mut contour = edges(&floor);
+                contour.color = color(*level);
+                contour
+            })
+            .collect();
+    }
+
+    // Cut every triangle of the surface in the parts between consecutive boundaries, sorted by height.
+    // Returns the positions, the normals and the indices of the triangles of every band
+    fn generate_bands(indices: &[u32], positions: &[f32], normals: &[f32], boundaries: &[f32]) -> Vec<(Vec<f32>, Vec<f32>, Vec<u32>)> {
+        let height = |vertex: Vertex| match vertex {
+            Vertex::Grid(i) => positions[3 * i as usize + 1],
+            Vertex::Crossing(_, _, level) => boundaries[level],
+        };
+        let point = |data: &[f32], i: u32| vec3(data[3 * i as usize], data[3 * i as usize + 1], data[3 * i as usize + 2]);
+
+        // The vertices at the cuts are on the edges of the grid, so that the bands of adjacent triangles match
+        let crossing = |p: Vertex, q: Vertex, level: usize| {
+            let (a, b) = match (p, q) {
+                (Vertex::Grid(a), Vertex::Grid(b)) => (a, b),
+                (Vertex::Crossing(a, b, _), _) | (_, Vertex::Crossing(a, b, _)) => (a, b),
+            };
+            Vertex::Crossing(a.min(b), a.max(b), level)
+        };
+        // Keep the part of the polygon above or below the boundary
+        let clip = |polygon: Vec<Vertex>, level: usize, above: bool| {
+            let inside = |vertex: Vertex| if above { height(vertex) >= boundaries[level] } else { height(vertex) <= boundaries[level] };
+            let mut clipped = Vec::with_capacity(polygon.len() + 1);
+            for (k, p) in polygon.iter().enumerate() {
+                let q = polygon[(k + 1) % polygon.len()];
+                if inside(*p) {
+                    clipped.push(*p);
+                }
+                if inside(*p) != inside(q) {
+                    clipped.push(crossing(*p, q, level));
+                }
+            }
+            clipped
+        };
+
+        let mut bands: Vec<(Vec<f32>, Vec<f32>, Vec<u32>)> = vec![(Vec::new(), Vec::new(), Vec::new()); boundaries.len() - 1];
+        let mut vertices: Vec<HashMap<Vertex, u32>> = vec![HashMap::new(); boundaries.len() - 1];
+
+        for face in indices.chunks(3) {
+            let heights: Vec<f32> = face.iter().map(|i| positions[3 * *i as usize + 1]).collect();
+            if heights.iter().any(|height| !height.is_finite()) {
+                continue;
+            }
+            let lowest = heights.iter().cloned().fold(f32::INFINITY, f32::min);
+            let highest = heights.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+
+            for band in 0..boundaries.len() - 1 {
+                if boundaries[band + 1] < lowest || boundaries[band] > highest {
+                    continue;
+                }
+
+                let polygon = face.iter().map(|i| Vertex::Grid(*i)).collect();
+                let polygon = clip(clip(polygon, band, true), band + 1, false);
+                if polygon.len() < 3 {
+                    continue;
+                }
+
+                let (band_positions, band_normals, band_indices) = &mut bands[band];
+                let mut index = |vertex: Vertex| *vertices[band].entry(vertex).or_insert_with(|| {
+                    let (position, normal) = match vertex {
+                        Vertex::Grid(i) => (point(positions, i), point(normals, i)),
+                        Vertex::Crossing(a, b, level) => {
+                            let t = (boundaries[level] - height(Vertex::Grid(a))) / (height(Vertex::Grid(b)) - height(Vertex::Grid(a)));
+                            let position = point(positions, a) + (point(positions, b) - point(positions, a)) * t;
+                            let normal = point(normals, a) + (point(normals, b) - point(normals, a)) * t;
+                            (position, normal.normalize())
+                        },
+                    };
+                    band_positions.extend_from_slice(&[position.x, position.y, position.z]);
+                    band_normals.extend_from_slice(&[normal.x, normal.y, normal.z]);
+                    (band_positions.len() / 3 - 1) as u32
+                });
+
+                // The clipped triangle is convex, split it in a fan of triangles
+                let first = index(polygon[0]);
+                for k in 1..polygon.len() - 1 {
+                    let (second, third) = (index(polygon[k]), index(polygon[k + 1]));
+                    band_indices.extend_from_slice(&[first, second, third]);
+                }
+            }
+        }
+
+        bands
+    }
+
+    // The segments where the surface crosses the level, as pairs of points
+    fn generate_contour(indices: &[u32], positions: &[f32], level: f32) -> Vec<f32> {
+        let point = |i: u32| vec3(positions[3 * i as usize], positions[3 * i as usize + 1], positions[3 * i as usize + 2]);
+
+        let mut segments = Vec::new();
+        for face in indices.chunks(3) {
+            for (k, a) in face.iter().enumerate() {
+                let b = face[(k + 1) % 3];
+                let (a, b) = (*a.min(&b), *a.max(&b));
+                let (p, q) = (point(a), point(b));
+                if (p.y >= level) != (q.y >= level) {
+                    let crossing = p + (q - p) * ((level - p.y) / (q.y - p.y));
+                    segments.extend_from_slice(&[crossing.x, crossing.y, crossing.z]);
+                }
+            }
+        }
+        segments
+    }
+
+    // Returns the closest intersection point between the ray and the plot mesh
+    // using the Möller–Trumbore algorithm on every triangle
+    fn ray_intersection(&self, origin: Vec3, direction: Vec3) -> Option<Vec3> {
+        let vertex = |index: u32| {
+            let index = index as usize;
+            vec3(self.plot_positions[index*3], self.plot_positions[index*3+1], self.plot_positions[index*3+2])
+        };
+
+        let mut closest: Option<f32> = None;
+        for face in self.plot_indices.chunks(3) {
+            let (p0, p1, p2) = (vertex(face[0]), vertex(face[1]), vertex(face[2]));
+            let edge1 = p1 - p0;
+            let edge2 = p2 - p0;
+
+            let h = direction.cross(edge2);
+            let det = edge1.dot(h);
+            if det.abs() < std::f32::EPSILON {
+                // The ray is parallel to the triangle
+                continue;
+            }
+
+            let s = origin - p0;
+            let u = s.dot(h) / det;
+            if u < 0.0 || u > 1.0 {
+                continue;
+            }
+
+            let q = s.cross(edge1);
+            let v = direction.dot(q) / det;
+            if v < 0.0 || u + v > 1.0 {
+                continue;
+            }
+
+            let t = edge2.dot(q) / det;
+            if t > 0.0 && closest.map_or(true, |closest| t < closest) {
+                closest = Some(t);
+            }
+        }
+
+        closest.map(|t| origin + direction * t)
     }
 
     fn render(&self, projection: &three_d::Camera) {
         let transformation = Mat4::identity();
-        self.plot_mesh.render(&transformation, projection);
+        for band in self.bands.iter() {
+            band.render(&transformation, projection);
+        }
         self.grid.render(&transformation, projection);
+        if let Some(contours) = &self.contours {
+            contours.render(&transformation, projection);
+        }
+        for contour in self.floor_contours.iter() {
+            contour.render(&transformation, projection);
+        }
     }
 
     fn generate_grid_positions(expression: &Expression<f64>, count: usize, camera: &Camera) ->  Vec<f32> {
-        // generate grid positions
+        let origin = (camera.position.0 - camera.size/2.0, camera.position.1 - camera.size/2.0);
+        let points = sample_grid(expression, count, origin, camera.size / count as f32);
+
         let mut positions: Vec<f32> = Vec::with_capacity(count * count * 3);
-        let step = camera.size / count as f32;
-        let mut x = camera.position.0 - camera.size/2.0;
-        for _ in 0..count {
-            let mut y = camera.position.1 - camera.size/2.0;
-            for _ in 0..count {
-                let z = expression.eval_3d(x as f64, y as f64) as f32;
-                let point = camera.to_normalized_coordinates((x,y,z));
-                positions.push(point.0);
-                positions.push(point.2);
-                positions.push(-point.1);
-                y += step;
-            }
-            x += step;
+        for point in points {
+            let point = camera.to_normalized_coordinates(point);
+            positions.push(point.0);
+            positions.push(point.2);
+            positions.push(-point.1);
         }
 
         positions
@@ -260,3 +674,153 @@ impl Axis {
         self.axis.render(&transformation, camera);
     }
 }
+
+// The colors of the heights of the surface, with the levels written next to them.
+// It is drawn over the scene, on the near plane
+struct Legend {
+    color_program: Program,
+    line_program: Program,
+    bar_buffer: VertexBuffer,
+    bar_color_buffer: VertexBuffer,
+    bar_buffer_size: u32,
+    label_buffer: VertexBuffer,
+    label_buffer_size: u32,
+}
+
+impl Legend {
+    fn new(gl: &Gl) -> Legend {
+        let color_program = Program::from_source(gl,
+            include_str!("../assets/shaders/vertex_color.vert"),
+            include_str!("../assets/shaders/color.frag")).unwrap();
+        let line_program = Program::from_source(gl,
+            include_str!("../assets/shaders/color.vert"),
+            include_str!("../assets/shaders/color.frag")).unwrap();
+
+        Legend {
+            color_program,
+            line_program,
+            bar_buffer: VertexBuffer::new_with_static_f32(gl, &[]).unwrap(),
+            bar_color_buffer: VertexBuffer::new_with_static_f32(gl, &[]).unwrap(),
+            bar_buffer_size: 0,
+            label_buffer: VertexBuffer::new_with_static_f32(gl, &[]).unwrap(),
+            label_buffer_size: 0,
+        }
+    }
+
+    fn update(&mut self, colormap: &Colormap, levels: &[(f64, String)], range: (f64, f64), screen_size: (usize, usize)) {
+        let (min, max) = range;
+        let mut positions: Vec<f32> = Vec::new();
+        let mut colors: Vec<f32> = Vec::new();
+        let mut labels: Vec<f32> = Vec::new();
+
+        if min.is_finite() && max.is_finite() {
+            let to_height = |value: f64| if max > min {
+                LEGEND_BOTTOM + (LEGEND_TOP - LEGEND_BOTTOM) * ((value - min) / (max - min)) as f32
+            } else {
+                (LEGEND_BOTTOM + LEGEND_TOP) / 2.0
+            };
+
+            // A stack of rectangles, with the colors interpolated from the bottom to the top of each one
+            for i in 0..COLOR_BANDS {
+                let (bottom, top) = (min + (max - min) * i as f64 / COLOR_BANDS as f64, min + (max - min) * (i + 1) as f64 / COLOR_BANDS as f64);
+                let (y_bottom, y_top) = (to_height(bottom), to_height(top));
+                let bottom_color = colormap.color(colormap.normalize(bottom, min, max));
+                let top_color = colormap.color(colormap.normalize(top, min, max));
+                for (x, y, color) in [(LEGEND_LEFT, y_bottom, bottom_color), (LEGEND_RIGHT, y_top, top_color), (LEGEND_LEFT, y_top, top_color),
+                                      (LEGEND_LEFT, y_bottom, bottom_color), (LEGEND_RIGHT, y_bottom, bottom_color), (LEGEND_RIGHT, y_top, top_color)].iter() {
+                    positions.extend_from_slice(&[*x, *y, -1.0]);
+                    colors.extend_from_slice(&[color.0, color.1, color.2]);
+                }
+            }
+
+            // A tick and a label for every level
+            let (x_scale, y_scale) = (2.0 / screen_size.0 as f32, 2.0 / screen_size.1 as f32);
+            for (level, label) in levels.iter().filter(|(level, _)| *level >= min && *level <= max) {
+                let y = to_height(*level);
+                labels.extend_from_slice(&[LEGEND_RIGHT, y, -1.0, LEGEND_RIGHT + 4.0 * x_scale, y, -1.0]);
+                text::add_text(&mut labels, label, (LEGEND_RIGHT + 8.0 * x_scale, y - LEGEND_LABEL_HEIGHT / 2.0 * y_scale, -1.0),
+                               LEGEND_LABEL_HEIGHT, screen_size);
+            }
+        }
+
+        self.bar_buffer.fill_with_static_f32(&positions);
+        self.bar_color_buffer.fill_with_static_f32(&colors);
+        self.bar_buffer_size = (positions.len() / 3) as u32;
+        self.label_buffer.fill_with_static_f32(&labels);
+        self.label_buffer_size = (labels.len() / 3) as u32;
+    }
+
+    fn render(&self) {
+        if self.bar_buffer_size > 0 {
+            self.color_program.add_uniform_mat4("worldViewProjectionMatrix", &Mat4::identity()).unwrap();
+            self.color_program.use_attribute_vec3_float(&self.bar_buffer, "position").unwrap();
+            self.color_program.use_attribute_vec3_float(&self.bar_color_buffer, "color").unwrap();
+            self.color_program.draw_arrays(self.bar_buffer_size);
+        }
+
+        if self.label_buffer_size > 0 {
+            self.line_program.add_uniform_mat4("worldViewProjectionMatrix", &Mat4::identity()).unwrap();
+            self.line_program.use_attribute_vec3_float(&self.label_buffer, "position").unwrap();
+            self.line_program.add_uniform_vec4("color", &vec4(0.2, 0.2, 0.2, 1.0)).unwrap();
+            self.line_program.draw_arrays_mode(self.label_buffer_size, consts::LINES);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A square of two triangles with the heights 0, 1, 2 and 3 at the corners
+    const INDICES: [u32; 6] = [0, 1, 3, 2, 0, 3];
+    const POSITIONS: [f32; 12] = [0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 2.0, 0.0, 1.0, 3.0, 1.0];
+    const NORMALS: [f32; 12] = [0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+
+    fn area(indices: &[u32], positions: &[f32]) -> f32 {
+        let point = |i: u32| vec3(positions[3 * i as usize], positions[3 * i as usize + 1], positions[3 * i as usize + 2]);
+        indices.chunks(3)
+            .map(|face| {
+                let normal = (point(face[1]) - point(face[0])).cross(point(face[2]) - point(face[0]));
+                (normal.x * normal.x + normal.y * normal.y + normal.z * normal.z).sqrt() / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn bands_cover_the_surface() {
+        let boundaries = [f32::NEG_INFINITY, 0.5, 1.5, 2.5, f32::INFINITY];
+        let bands = Plot::generate_bands(&INDICES, &POSITIONS, &NORMALS, &boundaries);
+        assert_eq!(bands.len(), 4);
+
+        let total: f32 = bands.iter().map(|(positions, _, indices)| area(indices, positions)).sum();
+        assert!((total - area(&INDICES, &POSITIONS)).abs() < 1e-5);
+
+        for (band, (positions, normals, indices)) in bands.iter().enumerate() {
+            assert!(!indices.is_empty());
+            assert_eq!(positions.len(), normals.len());
+            // every vertex is between the boundaries of its band
+            assert!(positions.iter().skip(1).step_by(3).all(|height| *height >= boundaries[band] && *height <= boundaries[band + 1]));
+        }
+    }
+
+    #[test]
+    fn bands_share_the_vertices_on_the_cuts() {
+        let bands = Plot::generate_bands(&INDICES, &POSITIONS, &NORMALS, &[f32::NEG_INFINITY, 1.5, f32::INFINITY]);
+        let cut = |positions: &[f32]| positions.chunks(3).filter(|point| point[1] == 1.5).count();
+        // the diagonal and two sides of the square cross the cut, each vertex is added once to each band
+        assert_eq!(cut(&bands[0].0), 3);
+        assert_eq!(cut(&bands[1].0), 3);
+    }
+
+    #[test]
+    fn contours_cross_the_edges_at_the_level() {
+        let segments = Plot::generate_contour(&INDICES, &POSITIONS, 1.5);
+        // one segment for each triangle
+        assert_eq!(segments.len(), 12);
+        assert!(segments.iter().skip(1).step_by(3).all(|height| *height == 1.5));
+        // the crossing on the diagonal from height 0 to height 3 is half way
+        assert!(segments.chunks(3).any(|point| point == [0.5, 1.5, 0.5]));
+
+        assert!(Plot::generate_contour(&INDICES, &POSITIONS, 4.0).is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+use three_d::*;
+use num_complex::Complex;
+use crate::expression::Expression;
+use crate::plotter::Plotter;
+use crate::plane::{Camera, Grid};
+use std::f64::consts::PI;
+
+// Side in pixels of the squares the plot is made of,
+// the function is evaluated on their corners and the colors are interpolated in between
+const CELL_SIZE: usize = 4;
+
+// Color of the points where the function is not defined
+const UNDEFINED_COLOR: (f32, f32, f32) = (0.5, 0.5, 0.5);
+
+// Plots a complex function f(z) with domain coloring:
+// every point z of the plane gets the hue of the argument of f(z) and the brightness of its modulus
+pub struct PlotterComplex {
+    program: Program,
+    position_buffer: VertexBuffer,
+    color_buffer: VertexBuffer,
+    buffer_size: u32,
+    expression: Expression<Complex<f64>>,
+    camera: Camera,
+    screen_size: (usize, usize),
+}
+
+impl PlotterComplex {
+    pub fn new(gl: &Gl, expression: Expression<Complex<f64>>, screen_size: (usize, usize)) -> PlotterComplex {
+        let program = Program::from_source(gl,
+            include_str!("../assets/shaders/vertex_color.vert"),
+            include_str!("../assets/shaders/color.frag")).unwrap();
+
+        let position_buffer = VertexBuffer::new_with_static_f32(&gl, &[]).unwrap();
+        let color_buffer = VertexBuffer::new_with_static_f32(&gl, &[]).unwrap();
+
+        let mut plotter = PlotterComplex {
+            program,
+            position_buffer,
+            color_buffer,
+            buffer_size: 0,
+            expression,
+            camera: Camera::new(screen_size),
+            screen_size,
+        };
+        plotter.update_view();
+
+        plotter
+    }
+
+    pub fn set_expression(&mut self, expression: Expression<Complex<f64>>) {
+        self.expression = expression;
+        self.update_view();
+    }
+
+    // The point z under the cursor along with f(z)
+    pub fn trace(&self, screen_position: (f64, f64)) -> (Complex<f64>, Complex<f64>) {
+        let (x, y) = self.camera.to_world_coordinates(screen_position, self.screen_size);
+        let (x, y) = (x as f64, y as f64);
+        (Complex::new(x, y), self.expression.eval_3d(Complex::from(x), Complex::from(y)))
+    }
+
+    // Evaluate the function on a grid of points covering the screen and color the squares in between
+    fn generate_plot(&self) -> (Vec<f32>, Vec<f32>) {
+        let grid = Grid::new(&self.camera, self.screen_size, CELL_SIZE);
+        let (xs, ys) = grid.points();
+        let xs: Vec<Complex<f64>> = xs.into_iter().map(Complex::from).collect();
+        let ys: Vec<Complex<f64>> = ys.into_iter().map(Complex::from).collect();
+        let mut values = vec![Complex::from(0.0); xs.len()];
+        self.expression.eval_batch(&xs, &ys, &mut values);
+        let colors: Vec<(f32, f32, f32)> = values.into_iter().map(domain_color).collect();
+
+        grid.triangles(&self.camera, &colors)
+    }
+}
+
+// Hue from the argument, starting with red on the positive reals,
+// lightness from the modulus: black in the zeros, white in the poles and full colors where the modulus is 1
+fn domain_color(value: Complex<f64>) -> (f32, f32, f32) {
+    if value.is_nan() {
+        return UNDEFINED_COLOR;
+    }
+
+    let modulus = value.norm();
+    let lightness = if modulus.is_infinite() {
+        1.0
+    } else {
+        2.0 / PI * modulus.atan()
+    };
+    let hue = (value.arg() / (2.0 * PI)).rem_euclid(1.0);
+
+    hsl_to_rgb(hue as f32, 1.0, lightness as f32)
+}
+
+// Convert a color from hue, saturation and lightness, all between 0 and 1, to red, green and blue
+fn hsl_to_rgb(hue: f32, saturation: f32, lightness: f32) -> (f32, f32, f32) {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue * 6.0;
+    let second = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+
+    let (red, green, blue) = match sector as u32 {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+
+    let lightest = lightness - chroma / 2.0;
+    (red + lightest, green + lightest, blue + lightest)
+}
+
+impl Plotter for PlotterComplex {
+
+    fn update_view(&mut self) {
+        let (positions, colors) = self.generate_plot();
+        self.position_buffer.fill_with_static_f32(&positions);
+        self.color_buffer.fill_with_static_f32(&colors);
+        self.buffer_size = (positions.len() / 3) as u32;
+    }
+
+    fn zoom(&mut self, delta: f32) {
+        self.camera.zoom(delta);
+        self.update_view();
+    }
+
+    fn translate(&mut self, delta_x: f32, delta_y: f32) {
+        self.camera.translate(delta_x, delta_y, self.screen_size);
+        self.update_view();
+    }
+
+    fn render(&self, gl: &Gl, _renderer: &mut DeferredPipeline) {
+        Screen::write(gl, 0, 0, self.screen_size.0, self.screen_size.1, Some(&vec4(0.9, 0.9, 0.9, 1.0)), None, &|| {
+            self.program.add_uniform_mat4("worldViewProjectionMatrix", &Mat4::identity()).unwrap();
+            self.program.use_attribute_vec3_float(&self.position_buffer, "position").unwrap();
+            self.program.use_attribute_vec3_float(&self.color_buffer, "color").unwrap();
+            self.program.draw_arrays(self.buffer_size);
+        }).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_color(color: (f32, f32, f32), expected: (f32, f32, f32)) {
+        let distance = (color.0 - expected.0).abs() + (color.1 - expected.1).abs() + (color.2 - expected.2).abs();
+        assert!(distance < 1e-5, "{:?} is not {:?}", color, expected);
+    }
+
+    #[test]
+    fn hues_go_around_the_colors() {
+        assert_color(hsl_to_rgb(0.0, 1.0, 0.5), (1.0, 0.0, 0.0));
+        assert_color(hsl_to_rgb(0.5, 1.0, 0.5), (0.0, 1.0, 1.0));
+        assert_color(hsl_to_rgb(0.75, 1.0, 0.5), (0.5, 0.0, 1.0));
+        assert_color(hsl_to_rgb(0.5, 0.0, 0.25), (0.25, 0.25, 0.25));
+        assert_color(hsl_to_rgb(0.3, 1.0, 0.0), (0.0, 0.0, 0.0));
+        assert_color(hsl_to_rgb(0.3, 1.0, 1.0), (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn domain_colors_follow_the_argument_and_the_modulus() {
+        assert_color(domain_color(Complex::from(1.0)), (1.0, 0.0, 0.0));
+        assert_color(domain_color(Complex::from(-1.0)), (0.0, 1.0, 1.0));
+        assert_color(domain_color(Complex::new(0.0, 1.0)), (0.5, 1.0, 0.0));
+        // black in the zeros and white in the poles
+        assert_color(domain_color(Complex::from(0.0)), (0.0, 0.0, 0.0));
+        assert_color(domain_color(Complex::new(std::f64::INFINITY, 0.0)), (1.0, 1.0, 1.0));
+        assert_color(domain_color(Complex::new(std::f64::NAN, 0.0)), UNDEFINED_COLOR);
+    }
+}
@@ -0,0 +1,305 @@
+use three_d::*;
+use crate::heatmap::Heatmap;
+use crate::plotter::Plotter;
+use crate::plane::{Camera, Grid};
+use crate::plot_generator2d;
+use crate::text;
+use crate::glsl;
+use honestintervals::IntervalSet;
+use log::info;
+
+// Side in pixels of the squares the heatmap is made of,
+// the function is evaluated on their corners and the colors are interpolated in between
+const CELL_SIZE: usize = 4;
+
+// Side in pixels of the squares on whose corners the function is evaluated to find its range,
+// when the heatmap is drawn by a shader evaluating the function on every pixel
+const RANGE_CELL_SIZE: usize = 16;
+
+// Color of the points where the function is not defined
+const UNDEFINED_COLOR: (f32, f32, f32) = (0.5, 0.5, 0.5);
+
+const LINE_WIDTH: f32 = 0.004;
+
+// Height in pixels of the digits of the labels of the contour lines, and the space around them
+const LABEL_HEIGHT: f32 = 12.0;
+const LABEL_PADDING: f32 = 3.0;
+
+// Plots a function of two variables as a heatmap, along with its contour lines
+pub struct PlotterHeatmap {
+    gl: Gl,
+    heatmap_program: Program,
+    // Evaluates the function on every pixel, when it can be translated to GLSL and the shader compiles
+    shader_program: Option<Program>,
+    // A square covering the screen, drawn by the shader
+    screen_buffer: VertexBuffer,
+    // The scale and the offset turning values into fractions of the colormap
+    normalization: (f64, f64),
+    contour_program: Program,
+    position_buffer: VertexBuffer,
+    color_buffer: VertexBuffer,
+    buffer_size: u32,
+    contour_buffer: VertexBuffer,
+    contour_buffer_size: u32,
+    label_background_buffer: VertexBuffer,
+    label_background_buffer_size: u32,
+    label_buffer: VertexBuffer,
+    label_buffer_size: u32,
+    heatmap: Heatmap,
+    camera: Camera,
+    screen_size: (usize, usize),
+}
+
+impl PlotterHeatmap {
+    pub fn new(gl: &Gl, heatmap: Heatmap, screen_size: (usize, usize)) -> PlotterHeatmap {
+        let heatmap_program = Program::from_source(gl,
+            include_str!("../assets/shaders/vertex_color.vert"),
+            include_str!("../assets/shaders/color.frag")).unwrap();
+        let contour_program = Program::from_source(gl,
+            include_str!("../assets/shaders/color.vert"),
+            include_str!("../assets/shaders/color.frag")).unwrap();
+
+        let mut screen = Vec::new();
+        add_rectangle(&mut screen, (-1.0, -1.0), (1.0, 1.0));
+        let screen_buffer = VertexBuffer::new_with_static_f32(gl, &screen).unwrap();
+        let shader_program = PlotterHeatmap::compile_shader(gl, &heatmap);
+
+        let mut plotter = PlotterHeatmap {
+            gl: gl.clone(),
+            heatmap_program,
+            shader_program,
+            screen_buffer,
+            normalization: (0.0, 0.5),
+            contour_program,
+            position_buffer: VertexBuffer::new_with_static_f32(gl, &[]).unwrap(),
+            color_buffer: VertexBuffer::new_with_static_f32(gl, &[]).unwrap(),
+            buffer_size: 0,
+            contour_buffer: VertexBuffer::new_with_static_f32(gl, &[]).unwrap(),
+            contour_buffer_size: 0,
+            label_background_buffer: VertexBuffer::new_with_static_f32(gl, &[]).unwrap(),
+            label_background_buffer_size: 0,
+            label_buffer: VertexBuffer::new_with_static_f32(gl, &[]).unwrap(),
+            label_buffer_size: 0,
+            heatmap,
+            camera: Camera::new(screen_size),
+            screen_size,
+        };
+        plotter.update_view();
+
+        plotter
+    }
+
+    pub fn set_heatmap(&mut self, heatmap: Heatmap) {
+        self.shader_program = PlotterHeatmap::compile_shader(&self.gl, &heatmap);
+        self.heatmap = heatmap;
+        self.update_view();
+    }
+
+    // The point under the cursor along with the value of the function
+    pub fn trace(&self, screen_position: (f64, f64)) -> (f64, f64, f64) {
+        let (x, y) = self.camera.to_world_coordinates(screen_position, self.screen_size);
+        let (x, y) = (x as f64, y as f64);
+        (x, y, self.heatmap.function.eval_3d(x, y))
+    }
+
+    // The program drawing the heatmap of the function with a shader, if the function can be translated to GLSL.
+    // Otherwise the heatmap is computed on the CPU
+    fn compile_shader(gl: &Gl, heatmap: &Heatmap) -> Option<Program> {
+        let function = heatmap.shader.as_ref()?;
+        let colormap = glsl::colormap_source(&heatmap.colormap);
+        let fragment_shader = glsl::program_source(include_str!("../assets/shaders/heatmap.frag"), &[function, &colormap]);
+        match Program::from_source(gl, include_str!("../assets/shaders/plane.vert"), &fragment_shader) {
+            Ok(program) => Some(program),
+            Err(err) => {
+                info!("Could not compile the shader of the heatmap, it is computed on the CPU: {:?}", err);
+                None
+            }
+        }
+    }
+
+    // Evaluate the function on a grid of points covering the screen, cell_size pixels apart.
+    // Returns the grid and the values a column after the other
+    fn sample(&self, cell_size: usize) -> (Grid, Vec<f64>) {
+        let grid = Grid::new(&self.camera, self.screen_size, cell_size);
+        let (xs, ys) = grid.points();
+        let mut values = vec![0.0; xs.len()];
+        self.heatmap.function.eval_batch(&xs, &ys, &mut values);
+
+        (grid, values)
+    }
+
+    // Evaluate the function on a grid of points covering the screen and color the squares in between.
+    // Returns the positions, the colors and the range of the values
+    fn generate_heatmap(&self) -> (Vec<f32>, Vec<f32>, (f64, f64)) {
+        let (grid, values) = self.sample(CELL_SIZE);
+        let (min, max) = range(&values);
+
+        let colormap = &self.heatmap.colormap;
+        let colors: Vec<(f32, f32, f32)> = values.iter()
+            .map(|value| if value.is_finite() {
+                colormap.color(colormap.normalize(*value, min, max))
+            } else {
+                UNDEFINED_COLOR
+            })
+            .collect();
+
+        let (positions, vertex_colors) = grid.triangles(&self.camera, &colors);
+        (positions, vertex_colors, (min, max))
+    }
+
+    // The contour lines are the implicit curves f(x, y) - level = 0, found with interval arithmetic
+    // within a budget of evaluations shared by all the levels, what a level doesn't use is left to the following ones.
+    // Every line gets its label on the point closest to the center of the view.
+    // Returns the positions of the lines, of the backgrounds of the labels and of the labels
+    fn generate_contours(&self, min: f64, max: f64) -> (Vec<f32>, Vec<f32>, Vec<f32>) {
+        let center = (self.camera.position.0 as f64, self.camera.position.1 as f64);
+
+        let mut lines = Vec::new();
+        let mut backgrounds = Vec::new();
+        let mut labels = Vec::new();
+        let levels = self.heatmap.levels.values(min, max);
+        let mut budget = plot_generator2d::MAX_IMPLICIT_EVALUATIONS;
+        for (i, (level, label)) in levels.iter().enumerate() {
+            let expression = self.heatmap.interval_function.level_set(IntervalSet::singleton(*level));
+            let display_info = plot_generator2d::Rectangle {
+                x_start: (self.camera.position.0 - self.camera.size.0 / 2.0) as f64,
+                x_end: (self.camera.position.0 + self.camera.size.0 / 2.0) as f64,
+                y_start: (self.camera.position.1 - self.camera.size.1 / 2.0) as f64,
+                y_end: (self.camera.position.1 + self.camera.size.1 / 2.0) as f64,
+            };
+            let level_budget = budget / (levels.len() - i) as u32;
+            let plot = plot_generator2d::generate_2dplot_implicit(&expression, display_info, self.screen_size.0 as u32, level_budget);
+            budget -= plot.evaluations;
+            let rectangles = plot.rectangles;
+
+            for rectangle in rectangles.iter() {
+                let (x_start, y_start) = self.camera.to_normalized_coordinates((rectangle.x_start as f32, rectangle.y_start as f32));
+                let (x_end, y_end) = self.camera.to_normalized_coordinates((rectangle.x_end as f32, rectangle.y_end as f32));
+                let x_width = ((LINE_WIDTH - x_end + x_start) / 2.0).max(0.0);
+                let y_width = ((LINE_WIDTH - y_end + y_start) / 2.0).max(0.0);
+                add_rectangle(&mut lines, (x_start - x_width, y_start - y_width), (x_end + x_width, y_end + y_width));
+            }
+
+            let closest = rectangles.iter()
+                .map(|rect| ((rect.x_start + rect.x_end) / 2.0, (rect.y_start + rect.y_end) / 2.0))
+                .min_by(|a, b| {
+                    let dist_a = (a.0 - center.0).powi(2) + (a.1 - center.1).powi(2);
+                    let dist_b = (b.0 - center.0).powi(2) + (b.1 - center.1).powi(2);
+                    dist_a.partial_cmp(&dist_b).unwrap()
+                });
+            if let Some(point) = closest {
+                let position = self.camera.to_normalized_coordinates((point.0 as f32, point.1 as f32));
+                self.add_label(label, position, &mut backgrounds, &mut labels);
+            }
+        }
+
+        (lines, backgrounds, labels)
+    }
+
+    // Write the label centered in position, in normalized coordinates, on a background covering the contour line
+    fn add_label(&self, label: &str, position: (f32, f32), backgrounds: &mut Vec<f32>, labels: &mut Vec<f32>) {
+        // pixels to normalized coordinates
+        let scale = (2.0 / self.screen_size.0 as f32, 2.0 / self.screen_size.1 as f32);
+
+        let width = text::width(label, LABEL_HEIGHT);
+        let left = position.0 - width / 2.0 * scale.0;
+        let bottom = position.1 - LABEL_HEIGHT / 2.0 * scale.1;
+        add_rectangle(backgrounds,
+            (left - LABEL_PADDING * scale.0, bottom - LABEL_PADDING * scale.1),
+            (left + (width + LABEL_PADDING) * scale.0, bottom + (LABEL_HEIGHT + LABEL_PADDING) * scale.1));
+        text::add_text(labels, label, (left, bottom, 0.0), LABEL_HEIGHT, self.screen_size);
+    }
+}
+
+// The smallest and the biggest of the defined values
+fn range(values: &[f64]) -> (f64, f64) {
+    values.iter()
+        .filter(|value| value.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), value| (min.min(*value), max.max(*value)))
+}
+
+// Two triangles covering the rectangle between the corners, in normalized coordinates
+fn add_rectangle(positions: &mut Vec<f32>, start: (f32, f32), end: (f32, f32)) {
+    positions.extend_from_slice(&[start.0, start.1, 0.0,
+                                end.0, end.1, 0.0,
+                                start.0, end.1, 0.0,
+
+                                start.0, start.1, 0.0,
+                                end.0, start.1, 0.0,
+                                end.0, end.1, 0.0]);
+}
+
+impl Plotter for PlotterHeatmap {
+
+    fn update_view(&mut self) {
+        let (min, max) = if self.shader_program.is_some() {
+            // the shader only needs the range of the colormap
+            let (_, values) = self.sample(RANGE_CELL_SIZE);
+            range(&values)
+        } else {
+            let (positions, colors, bounds) = self.generate_heatmap();
+            self.position_buffer.fill_with_static_f32(&positions);
+            self.color_buffer.fill_with_static_f32(&colors);
+            self.buffer_size = (positions.len() / 3) as u32;
+            bounds
+        };
+        self.normalization = self.heatmap.colormap.normalization(min, max);
+
+        let (lines, backgrounds, labels) = self.generate_contours(min, max);
+        self.contour_buffer.fill_with_static_f32(&lines);
+        self.contour_buffer_size = (lines.len() / 3) as u32;
+        self.label_background_buffer.fill_with_static_f32(&backgrounds);
+        self.label_background_buffer_size = (backgrounds.len() / 3) as u32;
+        self.label_buffer.fill_with_static_f32(&labels);
+        self.label_buffer_size = (labels.len() / 3) as u32;
+    }
+
+    fn zoom(&mut self, delta: f32) {
+        self.camera.zoom(delta);
+        self.update_view();
+    }
+
+    fn translate(&mut self, delta_x: f32, delta_y: f32) {
+        self.camera.translate(delta_x, delta_y, self.screen_size);
+        self.update_view();
+    }
+
+    fn render(&self, gl: &Gl, _renderer: &mut DeferredPipeline) {
+        Screen::write(gl, 0, 0, self.screen_size.0, self.screen_size.1, Some(&vec4(0.9, 0.9, 0.9, 1.0)), None, &|| {
+            match &self.shader_program {
+                Some(program) => {
+                    let view = &self.camera;
+                    program.add_uniform_vec4("view", &vec4(view.position.0 - view.size.0 / 2.0, view.position.1 - view.size.1 / 2.0,
+                                                           view.position.0 + view.size.0 / 2.0, view.position.1 + view.size.1 / 2.0)).unwrap();
+                    program.add_uniform_vec4("normalization", &vec4(self.normalization.0 as f32, self.normalization.1 as f32, 0.0, 0.0)).unwrap();
+                    program.add_uniform_vec4("undefinedColor", &vec4(UNDEFINED_COLOR.0, UNDEFINED_COLOR.1, UNDEFINED_COLOR.2, 1.0)).unwrap();
+                    program.use_attribute_vec3_float(&self.screen_buffer, "position").unwrap();
+                    program.draw_arrays(6);
+                },
+                None => {
+                    self.heatmap_program.add_uniform_mat4("worldViewProjectionMatrix", &Mat4::identity()).unwrap();
+                    self.heatmap_program.use_attribute_vec3_float(&self.position_buffer, "position").unwrap();
+                    self.heatmap_program.use_attribute_vec3_float(&self.color_buffer, "color").unwrap();
+                    self.heatmap_program.draw_arrays(self.buffer_size);
+                },
+            }
+
+            self.contour_program.add_uniform_mat4("worldViewProjectionMatrix", &Mat4::identity()).unwrap();
+            if self.contour_buffer_size > 0 {
+                self.contour_program.use_attribute_vec3_float(&self.contour_buffer, "position").unwrap();
+                self.contour_program.add_uniform_vec4("color", &vec4(0.15, 0.15, 0.15, 1.0)).unwrap();
+                self.contour_program.draw_arrays(self.contour_buffer_size);
+            }
+
+            if self.label_buffer_size > 0 {
+                self.contour_program.use_attribute_vec3_float(&self.label_background_buffer, "position").unwrap();
+                self.contour_program.add_uniform_vec4("color", &vec4(0.95, 0.95, 0.95, 1.0)).unwrap();
+                self.contour_program.draw_arrays(self.label_background_buffer_size);
+
+                self.contour_program.use_attribute_vec3_float(&self.label_buffer, "position").unwrap();
+                self.contour_program.add_uniform_vec4("color", &vec4(0.15, 0.15, 0.15, 1.0)).unwrap();
+                self.contour_program.draw_arrays_mode(self.label_buffer_size, consts::LINES);
+            }
+        }).unwrap();
+    }
+}
@@ -1,4 +1,6 @@
-use crate::expression::{Operation};
+use crate::expression::{Operation, BatchedBinary};
+use crate::number_literal::NumberLiteral;
+use crate::definitions::Definition;
 
 // Associativity of a binary operator
 #[derive(PartialEq, Eq, Clone, Copy)]
@@ -11,9 +13,10 @@ pub enum Assoc {
 pub type Prec = u32;
 
 // Description of an operator supported
-// For now we support three types of operators:
+// For now we support four types of operators:
 // - Unary operators, are written in prefix form
 // - Binary operators, are written in infix form
+// - Functions of several arguments, are written as f(x, y)
 // - Constants,
 pub struct UnaryOp<Number: Clone + From<f64>> {
     pub symbol: &'static str,
@@ -32,12 +35,32 @@ pub struct BinaryOp<Number: Clone + From<f64>> {
     pub prec: Prec,
 
     pub semantics: fn (Number, Number) -> Number,
+    // The operation on whole arrays, for the operations worth vectorizing
+    pub batched: Option<BatchedBinary<Number>>,
 }
 
 
 impl<Number: Clone + From<f64>> BinaryOp<Number> {
     pub fn operation(&self) -> Operation<Number> {
-        Operation::BinaryOperation(self.semantics)
+        match self.batched {
+            Some(batched) => Operation::BatchedBinaryOperation(self.semantics, batched),
+            None => Operation::BinaryOperation(self.semantics),
+        }
+    }
+}
+
+// Functions of several arguments, written as f(x, y)
+pub struct FunctionOp<Number: Clone + From<f64>> {
+    pub symbol: &'static str,
+    pub arity: usize,
+
+    pub semantics: fn (&[Number]) -> Number,
+}
+
+
+impl<Number: Clone + From<f64>> FunctionOp<Number> {
+    pub fn operation(&self) -> Operation<Number> {
+        Operation::Function(self.semantics, self.arity)
     }
 }
 
@@ -61,9 +84,20 @@ pub trait Semantics {
 
     fn lookup_binary(&self, name: &str) -> Option<&BinaryOp<Self::Number>>;
     fn lookup_unary(&self, name: &str) -> Option<&UnaryOp<Self::Number>>;
+    fn lookup_function(&self, name: &str) -> Option<&FunctionOp<Self::Number>>;
     fn lookup_const(&self, name: &str) -> Option<&ConstantOp<Self::Number>>;
+    // Functions defined by the user, operator tables have none
+    fn lookup_definition(&self, _name: &str) -> Option<&Definition> {
+        None
+    }
 
-    fn number(&self, num: f64) -> Operation<Self::Number>;
+    fn number(&self, literal: NumberLiteral) -> Operation<Self::Number>;
+    // The value of a piecewise function where none of the conditions holds
+    fn undefined(&self) -> Operation<Self::Number>;
     fn xvar(&self) -> Operation<Self::Number>;
     fn yvar(&self) -> Operation<Self::Number>;
+    // The complex variable z = x + iy, only complex numbers have it
+    fn zvar(&self) -> Option<Operation<Self::Number>> {
+        None
+    }
 }
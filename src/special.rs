@@ -0,0 +1,718 @@
+// Floating point approximations of special functions.
+// Every function comes with a generous bound of its approximation error,
+// used to build conservative interval enclosures in the interval module.
+use std::f64::consts::PI;
+
+pub const SQRT_2PI: f64 = 2.5066282746310002;
+const FRAC_1_SQRT_PI: f64 = 0.5641895835477563;
+
+// Bounds of the approximation errors, a few orders of magnitude above the measured ones
+pub const GAMMA_RELATIVE_ERROR: f64 = 1e-11;
+pub const LGAMMA_ERROR: f64 = 1e-12;
+pub const DIGAMMA_ERROR: f64 = 1e-12;
+pub const ERF_RELATIVE_ERROR: f64 = 1e-13;
+// erfc is computed as 1 - erf up to 3, where it is about 2e-5
+pub const ERFC_RELATIVE_ERROR: f64 = 1e-10;
+pub const BESSEL_ERROR: f64 = 1e-10;
+pub const AIRY_ERROR: f64 = 1e-8;
+pub const ZETA_RELATIVE_ERROR: f64 = 1e-12;
+pub const LAMBERT_W_RELATIVE_ERROR: f64 = 1e-12;
+// Near the branch point W is very steep, so the rounding of -1/e gives a bigger error
+pub const LAMBERT_W_BRANCH_ERROR: f64 = 1e-7;
+
+// Point where gamma (and lgamma) has its minimum on the positive numbers
+pub const GAMMA_MINIMUM: f64 = 1.4616321449683623;
+// First zero of the derivative of Ai, the function is decreasing from there on
+pub const AIRY_DERIVATIVE_ZERO: f64 = -1.0187929716474710;
+// Local maximum of zeta on the negative numbers, zeta is decreasing from there to the pole in 1
+pub const ZETA_MAXIMUM: f64 = -2.7172628292;
+
+pub const LANCZOS_G: f64 = 7.0;
+pub const LANCZOS_COEFFICIENTS: [f64; 9] = [
+    0.99999999999980993,
+    676.5203681218851,
+    -1259.1392167224028,
+    771.32342877765313,
+    -176.61502916214059,
+    12.507343278686905,
+    -0.13857109526572012,
+    9.9843695780195716e-6,
+    1.5056327351493116e-7,
+];
+
+// sin(pi x), with the argument reduced exactly so that it is accurate for big x,
+// and near the odd integers, where sin(pi (1 - r)) = sin(pi r) is computed close to 0
+pub fn sin_pi(x: f64) -> f64 {
+    let reduced = x - 2.0 * (x / 2.0).round();
+    let reduced = if reduced > 0.5 {
+        1.0 - reduced
+    } else if reduced < -0.5 {
+        -1.0 - reduced
+    } else {
+        reduced
+    };
+    (PI * reduced).sin()
+}
+
+// The sum of the Lanczos approximation, for x >= 0.5
+fn lanczos_sum(x: f64) -> f64 {
+    let x = x - 1.0;
+    let mut sum = LANCZOS_COEFFICIENTS[0];
+    for (i, coefficient) in LANCZOS_COEFFICIENTS.iter().enumerate().skip(1) {
+        sum += coefficient / (x + i as f64);
+    }
+    sum
+}
+
+pub fn gamma(x: f64) -> f64 {
+    if x == 0.0 {
+        // Infinity with the sign of the zero
+        1.0 / x
+    } else if x < 0.5 {
+        if x == x.floor() {
+            // Poles in the negative integers
+            return std::f64::NAN;
+        }
+        // Reflection formula
+        PI / (sin_pi(x) * gamma(1.0 - x))
+    } else if x > 171.7 {
+        std::f64::INFINITY
+    } else {
+        let t = x - 0.5 + LANCZOS_G;
+        // The power is split in two to avoid overflowing before the exponential brings it down
+        let power = t.powf((x - 0.5) / 2.0);
+        SQRT_2PI * (power * (-t).exp()) * power * lanczos_sum(x)
+    }
+}
+
+// Logarithm of the absolute value of gamma
+pub fn lgamma(x: f64) -> f64 {
+    if x < 0.5 {
+        if x == x.floor() {
+            return std::f64::INFINITY;
+        }
+        (PI / sin_pi(x).abs()).ln() - lgamma(1.0 - x)
+    } else if x == std::f64::INFINITY {
+        x
+    } else {
+        let t = x - 0.5 + LANCZOS_G;
+        0.5 * (2.0 * PI).ln() + (x - 0.5) * t.ln() - t + lanczos_sum(x).ln()
+    }
+}
+
+// Derivative of lgamma
+pub fn digamma(x: f64) -> f64 {
+    if x == 0.0 {
+        return -1.0 / x;
+    }
+    if x < 0.5 {
+        if x == x.floor() {
+            return std::f64::NAN;
+        }
+        // Reflection formula, tan has period pi so the argument is reduced to [-pi/2, pi/2]
+        return digamma(1.0 - x) - PI / (PI * (x - x.round())).tan();
+    }
+
+    // Shift the argument up with the recurrence, then use the asymptotic expansion
+    let mut x = x;
+    let mut result = 0.0;
+    while x < 10.0 {
+        result -= 1.0 / x;
+        x += 1.0;
+    }
+    let inverse_square = 1.0 / (x * x);
+    let series = inverse_square * (1.0 / 12.0 - inverse_square * (1.0 / 120.0 - inverse_square * (1.0 / 252.0
+        - inverse_square * (1.0 / 240.0 - inverse_square * (1.0 / 132.0 - inverse_square * 691.0 / 32760.0)))));
+    result + x.ln() - 0.5 / x - series
+}
+
+// Limit between the power series and the continued fraction of erf and erfc
+const ERF_SERIES_LIMIT: f64 = 3.0;
+
+// erf(x) = 2/sqrt(pi) e^(-x^2) sum 2^n x^(2n+1) / (1 3 5 ... (2n+1)), all the terms are positive
+fn erf_series(x: f64) -> f64 {
+    let square = x * x;
+    let mut term = x;
+    let mut sum = x;
+    for n in 1..200 {
+        term *= 2.0 * square / (2 * n + 1) as f64;
+        sum += term;
+        if term <= sum * 1e-17 {
+            break;
+        }
+    }
+    2.0 * FRAC_1_SQRT_PI * (-square).exp() * sum
+}
+
+// erfc(x) = e^(-x^2)/sqrt(pi) / (x + (1/2) / (x + 1 / (x + (3/2) / (x + ...)))), for big x
+fn erfc_continued_fraction(x: f64) -> f64 {
+    let mut fraction = x;
+    for k in (1..200).rev() {
+        fraction = x + (k as f64 / 2.0) / fraction;
+    }
+    FRAC_1_SQRT_PI * (-x * x).exp() / fraction
+}
+
+pub fn erf(x: f64) -> f64 {
+    if x < 0.0 {
+        -erf(-x)
+    } else if x < ERF_SERIES_LIMIT {
+        erf_series(x)
+    } else {
+        1.0 - erfc_continued_fraction(x)
+    }
+}
+
+pub fn erfc(x: f64) -> f64 {
+    if x < 0.0 {
+        2.0 - erfc(-x)
+    } else if x < ERF_SERIES_LIMIT {
+        1.0 - erf_series(x)
+    } else {
+        erfc_continued_fraction(x)
+    }
+}
+
+// Higher orders would make Miller's algorithm too slow where the asymptotic expansion is not accurate
+const MAX_BESSEL_ORDER: f64 = 200.0;
+
+// Bessel function of the first kind of integer order n
+pub fn besselj(n: f64, x: f64) -> f64 {
+    if n.fract() != 0.0 || n.abs() > MAX_BESSEL_ORDER || x.is_nan() {
+        return std::f64::NAN;
+    }
+    if x.is_infinite() {
+        return 0.0;
+    }
+
+    // J_(-n)(x) = (-1)^n J_n(x) and J_n(-x) = (-1)^n J_n(x)
+    let order = n.abs();
+    let odd = order % 2.0 == 1.0;
+    let sign = if odd && ((n < 0.0) != (x < 0.0)) { -1.0 } else { 1.0 };
+    let x = x.abs();
+
+    if x == 0.0 {
+        return if order == 0.0 { 1.0 } else { 0.0 };
+    }
+
+    if x >= 1000.0 && x >= 25.0 * order * order {
+        sign * besselj_asymptotic(order, x)
+    } else {
+        sign * besselj_miller(order as usize, x)
+    }
+}
+
+// Miller's algorithm: use the recurrence J_(k-1)(x) = 2k/x J_k(x) - J_(k+1)(x) backwards
+// starting from an order high enough that the values are negligible,
+// then normalize with J_0(x) + 2 J_2(x) + 2 J_4(x) + ... = 1
+fn besselj_miller(order: usize, x: f64) -> f64 {
+    let size = order.max(x as usize);
+    let start = 2 * ((size + 20 + (40.0 * size as f64).sqrt() as usize) / 2);
+
+    let mut next = 0.0;
+    let mut current = 1e-30;
+    let mut result = 0.0;
+    let mut normalization = 0.0;
+    for k in (1..=start).rev() {
+        if k == order {
+            result = current;
+        }
+        if k % 2 == 0 {
+            normalization += 2.0 * current;
+        }
+
+        let previous = 2.0 * k as f64 / x * current - next;
+        next = current;
+        current = previous;
+
+        // Rescale everything to avoid overflowing
+        if current.abs() > 1e250 {
+            current *= 1e-250;
+            next *= 1e-250;
+            result *= 1e-250;
+            normalization *= 1e-250;
+        }
+    }
+    if order == 0 {
+        result = current;
+    }
+    normalization += current;
+
+    result / normalization
+}
+
+// The bound grows with x, since for big x the phase of the asymptotic expansion is only known up to its rounding
+pub fn besselj_error(x: f64) -> f64 {
+    BESSEL_ERROR + x.abs() * 1e-15
+}
+
+// Hankel asymptotic expansion for x much bigger than the order
+fn besselj_asymptotic(order: f64, x: f64) -> f64 {
+    let mu = 4.0 * order * order;
+    let mut p = 1.0;
+    let mut q = 0.0;
+    let mut term = 1.0;
+    for k in 1..30 {
+        let odd = (2 * k - 1) as f64;
+        term *= (mu - odd * odd) / (k as f64 * 8.0 * x);
+        if term.abs() < 1e-17 {
+            break;
+        }
+        match k % 4 {
+            1 => q += term,
+            2 => p -= term,
+            3 => q -= term,
+            _ => p += term,
+        }
+    }
+    let phase = x - (order / 2.0 + 0.25) * PI;
+    (2.0 / (PI * x)).sqrt() * (p * phase.cos() - q * phase.sin())
+}
+
+// Ai(0) and -Ai'(0)
+const AIRY_C1: f64 = 0.35502805388781724;
+const AIRY_C2: f64 = 0.25881940379280680;
+// Beyond this the asymptotic expansions are accurate
+const AIRY_SERIES_LIMIT: f64 = 8.0;
+
+// The Airy function Ai and its derivative
+pub fn airy(x: f64) -> (f64, f64) {
+    if x.is_nan() {
+        (x, x)
+    } else if x.abs() <= AIRY_SERIES_LIMIT {
+        airy_series(x)
+    } else if x > 0.0 {
+        airy_asymptotic_positive(x)
+    } else {
+        airy_asymptotic_negative(-x)
+    }
+}
+
+// Ai(x) = c1 f(x) - c2 g(x), with the Maclaurin series
+// f(x) = sum 3^k (1/3)_k x^(3k) / (3k)!, g(x) = sum 3^k (2/3)_k x^(3k+1) / (3k+1)!
+fn airy_series(x: f64) -> (f64, f64) {
+    let cube = x * x * x;
+    let (mut f_term, mut g_term) = (1.0, x);
+    let (mut f_derivative_term, mut g_derivative_term) = (x * x / 2.0, 1.0);
+    let (mut f, mut g) = (f_term, g_term);
+    let (mut f_derivative, mut g_derivative) = (f_derivative_term, g_derivative_term);
+
+    for k in 1..200 {
+        let k = k as f64;
+        f_term *= cube / ((3.0 * k - 1.0) * (3.0 * k));
+        g_term *= cube / ((3.0 * k) * (3.0 * k + 1.0));
+        g_derivative_term *= cube / ((3.0 * k - 2.0) * (3.0 * k));
+        f_derivative_term *= cube / ((3.0 * k + 2.0) * (3.0 * k));
+        f += f_term;
+        g += g_term;
+        f_derivative += f_derivative_term;
+        g_derivative += g_derivative_term;
+        if f_term.abs() + g_term.abs() + f_derivative_term.abs() + g_derivative_term.abs() < 1e-18 {
+            break;
+        }
+    }
+
+    (AIRY_C1 * f - AIRY_C2 * g, AIRY_C1 * f_derivative - AIRY_C2 * g_derivative)
+}
+
+// The coefficients u_k and v_k of the asymptotic expansions of Ai and Ai', up to the given accuracy
+fn airy_asymptotic_terms(zeta: f64) -> Vec<(f64, f64)> {
+    let mut terms = vec![(1.0, 1.0)];
+    let mut u = 1.0;
+    for k in 1..50 {
+        let k = k as f64;
+        u *= (6.0 * k - 5.0) * (6.0 * k - 3.0) * (6.0 * k - 1.0) / ((2.0 * k - 1.0) * 216.0 * k * zeta);
+        let v = -(6.0 * k + 1.0) / (6.0 * k - 1.0) * u;
+        if u.abs() < 1e-17 {
+            break;
+        }
+        terms.push((u, v));
+    }
+    terms
+}
+
+fn airy_asymptotic_positive(x: f64) -> (f64, f64) {
+    let zeta = 2.0 / 3.0 * x * x.sqrt();
+    let (mut u_sum, mut v_sum) = (0.0, 0.0);
+    for (k, (u, v)) in airy_asymptotic_terms(zeta).into_iter().enumerate() {
+        let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+        u_sum += sign * u;
+        v_sum += sign * v;
+    }
+    let scale = 0.5 * FRAC_1_SQRT_PI * (-zeta).exp();
+    let root = x.sqrt().sqrt();
+    (scale / root * u_sum, -scale * root * v_sum)
+}
+
+// Ai(-x) for big x
+fn airy_asymptotic_negative(x: f64) -> (f64, f64) {
+    let zeta = 2.0 / 3.0 * x * x.sqrt();
+    let (mut u_even, mut u_odd, mut v_even, mut v_odd) = (0.0, 0.0, 0.0, 0.0);
+    for (k, (u, v)) in airy_asymptotic_terms(zeta).into_iter().enumerate() {
+        let sign = if (k / 2) % 2 == 0 { 1.0 } else { -1.0 };
+        if k % 2 == 0 {
+            u_even += sign * u;
+            v_even += sign * v;
+        } else {
+            u_odd += sign * u;
+            v_odd += sign * v;
+        }
+    }
+    let phase = zeta - PI / 4.0;
+    let root = x.sqrt().sqrt();
+    let value = FRAC_1_SQRT_PI / root * (phase.cos() * u_even + phase.sin() * u_odd);
+    let derivative = FRAC_1_SQRT_PI * root * (phase.sin() * v_even - phase.cos() * v_odd);
+    (value, derivative)
+}
+
+// Same as besselj_error, the phase of the expansion for negative x is 2/3 |x|^(3/2)
+pub fn airy_error(x: f64) -> f64 {
+    AIRY_ERROR + x.abs().powf(1.5) * 1e-15
+}
+
+// Bound of |Ai'| on x <= AIRY_DERIVATIVE_ZERO, where x is the point farthest from the origin.
+// Asymptotically |Ai'(x)| is bounded by |x|^(1/4)/sqrt(pi)
+pub fn airy_slope_bound(x: f64) -> f64 {
+    0.6 * (1.0 + x.abs()).sqrt().sqrt()
+}
+
+// Number of terms of Borwein's algorithm for the eta function, the error is about 5.8^(-n)
+pub const BORWEIN_TERMS: usize = 30;
+
+// Riemann zeta function
+pub fn zeta(s: f64) -> f64 {
+    if s == 1.0 || s.is_nan() {
+        std::f64::NAN
+    } else if s == std::f64::INFINITY {
+        1.0
+    } else if s < 0.0 {
+        if s % 2.0 == 0.0 {
+            // Trivial zeros
+            return 0.0;
+        }
+        // Functional equation
+        let reflected = 1.0 - s;
+        2.0f64.powf(s) * PI.powf(s - 1.0) * sin_pi(s / 2.0) * gamma(reflected) * zeta(reflected)
+    } else {
+        // zeta(s) = eta(s) / (1 - 2^(1-s)), where eta is the alternating zeta function
+        eta(s) / -((1.0 - s) * std::f64::consts::LN_2).exp_m1()
+    }
+}
+
+// Borwein's algorithm for the alternating zeta function eta(s) = sum (-1)^(k-1) / k^s
+fn eta(s: f64) -> f64 {
+    let n = BORWEIN_TERMS;
+    let d = borwein_coefficients();
+
+    let mut result = 0.0;
+    for k in 0..n {
+        let sign = if k % 2 == 0 { 1.0 } else { -1.0 };
+        result += sign * (d[k] - d[n]) / ((k + 1) as f64).powf(s);
+    }
+    -result / d[n]
+}
+
+// The coefficients d_k = n sum_(i=0)^k (n+i-1)! 4^i / ((n-i)! (2i)!) of Borwein's algorithm, for k from 0 to n
+pub fn borwein_coefficients() -> Vec<f64> {
+    let n = BORWEIN_TERMS;
+    let mut d = Vec::with_capacity(n + 1);
+    let mut term = 1.0 / n as f64;
+    let mut sum = term;
+    d.push(n as f64 * sum);
+    for i in 1..=n {
+        let i = i as f64;
+        let n = n as f64;
+        term *= 4.0 * (n + i - 1.0) * (n - i + 1.0) / ((2.0 * i - 1.0) * (2.0 * i));
+        sum += term;
+        d.push(n * sum);
+    }
+    d
+}
+
+// Principal branch of the Lambert W function, the inverse of w e^w
+pub fn lambert_w(x: f64) -> f64 {
+    let branch_point = -(-1.0f64).exp();
+    if x.is_nan() || x < branch_point {
+        return std::f64::NAN;
+    }
+    if x == 0.0 || x == std::f64::INFINITY {
+        return x;
+    }
+
+    if x > 3.0 {
+        // Newton's method on w + ln(w) = ln(x), which doesn't overflow
+        let log = x.ln();
+        let mut w = log - log.ln();
+        for _ in 0..50 {
+            let next = w - (w + w.ln() - log) * w / (w + 1.0);
+            let converged = (next - w).abs() <= 1e-16 * next.abs();
+            w = next;
+            if converged {
+                break;
+            }
+        }
+        return w;
+    }
+
+    let mut w = if x < -0.25 {
+        // Series around the branch point
+        let p = (2.0 * (std::f64::consts::E * x + 1.0)).max(0.0).sqrt();
+        -1.0 + p - p * p / 3.0 + 11.0 / 72.0 * p * p * p
+    } else {
+        (1.0 + x).ln()
+    };
+
+    // Halley's method on w e^w - x = 0
+    for _ in 0..50 {
+        let exp = w.exp();
+        let residual = w * exp - x;
+        let w_plus_one = w + 1.0;
+        if w_plus_one == 0.0 || residual == 0.0 {
+            break;
+        }
+        let next = w - residual / (exp * w_plus_one - (w + 2.0) * residual / (2.0 * w_plus_one));
+        let converged = (next - w).abs() <= 1e-16 * (1.0 + next.abs());
+        w = next;
+        if converged {
+            break;
+        }
+    }
+    w
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interval;
+    use honestintervals::IntervalSet;
+
+    // Reference values computed with mpmath at 40 digits, from the exact values of the arguments
+    const GAMMA: [(f64, f64); 15] = [
+        (0.5, 1.772453850905516),
+        (1.0, 1.0),
+        (5.0, 24.0),
+        (10.5, 1133278.3889487856),
+        (100.3, 3.711481867182677e+156),
+        (170.5, 5.56209241456e+305),
+        (1e-08, 99999999.42278434),
+        (0.0001, 9999.422883231624),
+        (-0.5, -3.544907701811032),
+        (-2.5, -0.9453087204829419),
+        (-0.99999999, -99999999.92030843),
+        (-3.0000001, 1666666.4600413917),
+        (-10.5, -2.640121820547716e-07),
+        (-150.5, -4.478447658150641e-264),
+        (1.4616321449683622, 0.8856031944108887),
+    ];
+
+    const LGAMMA: [(f64, f64); 11] = [
+        (0.5, 0.5723649429247001),
+        (3.0, 0.6931471805599453),
+        (100.0, 359.1342053695754),
+        (100000.0, 1051287.7089736569),
+        (1e-10, 23.025850929882736),
+        (-0.5, 1.2655121234846454),
+        (-2.5, -0.056243716497674054),
+        (-0.99999999, 18.42068074315545),
+        (1.0, 0.0),
+        (2.0, 0.0),
+        (-100.5, -364.90096830942736),
+    ];
+
+    const DIGAMMA: [(f64, f64); 9] = [
+        (0.5, -1.9635100260214235),
+        (1.0, -0.5772156649015329),
+        (10.0, 2.251752589066721),
+        (1e-08, -100000000.57721564),
+        (-0.5, 0.03648997397857652),
+        (-0.99999999, -99999999.07473971),
+        (-2.5, 1.103156640645243),
+        (-3.0000001, 10000001.272483157),
+        (1000.0, 6.907255195648812),
+    ];
+
+    const ERF: [(f64, f64); 9] = [
+        (1e-10, 1.1283791670955126e-10),
+        (0.1, 0.1124629160182849),
+        (0.5, 0.5204998778130465),
+        (1.0, 0.8427007929497149),
+        (2.0, 0.9953222650189527),
+        (2.9, 0.9999589021219005),
+        (3.1, 0.9999883513426328),
+        (5.0, 0.9999999999984626),
+        (-1.0, -0.8427007929497149),
+    ];
+
+    const ERFC: [(f64, f64); 9] = [
+        (0.1, 0.887537083981715),
+        (1.0, 0.15729920705028513),
+        (2.9, 4.109787809945886e-05),
+        (3.1, 1.1648657367199589e-05),
+        (5.0, 1.537459794428035e-12),
+        (10.0, 2.088487583762545e-45),
+        (26.0, 5.663192408856143e-296),
+        (-1.0, 1.8427007929497148),
+        (-3.0, 1.9999779095030015),
+    ];
+
+    // order, x and J_order(x)
+    const BESSELJ: [(f64, f64, f64); 13] = [
+        (0.0, 1.0, 0.7651976865579666),
+        (1.0, 1.0, 0.4400505857449335),
+        (0.0, 10.0, -0.24593576445134835),
+        (2.0, 5.0, 0.046565116277752214),
+        (1.0, 10.0, 0.04347274616886144),
+        (5.0, 1.0, 0.00024975773021123444),
+        (-3.0, 2.0, -0.12894324947440206),
+        (3.0, -2.0, -0.12894324947440206),
+        (0.0, 50.0, 0.055812327669251816),
+        (20.0, 3.0, 1.2275946737992987e-15),
+        (10.0, 1000.0, -0.02452062230603656),
+        (1.0, 1500.0, -0.01287620247319177),
+        (0.0, 10000.0, -0.0070961603533888015),
+    ];
+
+    // x, Ai(x) and Ai'(x)
+    const AIRY: [(f64, f64, f64); 11] = [
+        (0.0, 0.3550280538878172, -0.2588194037928068),
+        (1.0, 0.13529241631288141, -0.1591474412967932),
+        (-1.0, 0.5355608832923521, -0.01016056711664521),
+        (2.5, 0.01572592338047049, -0.026250881035903232),
+        (-5.0, 0.35076100902411433, 0.32719281855444315),
+        (7.9, 6.239640097283934e-08, -1.7729958329430335e-07),
+        (8.1, 3.5224356235735714e-08, -1.0130972032660844e-07),
+        (10.0, 1.1047532552898686e-10, -3.5206336767389237e-10),
+        (-8.1, -0.14290814709358113, 0.856218586328625),
+        (-20.0, -0.1764061270779847, 0.8928628567364713),
+        (-100.0, 0.1767533932395529, -0.2422970316605838),
+    ];
+
+    const ZETA: [(f64, f64); 14] = [
+        (2.0, 1.6449340668482264),
+        (3.0, 1.2020569031595942),
+        (0.5, -1.4603545088095868),
+        (0.0, -0.5),
+        (-1.0, -0.08333333333333333),
+        (-3.5, 0.004441011335479432),
+        (-10.5, 0.011146122473942813),
+        (1.5, 2.612375348685488),
+        (1.000001, 1000000.5772980044),
+        (0.999999, -999999.4227556522),
+        (20.0, 1.0000009539620338),
+        (50.0, 1.0000000000000009),
+        (-20.5, -108.21747505877606),
+        (-2.0, 0.0),
+    ];
+
+    // The arguments just above -1/e are -1/e + 1e-12 and -1/e + 1e-6
+    const LAMBERT_W: [(f64, f64); 14] = [
+        (0.0, 0.0),
+        (1.0, 0.5671432904097838),
+        (2.718281828459045, 1.0),
+        (10.0, 1.7455280027406994),
+        (10000000000.0, 20.028685413304952),
+        (1e+300, 684.2472086297608),
+        (-0.2, -0.25917110181907377),
+        (-0.3, -0.4894022271802149),
+        (-0.36, -0.8060843159708176),
+        (-0.36787944117044236, -0.9999976683981106),
+        (-0.36787844117144236, -0.9976701662720535),
+        (2.9, 1.0326156691660666),
+        (3.1, 1.0667683070416147),
+        (-1e-10, -1.0000000001000001e-10),
+    ];
+
+    fn assert_within(function: &str, x: f64, value: f64, expected: f64, error: f64) {
+        assert!((value - expected).abs() <= error, "{}({}) = {}, expected {} within {}", function, x, value, expected, error);
+    }
+
+    fn assert_encloses(function: &str, x: f64, enclosure: IntervalSet<f64>, expected: f64) {
+        let intervals = interval::intervals(&enclosure);
+        assert!(intervals.iter().any(|(start, end)| *start <= expected && expected <= *end),
+                "{}({}) = {:?} doesn't contain {}", function, x, intervals, expected);
+    }
+
+    #[test]
+    fn gamma_is_accurate() {
+        for &(x, expected) in GAMMA.iter() {
+            assert_within("gamma", x, gamma(x), expected, expected.abs() * GAMMA_RELATIVE_ERROR);
+            assert_encloses("gamma", x, interval::gamma(IntervalSet::singleton(x)), expected);
+        }
+        assert!(gamma(-1.0).is_nan() && gamma(-20.0).is_nan());
+        assert_eq!(gamma(0.0), f64::INFINITY);
+        assert_eq!(gamma(-0.0), f64::NEG_INFINITY);
+        assert_eq!(gamma(172.0), f64::INFINITY);
+    }
+
+    #[test]
+    fn lgamma_is_accurate() {
+        for &(x, expected) in LGAMMA.iter() {
+            assert_within("lgamma", x, lgamma(x), expected, (1.0 + expected.abs()) * LGAMMA_ERROR);
+            assert_encloses("lgamma", x, interval::lgamma(IntervalSet::singleton(x)), expected);
+        }
+        assert_eq!(lgamma(-3.0), f64::INFINITY);
+    }
+
+    #[test]
+    fn digamma_is_accurate() {
+        for &(x, expected) in DIGAMMA.iter() {
+            assert_within("digamma", x, digamma(x), expected, (1.0 + expected.abs()) * DIGAMMA_ERROR);
+            assert_encloses("digamma", x, interval::digamma(IntervalSet::singleton(x)), expected);
+        }
+    }
+
+    #[test]
+    fn erf_and_erfc_are_accurate() {
+        for &(x, expected) in ERF.iter() {
+            assert_within("erf", x, erf(x), expected, expected.abs() * ERF_RELATIVE_ERROR);
+            assert_encloses("erf", x, interval::erf(IntervalSet::singleton(x)), expected);
+        }
+        for &(x, expected) in ERFC.iter() {
+            assert_within("erfc", x, erfc(x), expected, expected.abs() * ERFC_RELATIVE_ERROR);
+            assert_encloses("erfc", x, interval::erfc(IntervalSet::singleton(x)), expected);
+        }
+    }
+
+    #[test]
+    fn besselj_is_accurate() {
+        for &(order, x, expected) in BESSELJ.iter() {
+            assert_within("besselj", x, besselj(order, x), expected, besselj_error(x));
+            assert_encloses("besselj", x, interval::besselj(IntervalSet::singleton(order), IntervalSet::singleton(x)), expected);
+        }
+        assert!(besselj(0.5, 1.0).is_nan());
+        assert_eq!(besselj(3.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn airy_is_accurate() {
+        for &(x, expected, expected_derivative) in AIRY.iter() {
+            let (value, derivative) = airy(x);
+            assert_within("airy", x, value, expected, airy_error(x));
+            assert_within("airy derivative", x, derivative, expected_derivative, airy_error(x));
+            assert_encloses("airy", x, interval::airy(IntervalSet::singleton(x)), expected);
+            assert_encloses("airy derivative", x, interval::airy_derivative(IntervalSet::singleton(x)), expected_derivative);
+        }
+    }
+
+    #[test]
+    fn zeta_is_accurate() {
+        for &(s, expected) in ZETA.iter() {
+            assert_within("zeta", s, zeta(s), expected, expected.abs() * ZETA_RELATIVE_ERROR);
+            assert_encloses("zeta", s, interval::zeta(IntervalSet::singleton(s)), expected);
+        }
+        assert!(zeta(1.0).is_nan());
+    }
+
+    #[test]
+    fn lambert_w_is_accurate() {
+        for &(x, expected) in LAMBERT_W.iter() {
+            let branch_error = if expected + 1.0 < 1e-3 { LAMBERT_W_BRANCH_ERROR } else { 0.0 };
+            assert_within("W", x, lambert_w(x), expected, expected.abs() * LAMBERT_W_RELATIVE_ERROR + branch_error);
+            assert_encloses("W", x, interval::lambert_w(IntervalSet::singleton(x)), expected);
+        }
+        // the rounding of -1/e is a bit below the branch point, where W is -1
+        let branch_point = -(-1.0f64).exp();
+        assert_within("W", branch_point, lambert_w(branch_point), -1.0, LAMBERT_W_BRANCH_ERROR);
+        assert!(lambert_w(-0.37).is_nan());
+    }
+}
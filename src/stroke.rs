@@ -0,0 +1,228 @@
+use crate::parser;
+
+// Lines of constant width on the screen, drawn as triangles with miter joins.
+// Every vertex also stores its distance from the middle of the line, so that the
+// fragment shader can fade the edges of the line to smooth them
+
+// Pixels added on each side of the lines, where their edges fade out
+const ANTIALIASING: f32 = 1.0;
+// Longest miter join relative to the width of the line, sharper corners are cut
+const MITER_LIMIT: f32 = 3.0;
+
+pub enum LineStyle {
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+impl LineStyle {
+    // Recognize the style written around a function, like dashed(sin(x)) or dotted(x^2 + y^2 = 1),
+    // returning the style and the function inside. Functions without a style are solid
+    pub fn parse(input: &str) -> (LineStyle, &str) {
+        let trimmed = input.trim();
+        for (name, style) in [("dashed", LineStyle::Dashed), ("dotted", LineStyle::Dotted)] {
+            if let Some(inner) = parser::call_argument(trimmed, name) {
+                return (style, inner);
+            }
+        }
+        (LineStyle::Solid, input)
+    }
+
+    // The lengths of the dashes and of the gaps between them, relative to the width of the line
+    fn pattern(&self) -> Option<(f32, f32)> {
+        match self {
+            LineStyle::Solid => None,
+            LineStyle::Dashed => Some((4.0, 2.5)),
+            LineStyle::Dotted => Some((1.0, 1.5)),
+        }
+    }
+}
+
+pub struct Strokes {
+    // pixels to normalized coordinates
+    scale: (f32, f32),
+    pub positions: Vec<f32>,
+    // for every vertex, its distance in pixels from the middle of the line and half the width of the line
+    pub edges: Vec<f32>,
+}
+
+impl Strokes {
+    pub fn new(screen_size: (f32, f32)) -> Strokes {
+        Strokes {
+            scale: (2.0 / screen_size.0, 2.0 / screen_size.1),
+            positions: Vec::new(),
+            edges: Vec::new(),
+        }
+    }
+
+    // Add a line through the points, in normalized coordinates, with the width in pixels
+    pub fn add(&mut self, points: &[(f32, f32)], width: f32, style: &LineStyle) {
+        let mut pixels: Vec<(f32, f32)> = Vec::with_capacity(points.len());
+        for point in points {
+            let pixel = (point.0 / self.scale.0, point.1 / self.scale.1);
+            // consecutive points closer than a fraction of a pixel have no direction between them
+            match pixels.last() {
+                Some(last) if distance(*last, pixel) < 1e-3 => (),
+                _ => pixels.push(pixel),
+            }
+        }
+        if pixels.len() < 2 {
+            return;
+        }
+
+        let (dash, gap) = match style.pattern() {
+            Some((dash, gap)) => (dash * width, gap * width),
+            None => return self.add_stroke(&pixels, width / 2.0),
+        };
+
+        // cut the line in dashes, measuring the length along the line
+        let mut dash_points = vec![pixels[0]];
+        let mut drawing = true;
+        let mut left = dash;
+        for segment in pixels.windows(2) {
+            let (mut start, end) = (segment[0], segment[1]);
+            let mut length = distance(start, end);
+            while length > left {
+                let t = left / length;
+                let point = (start.0 + (end.0 - start.0) * t, start.1 + (end.1 - start.1) * t);
+                // the point ends a dash or starts the next one
+                dash_points.push(point);
+                if drawing {
+                    self.add_stroke(&dash_points, width / 2.0);
+                    dash_points.clear();
+                }
+                drawing = !drawing;
+                start = point;
+                length -= left;
+                left = if drawing { dash } else { gap };
+            }
+            left -= length;
+            if drawing {
+                dash_points.push(end);
+            }
+        }
+        if drawing {
+            self.add_stroke(&dash_points, width / 2.0);
+        }
+    }
+
+    // The triangles of a line through the points, in pixels, with miter joins between its segments
+    fn add_stroke(&mut self, points: &[(f32, f32)], half_width: f32) {
+        if points.len() < 2 {
+            return;
+        }
+        let extent = half_width + ANTIALIASING;
+
+        let normals: Vec<(f32, f32)> = points.windows(2)
+            .map(|segment| {
+                let (dx, dy) = (segment[1].0 - segment[0].0, segment[1].1 - segment[0].1);
+                let length = dx.hypot(dy).max(f32::EPSILON);
+                (-dy / length, dx / length)
+            })
+            .collect();
+
+        // the offsets of the sides of the line from every point
+        let offsets: Vec<(f32, f32)> = (0..points.len())
+            .map(|i| {
+                if i == 0 {
+                    return (normals[0].0 * extent, normals[0].1 * extent);
+                }
+                let (before, after) = (normals[i - 1], normals[i.min(normals.len() - 1)]);
+                let sum = (before.0 + after.0, before.1 + after.1);
+                let length = sum.0.hypot(sum.1);
+                if length < 1e-3 {
+                    // the line turns back on itself
+                    return (after.0 * extent, after.1 * extent);
+                }
+                let miter = (sum.0 / length, sum.1 / length);
+                let scale = (1.0 / (miter.0 * after.0 + miter.1 * after.1)).min(MITER_LIMIT);
+                (miter.0 * extent * scale, miter.1 * extent * scale)
+            })
+            .collect();
+
+        let scale = self.scale;
+        let mut add_vertex = |point: (f32, f32), offset: (f32, f32), side: f32| {
+            self.positions.extend_from_slice(&[(point.0 + offset.0 * side) * scale.0, (point.1 + offset.1 * side) * scale.1, 0.0]);
+            self.edges.extend_from_slice(&[extent * side, half_width, 0.0]);
+        };
+
+        for i in 0..points.len() - 1 {
+            add_vertex(points[i], offsets[i], 1.0);
+            add_vertex(points[i + 1], offsets[i + 1], 1.0);
+            add_vertex(points[i + 1], offsets[i + 1], -1.0);
+
+            add_vertex(points[i], offsets[i], 1.0);
+            add_vertex(points[i + 1], offsets[i + 1], -1.0);
+            add_vertex(points[i], offsets[i], -1.0);
+        }
+    }
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the floats of a stroke of a single segment, two triangles of three coordinates
+    const SEGMENT: usize = 18;
+
+    #[test]
+    fn styles_are_recognized_around_functions() {
+        let (style, inner) = LineStyle::parse(" dashed(sin(x)) ");
+        assert!(matches!(style, LineStyle::Dashed));
+        assert_eq!(inner, "sin(x)");
+
+        let (style, inner) = LineStyle::parse("dotted(x^2 + y^2 = 1)");
+        assert!(matches!(style, LineStyle::Dotted));
+        assert_eq!(inner, "x^2 + y^2 = 1");
+
+        // the style must wrap the whole function
+        for input in ["sin(x)", "dashed(x) + 1", "dashedx"].iter() {
+            let (style, inner) = LineStyle::parse(input);
+            assert!(matches!(style, LineStyle::Solid));
+            assert_eq!(inner, *input);
+        }
+    }
+
+    #[test]
+    fn solid_lines_are_single_strokes() {
+        let mut strokes = Strokes::new((100.0, 100.0));
+        strokes.add(&[(-0.5, 0.0), (0.0, 0.0), (0.5, 0.0)], 2.0, &LineStyle::Solid);
+        assert_eq!(strokes.positions.len(), 2 * SEGMENT);
+        assert_eq!(strokes.edges.len(), strokes.positions.len());
+
+        // points closer than a pixel are merged, a single point has no line
+        let mut strokes = Strokes::new((100.0, 100.0));
+        strokes.add(&[(0.0, 0.0), (0.0001, 0.0)], 2.0, &LineStyle::Solid);
+        assert!(strokes.positions.is_empty());
+    }
+
+    #[test]
+    fn dashes_are_split_along_the_line() {
+        // a line 50 pixels long, with dashes 8 pixels long and gaps of 5
+        let mut strokes = Strokes::new((100.0, 100.0));
+        strokes.add(&[(-0.5, 0.0), (0.5, 0.0)], 2.0, &LineStyle::Dashed);
+        assert_eq!(strokes.positions.len(), 4 * SEGMENT);
+
+        let dashes: Vec<(f32, f32)> = strokes.positions.chunks(SEGMENT)
+            .map(|dash| {
+                let xs = dash.iter().step_by(3);
+                (xs.clone().cloned().fold(f32::INFINITY, f32::min), xs.cloned().fold(f32::NEG_INFINITY, f32::max))
+            })
+            .collect();
+        for (dash, start) in dashes.iter().zip([0.0, 13.0, 26.0, 39.0].iter()) {
+            // in normalized coordinates, the sides of a straight line only widen it vertically
+            assert!((dash.0 - (start - 25.0) * 0.02).abs() < 1e-4, "{:?}", dashes);
+            assert!((dash.1 - (start + 8.0 - 25.0) * 0.02).abs() < 1e-4, "{:?}", dashes);
+        }
+
+        // the first dash goes on through the corner of the line, 2 pixels after its start
+        let mut strokes = Strokes::new((100.0, 100.0));
+        strokes.add(&[(-0.5, 0.0), (-0.46, 0.0), (-0.46, 0.5)], 2.0, &LineStyle::Dashed);
+        let first_dash = &strokes.positions[..2 * SEGMENT];
+        assert!(first_dash.iter().skip(1).step_by(3).any(|y| (y - 0.12).abs() < 1e-4));
+    }
+}
@@ -0,0 +1,55 @@
+// Numbers written with line segments, like on a seven-segment display, to label plots.
+// Only digits, '-' and '.' are drawn, other characters are left blank
+
+// The segments go from (x, y) to (x, y) in a box 1 wide and 2 high, with the origin at the bottom left corner
+const SEGMENTS: [((f32, f32), (f32, f32)); 7] = [
+    ((0.0, 2.0), (1.0, 2.0)),
+    ((1.0, 2.0), (1.0, 1.0)),
+    ((1.0, 1.0), (1.0, 0.0)),
+    ((0.0, 0.0), (1.0, 0.0)),
+    ((0.0, 0.0), (0.0, 1.0)),
+    ((0.0, 1.0), (0.0, 2.0)),
+    ((0.0, 1.0), (1.0, 1.0)),
+];
+
+// The segments making each digit, one bit per segment
+const DIGITS: [u8; 10] = [0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07, 0x7f, 0x6f];
+
+// Horizontal space taken by a character, relative to the height of the text
+fn advance(c: char) -> f32 {
+    if c == '.' { 0.25 } else { 0.75 }
+}
+
+// Width in pixels of the text written with the given height in pixels
+pub fn width(text: &str, height: f32) -> f32 {
+    (text.chars().map(advance).sum::<f32>() - 0.25).max(0.0) * height
+}
+
+// Add the segments of the text to lines, to be drawn as LINES.
+// The position of the bottom left corner is in normalized coordinates, the height is in pixels
+pub fn add_text(lines: &mut Vec<f32>, text: &str, position: (f32, f32, f32), height: f32, screen_size: (usize, usize)) {
+    // pixels to normalized coordinates
+    let unit = (height / screen_size.0 as f32, height / screen_size.1 as f32);
+
+    let mut x = position.0;
+    for c in text.chars() {
+        let segments = match c {
+            '0'..='9' => DIGITS[c as usize - '0' as usize],
+            '-' => 0x40,
+            _ => 0,
+        };
+
+        for (i, (start, end)) in SEGMENTS.iter().enumerate() {
+            if segments & (1 << i) != 0 {
+                lines.extend_from_slice(&[x + start.0 * unit.0, position.1 + start.1 * unit.1, position.2,
+                                          x + end.0 * unit.0, position.1 + end.1 * unit.1, position.2]);
+            }
+        }
+        if c == '.' {
+            // a short vertical stroke on the baseline
+            lines.extend_from_slice(&[x, position.1, position.2, x, position.1 + 0.2 * unit.1, position.2]);
+        }
+
+        x += 2.0 * advance(c) * unit.0;
+    }
+}
@@ -0,0 +1,370 @@
+use crate::expression::{Expression, ExprType};
+use crate::plot_generator2d::{self, Rectangle};
+use honestintervals::IntervalSet;
+use std::collections::HashMap;
+
+// The plane is split in square tiles of side 2^level, the level is chosen so that the resolution steps
+// of a tile are between half and one pixel wide. Tiles are evaluated once and reused while panning,
+// and when zooming the tiles of the previous level are drawn in place of those still missing.
+// Tiles are generated elsewhere, the cache tells which ones are missing and stores them when they are ready
+
+// Resolution steps along each side of a tile
+const TILE_RESOLUTION: u32 = 256;
+// Number of coarser levels searched for a tile to draw in place of a missing one
+const PLACEHOLDER_LEVELS: i32 = 4;
+// Number of levels coarser than the view of the tiles generated first where there is nothing to draw,
+// they are quicker to generate and show a preview of the plot
+const PREVIEW_LEVELS: i32 = 2;
+// Number of tiles kept for every curve, those of other levels and far from the view are dropped first
+const MAX_TILES: usize = 256;
+
+// The tile at (x, y) on the grid of the given level.
+// Explicit functions are split in columns spanning the whole plane, whose y is always 0
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct TileKey {
+    pub level: i32,
+    pub x: i64,
+    pub y: i64,
+}
+
+impl TileKey {
+    fn size(&self) -> f64 {
+        2.0f64.powi(self.level)
+    }
+
+    // The corners of the tiles are multiples of a power of two, so halving the tiles in the quadtree
+    // gives exactly the same points on the edges shared by adjacent tiles
+    pub fn rectangle(&self, explicit: bool) -> Rectangle {
+        let size = self.size();
+        if explicit {
+            Rectangle { x_start: self.x as f64 * size, y_start: f64::NEG_INFINITY, x_end: (self.x + 1) as f64 * size, y_end: f64::INFINITY }
+        } else {
+            Rectangle { x_start: self.x as f64 * size, y_start: self.y as f64 * size, x_end: (self.x + 1) as f64 * size, y_end: (self.y + 1) as f64 * size }
+        }
+    }
+
+    // The width of the resolution steps of the tile
+    pub fn step(&self) -> f64 {
+        self.size() / TILE_RESOLUTION as f64
+    }
+
+    // The part of the budget of evaluations of the implicit curves of a view given to the tile,
+    // as much as the share of the view it covers, so that the tiles of a view together don't evaluate much more
+    pub fn implicit_budget(&self, view: &Rectangle) -> u32 {
+        let size = self.size();
+        let share = size * size / ((view.x_end - view.x_start) * (view.y_end - view.y_start));
+        (plot_generator2d::MAX_IMPLICIT_EVALUATIONS as f64 * share.min(1.0)).ceil() as u32
+    }
+
+    // The tile a number of levels coarser containing this one
+    fn ancestor(&self, levels: i32) -> TileKey {
+        TileKey { level: self.level + levels, x: self.x >> levels, y: self.y >> levels }
+    }
+
+    // The tiles of the next finer level covering this one
+    fn children(&self, explicit: bool) -> Vec<TileKey> {
+        let level = self.level - 1;
+        if explicit {
+            vec![TileKey { level, x: 2 * self.x, y: 0 }, TileKey { level, x: 2 * self.x + 1, y: 0 }]
+        } else {
+            (0..4).map(|i| TileKey { level, x: 2 * self.x + (i & 1), y: 2 * self.y + (i >> 1) }).collect()
+        }
+    }
+
+    // Distance of the center of the tile from a point, in tiles
+    fn distance(&self, point: (f64, f64), explicit: bool) -> f64 {
+        let size = self.size();
+        let dx = (self.x as f64 + 0.5) - point.0 / size;
+        let dy = if explicit { 0.0 } else { (self.y as f64 + 0.5) - point.1 / size };
+        dx.hypot(dy)
+    }
+}
+
+// The plot of a curve inside a tile, explicit functions are not clipped vertically
+pub struct Tile {
+    pub rectangles: Vec<Rectangle>,
+    pub polylines: Vec<Vec<(f64, f64)>>,
+    // the indices of the rectangles drawn as they are, where there are no polylines
+    pub loose: Vec<usize>,
+    pub asymptotes: Vec<f64>,
+    // true when an implicit curve could not be refined to the resolution of the tile
+    pub budget_exceeded: bool,
+}
+
+impl Tile {
+    // Implicit curves are refined within the budget of evaluations, explicit functions don't need it
+    pub fn generate(expression: &Expression<IntervalSet<f64>>, key: TileKey, budget: u32) -> Tile {
+        let mut generation = TileGeneration::new(expression, key, budget);
+        let mut allowance = u32::MAX;
+        loop {
+            if let Some(tile) = generation.advance(expression, &mut allowance) {
+                return tile;
+            }
+        }
+    }
+}
+
+// A tile generated a few evaluations at a time, so that a heavy tile can be spread over several frames
+pub enum TileGeneration {
+    Explicit(plot_generator2d::ExplicitSampling),
+    Refining(plot_generator2d::ImplicitRefinement),
+    Tracing(plot_generator2d::ImplicitPlot, plot_generator2d::ContourTracing),
+}
+
+impl TileGeneration {
+    pub fn new(expression: &Expression<IntervalSet<f64>>, key: TileKey, budget: u32) -> TileGeneration {
+        match expression.expr_type() {
+            ExprType::Expr2d | ExprType::Constant => {
+                TileGeneration::Explicit(plot_generator2d::ExplicitSampling::new(expression, key.rectangle(true), TILE_RESOLUTION))
+            },
+            ExprType::ExprImplicit => {
+                TileGeneration::Refining(plot_generator2d::ImplicitRefinement::new(key.rectangle(false), TILE_RESOLUTION, budget))
+            },
+            ExprType::Expr3d => panic!("expected 2d expression, found 3d expression"),
+        }
+    }
+
+    // Go on generating the tile spending about the allowance of evaluations, which is decreased by those spent,
+    // the tile once it is complete
+    pub fn advance(&mut self, expression: &Expression<IntervalSet<f64>>, allowance: &mut u32) -> Option<Tile> {
+        loop {
+            match self {
+                TileGeneration::Explicit(sampling) => {
+                    if !sampling.advance(expression, allowance) {
+                        return None;
+                    }
+                    let plot = sampling.polylines();
+                    let rectangles = sampling.rectangles();
+                    // functions defined only in isolated points have no polylines
+                    let loose = if plot.polylines.is_empty() { (0..rectangles.len()).collect() } else { Vec::new() };
+                    return Some(Tile { rectangles, polylines: plot.polylines, loose, asymptotes: plot.asymptotes, budget_exceeded: false });
+                },
+                TileGeneration::Refining(refinement) => {
+                    let plot = refinement.advance(expression, allowance)?;
+                    let tracing = plot_generator2d::ContourTracing::new(plot.rectangles.len());
+                    *self = TileGeneration::Tracing(plot, tracing);
+                },
+                TileGeneration::Tracing(plot, tracing) => {
+                    if !tracing.advance(expression, &plot.rectangles, allowance) {
+                        return None;
+                    }
+                    let (polylines, loose) = tracing.polylines();
+                    let rectangles = std::mem::take(&mut plot.rectangles);
+                    return Some(Tile { rectangles, polylines, loose, asymptotes: Vec::new(), budget_exceeded: plot.budget_exceeded });
+                },
+            }
+        }
+    }
+}
+
+// The tiles drawing the view, some may be placeholders of another level for those still missing
+pub struct Coverage {
+    pub tiles: Vec<TileKey>,
+    pub complete: bool,
+    // the coarser tiles to generate first, where there is nothing to draw
+    pub previews: Vec<TileKey>,
+    // the missing tiles of the view, closest to its center first
+    pub missing: Vec<TileKey>,
+}
+
+// The tiles of the plot of a curve, by level and position
+pub struct TileCache {
+    tiles: HashMap<TileKey, Tile>,
+}
+
+impl TileCache {
+    pub fn new() -> TileCache {
+        TileCache { tiles: HashMap::new() }
+    }
+
+    pub fn get(&self, key: &TileKey) -> &Tile {
+        &self.tiles[key]
+    }
+
+    pub fn insert(&mut self, key: TileKey, tile: Tile) {
+        self.tiles.insert(key, tile);
+    }
+
+    // The level whose tiles have steps between half and one pixel wide
+    fn level(view: &Rectangle, resolution: u32) -> i32 {
+        let pixel = (view.x_end - view.x_start) / resolution as f64;
+        (pixel * TILE_RESOLUTION as f64).log2().floor() as i32
+    }
+
+    // The columns of explicit tiles covering the view, from left to right
+    pub fn columns(view: &Rectangle, resolution: u32) -> Vec<TileKey> {
+        let level = TileCache::level(view, resolution);
+        let size = 2.0f64.powi(level);
+        ((view.x_start / size).floor() as i64..=(view.x_end / size).ceil() as i64 - 1)
+            .map(|x| TileKey { level, x, y: 0 })
+            .collect()
+    }
+
+    // The tiles to draw the view and those missing. Missing tiles are replaced by a coarser tile or by the finer ones covering them
+    pub fn cover(&mut self, expression: &Expression<IntervalSet<f64>>, view: &Rectangle, resolution: u32) -> Coverage {
+        let explicit = match expression.expr_type() {
+            ExprType::Expr2d | ExprType::Constant => true,
+            _ => false,
+        };
+        let level = TileCache::level(view, resolution);
+        let size = 2.0f64.powi(level);
+        let center = ((view.x_start + view.x_end) / 2.0, (view.y_start + view.y_end) / 2.0);
+
+        let x_range = (view.x_start / size).floor() as i64..=(view.x_end / size).ceil() as i64 - 1;
+        let y_range = if explicit { 0..=0 } else { (view.y_start / size).floor() as i64..=(view.y_end / size).ceil() as i64 - 1 };
+        let mut visible: Vec<TileKey> = x_range
+            .flat_map(|x| y_range.clone().map(move |y| TileKey { level, x, y }))
+            .collect();
+        visible.sort_by(|a, b| a.distance(center, explicit).partial_cmp(&b.distance(center, explicit)).unwrap());
+
+        let mut coverage = Coverage { tiles: Vec::with_capacity(visible.len()), complete: true, previews: Vec::new(), missing: Vec::new() };
+        for key in visible.iter() {
+            if self.tiles.contains_key(key) {
+                coverage.tiles.push(*key);
+                continue;
+            }
+            coverage.complete = false;
+            coverage.missing.push(*key);
+
+            let placeholders = match (1..=PLACEHOLDER_LEVELS).map(|levels| key.ancestor(levels)).find(|ancestor| self.tiles.contains_key(ancestor)) {
+                Some(ancestor) => vec![ancestor],
+                None => key.children(explicit).into_iter().filter(|child| self.tiles.contains_key(child)).collect(),
+            };
+            if placeholders.is_empty() {
+                let preview = key.ancestor(PREVIEW_LEVELS);
+                if !coverage.previews.contains(&preview) {
+                    coverage.previews.push(preview);
+                }
+            }
+            for placeholder in placeholders {
+                if !coverage.tiles.contains(&placeholder) {
+                    coverage.tiles.push(placeholder);
+                }
+            }
+        }
+
+        self.evict(&coverage, level, center, explicit);
+        coverage
+    }
+
+    // Drop the tiles not drawn which are farthest from the current level and view
+    fn evict(&mut self, coverage: &Coverage, level: i32, center: (f64, f64), explicit: bool) {
+        if self.tiles.len() <= MAX_TILES {
+            return;
+        }
+
+        let remoteness = |key: &TileKey| {
+            let distance = key.distance(center, explicit) * 2.0f64.powi(key.level - level);
+            ((key.level - level).abs(), distance)
+        };
+        let mut unused: Vec<TileKey> = self.tiles.keys().filter(|key| !coverage.tiles.contains(key)).cloned().collect();
+        unused.sort_by(|a, b| remoteness(b).partial_cmp(&remoteness(a)).unwrap());
+
+        let excess = self.tiles.len() - MAX_TILES;
+        for key in unused.into_iter().take(excess) {
+            self.tiles.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator_tables::parse_interval;
+
+    fn view(x_start: f64, y_start: f64, width: f64) -> Rectangle {
+        Rectangle { x_start, y_start, x_end: x_start + width, y_end: y_start + width }
+    }
+
+    // Cover the view generating all the missing tiles
+    fn cover_all(cache: &mut TileCache, expression: &Expression<IntervalSet<f64>>, view: &Rectangle) -> (Coverage, usize) {
+        let missing = cache.cover(expression, view, 512).missing;
+        for key in missing.iter() {
+            cache.insert(*key, Tile::generate(expression, *key, key.implicit_budget(view)));
+        }
+        (cache.cover(expression, view, 512), missing.len())
+    }
+
+    #[test]
+    fn panning_generates_only_the_exposed_tiles() {
+        let expression = parse_interval("x^2 + y^2 = 4");
+        let mut cache = TileCache::new();
+        // with 512 pixels the tiles are 8 units wide and the view covers 2x2 of them
+        let (coverage, generated) = cover_all(&mut cache, &expression, &view(-8.0, -8.0, 16.0));
+        assert!(coverage.complete);
+        assert_eq!(coverage.tiles.len(), 4);
+        assert_eq!(generated, 4);
+
+        // moving right by half a tile exposes the column on the right
+        let (coverage, generated) = cover_all(&mut cache, &expression, &view(-4.0, -8.0, 16.0));
+        assert!(coverage.complete);
+        assert_eq!(coverage.tiles.len(), 6);
+        assert_eq!(generated, 2);
+    }
+
+    #[test]
+    fn tiles_share_the_budget_of_the_view() {
+        let view = view(-8.0, -8.0, 16.0);
+        let tiles = TileCache::new().cover(&parse_interval("x^2 + y^2 = 4"), &view, 512).missing;
+        let budgets: Vec<u32> = tiles.iter().map(|key| key.implicit_budget(&view)).collect();
+        assert_eq!(budgets, vec![plot_generator2d::MAX_IMPLICIT_EVALUATIONS / 4; 4]);
+
+        // a tile bigger than the view has the whole budget
+        assert_eq!(TileKey { level: 5, x: 0, y: 0 }.implicit_budget(&view), plot_generator2d::MAX_IMPLICIT_EVALUATIONS);
+
+        // a dense curve runs out of the budget of its tile
+        let expression = parse_interval("sin(100*x) = sin(100*y)");
+        let key = TileKey { level: 3, x: 0, y: 0 };
+        let tile = Tile::generate(&expression, key, key.implicit_budget(&view));
+        assert!(tile.budget_exceeded);
+    }
+
+    #[test]
+    fn columns_cover_the_view() {
+        let columns = TileCache::columns(&view(-8.0, -8.0, 16.0), 512);
+        assert_eq!(columns, vec![TileKey { level: 3, x: -1, y: 0 }, TileKey { level: 3, x: 0, y: 0 }]);
+        assert_eq!(columns[0].step(), 8.0 / 256.0);
+
+        // they are those of the explicit tiles
+        let missing = TileCache::new().cover(&parse_interval("sin(x)"), &view(-4.0, -8.0, 16.0), 512).missing;
+        assert_eq!(TileCache::columns(&view(-4.0, -8.0, 16.0), 512), vec![missing[1], missing[0], missing[2]]);
+    }
+
+    #[test]
+    fn coarser_tiles_are_placeholders_for_missing_ones() {
+        let expression = parse_interval("sin(x)");
+        let mut cache = TileCache::new();
+        cover_all(&mut cache, &expression, &view(-8.0, -8.0, 16.0));
+
+        // zooming in by a factor of two halves the tiles
+        let coverage = cache.cover(&expression, &view(-4.0, -4.0, 8.0), 512);
+        assert!(!coverage.complete);
+        assert_eq!(coverage.tiles, vec![TileKey { level: 3, x: -1, y: 0 }, TileKey { level: 3, x: 0, y: 0 }]);
+        assert_eq!(coverage.missing, vec![TileKey { level: 2, x: -1, y: 0 }, TileKey { level: 2, x: 0, y: 0 }]);
+        assert!(coverage.previews.is_empty());
+    }
+
+    #[test]
+    fn coarser_previews_are_generated_first() {
+        let expression = parse_interval("sin(x)");
+        let mut cache = TileCache::new();
+        let coverage = cache.cover(&expression, &view(-8.0, -8.0, 16.0), 512);
+        assert!(coverage.tiles.is_empty());
+        assert_eq!(coverage.missing.len(), 2);
+        assert_eq!(coverage.previews, vec![TileKey { level: 5, x: -1, y: 0 }, TileKey { level: 5, x: 0, y: 0 }]);
+    }
+
+    #[test]
+    fn implicit_curves_meet_on_the_edges_of_the_tiles() {
+        // the circle crosses the four tiles around the origin
+        let expression = parse_interval("x^2 + y^2 = 5");
+        let mut cache = TileCache::new();
+        let (coverage, _) = cover_all(&mut cache, &expression, &view(-8.0, -8.0, 16.0));
+
+        let polylines: Vec<Vec<(f64, f64)>> = coverage.tiles.iter()
+            .flat_map(|key| cache.get(key).polylines.iter().cloned())
+            .collect();
+        assert!(polylines.len() >= 4);
+        assert_eq!(plot_generator2d::join_polylines(polylines).len(), 1);
+    }
+}